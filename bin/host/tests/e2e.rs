@@ -0,0 +1,237 @@
+//! End-to-end runs of the benchmark binary, without APCs, on mainnet blocks fetched from the RPC
+//! endpoint in `RPC_1`. They are skipped when it is not set.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    thread,
+    time::Duration,
+};
+
+const BLOCK_NUMBER: &str = "23992138";
+
+/// Returns an empty directory for the caches and outputs of the test `name`, or `None` if no RPC
+/// endpoint is configured.
+fn test_dir(name: &str) -> Option<PathBuf> {
+    dotenv::dotenv().ok();
+    std::env::var("RPC_1").ok()?;
+    let dir = std::env::temp_dir().join(format!("openvm-reth-benchmark-e2e-{name}"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("output")).unwrap();
+    Some(dir)
+}
+
+/// The benchmark command in `mode`, with its caches in `dir`.
+fn host(dir: &Path, mode: &str) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_openvm-reth-benchmark-bin"));
+    command
+        .args(["--mode", mode, "--chain-id", "1", "--apc", "0", "--apc-skip", "0"])
+        .args(["--pgo-type", "none", "--apc-setup-name", "e2e"])
+        .arg("--apc-cache-dir")
+        .arg(dir.join("apc-cache"))
+        .arg("--cache-dir")
+        .arg(dir.join("rpc-cache"));
+    command
+}
+
+/// Runs `command` and fails if it does not succeed.
+fn run(command: &mut Command) -> Output {
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+/// Returns the lines of the standard output of a run that start with `prefix`.
+fn stdout_lines(output: &Output, prefix: &str) -> Vec<String> {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines().filter(|line| line.starts_with(prefix)).map(str::to_string).collect()
+}
+
+/// Returns the segment proofs checkpointed under `checkpoint_dir`, by path relative to it.
+fn segment_proofs(checkpoint_dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    let mut proofs = BTreeMap::new();
+    let mut dirs = vec![checkpoint_dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.to_string_lossy().ends_with(".proof.bitcode") {
+                let relative = path.strip_prefix(checkpoint_dir).unwrap().to_path_buf();
+                proofs.insert(relative, fs::read(&path).unwrap());
+            }
+        }
+    }
+    proofs
+}
+
+#[test]
+fn test_prove_stark_then_verify_proof() {
+    let Some(dir) = test_dir("verify-proof") else {
+        return;
+    };
+    let output_dir = dir.join("output");
+    let proved = run(host(&dir, "prove-stark")
+        .args(["--block-number", BLOCK_NUMBER, "--skip-comparison"])
+        .arg("--output-dir")
+        .arg(&output_dir));
+    let [proved_hash] = &stdout_lines(&proved, "block_hash (prove_stark): ")[..] else {
+        panic!("expected one proved block hash");
+    };
+    // the reported size is the size of the written proof
+    let proof_size = fs::read_to_string(output_dir.join("proof_size")).unwrap();
+    let proof_len = fs::metadata(output_dir.join("proof.json")).unwrap().len();
+    assert_eq!(proof_size.parse::<u64>().unwrap(), proof_len);
+
+    fs::remove_dir_all(dir.join("rpc-cache")).unwrap();
+    let verified =
+        run(host(&dir, "verify-proof").arg("--proof-path").arg(output_dir.join("proof.json")));
+    // no block was fetched to verify the proof
+    assert!(!dir.join("rpc-cache").exists());
+    assert_eq!(stdout_lines(&verified, "proof verification: "), ["proof verification: pass"]);
+    let [verified_hash] = &stdout_lines(&verified, "block_hash (verify_proof): ")[..] else {
+        panic!("expected one verified block hash");
+    };
+    assert_eq!(
+        verified_hash.strip_prefix("block_hash (verify_proof): "),
+        proved_hash.strip_prefix("block_hash (prove_stark): ")
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_execute_block_range() {
+    let Some(dir) = test_dir("block-range") else {
+        return;
+    };
+    let comparison_json = dir.join("output/comparison.json");
+    let timings_json = dir.join("output/timings.json");
+    let output = run(host(&dir, "execute")
+        .args(["--block-range", "23992138..23992140"])
+        .arg("--comparison-json")
+        .arg(&comparison_json)
+        .arg("--timings-json")
+        .arg(&timings_json));
+
+    // both blocks are executed on the host and in the VM, with agreeing hashes
+    assert_eq!(stdout_lines(&output, "block_hash (execute): ").len(), 2);
+    let comparisons: serde_json::Value =
+        serde_json::from_slice(&fs::read(&comparison_json).unwrap()).unwrap();
+    let comparisons = comparisons.as_array().unwrap();
+    assert_eq!(comparisons.len(), 2);
+    for (comparison, block_number) in comparisons.iter().zip([23992138, 23992139]) {
+        assert_eq!(comparison["block_number"], block_number);
+        assert_eq!(comparison["match"], true);
+        assert!(comparison["execute"].is_string());
+        assert_eq!(comparison["host"], comparison["execute"]);
+    }
+    assert_ne!(comparisons[0]["execute"], comparisons[1]["execute"]);
+
+    // the setup is timed once, and each block is fetched and timed on its own
+    let timings: serde_json::Value =
+        serde_json::from_slice(&fs::read(&timings_json).unwrap()).unwrap();
+    assert!(timings["elf_decode"].is_number());
+    assert!(timings["create_sdk"].is_number());
+    for block_number in [23992138, 23992139] {
+        assert!(timings[format!("get_client_input_block_{block_number}")].is_number());
+        assert!(timings[format!("execute_block_{block_number}")].is_number());
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_prove_app_resume_from_checkpoint() {
+    let Some(dir) = test_dir("checkpoint") else {
+        return;
+    };
+    let prove_app = |checkpoint_dir: &Path| {
+        let mut command = host(&dir, "prove-app");
+        command
+            .args(["--block-number", BLOCK_NUMBER, "--skip-comparison"])
+            .args(["--max-segment-length", "1048576"])
+            .arg("--checkpoint-dir")
+            .arg(checkpoint_dir);
+        command
+    };
+
+    let one_shot_dir = dir.join("one-shot");
+    run(&mut prove_app(&one_shot_dir));
+    let one_shot = segment_proofs(&one_shot_dir);
+    assert!(one_shot.len() > 1, "expected several segments, got {}", one_shot.len());
+
+    // kill a run as soon as its first segment proof is checkpointed
+    let resumed_dir = dir.join("resumed");
+    let mut child = prove_app(&resumed_dir).spawn().unwrap();
+    while segment_proofs(&resumed_dir).is_empty() {
+        assert!(child.try_wait().unwrap().is_none(), "exited before any segment was checkpointed");
+        thread::sleep(Duration::from_millis(100));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let checkpointed = segment_proofs(&resumed_dir).len();
+    assert!(checkpointed < one_shot.len(), "all segments were proven before the kill");
+
+    let resumed = run(&mut prove_app(&resumed_dir));
+    assert_eq!(stdout_lines(&resumed, "Resuming segment ").len(), checkpointed);
+    assert_eq!(segment_proofs(&resumed_dir), one_shot);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_execute_profile_total_matches_instret() {
+    let Some(dir) = test_dir("execute-profile") else {
+        return;
+    };
+    let total = |mode: &str| {
+        let output = run(host(&dir, mode).args(["--block-number", BLOCK_NUMBER]));
+        let [total] = &stdout_lines(&output, "Total executed instructions: ")[..] else {
+            panic!("expected one instruction count in {mode} mode");
+        };
+        total.strip_prefix("Total executed instructions: ").unwrap().parse::<u64>().unwrap()
+    };
+
+    // without APCs, the profiled program is the one executed in the VM
+    let profiled = total("execute-profile");
+    assert!(profiled > 0);
+    assert_eq!(profiled, total("execute-metered"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_execute_count_instructions() {
+    let Some(dir) = test_dir("count-instructions") else {
+        return;
+    };
+    let execute = |extra_args: &[&str]| {
+        run(host(&dir, "execute")
+            .args(["--block-number", BLOCK_NUMBER, "--skip-comparison"])
+            .args(extra_args))
+    };
+
+    // the timed execution runs without the comparison, and the count is opt-in
+    let output = execute(&[]);
+    assert_eq!(stdout_lines(&output, "block_hash (execute): ").len(), 1);
+    assert!(stdout_lines(&output, "Total executed instructions: ").is_empty());
+
+    let count = || {
+        let output = execute(&["--count-instructions"]);
+        let [count] = &stdout_lines(&output, "Total executed instructions: ")[..] else {
+            panic!("expected one instruction count");
+        };
+        count.strip_prefix("Total executed instructions: ").unwrap().parse::<u64>().unwrap()
+    };
+    let first = count();
+    assert!(first > 0);
+    assert_eq!(count(), first);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -10,12 +10,19 @@ use openvm_sdk::{
     SC,
 };
 use openvm_stark_sdk::{
-    config::baby_bear_poseidon2::BabyBearPoseidon2Engine,
+    config::{baby_bear_poseidon2::BabyBearPoseidon2Engine, FriParameters},
     engine::{StarkEngine, StarkFriEngine},
-    openvm_stark_backend::{proof::Proof, prover::hal::DeviceDataTransporter},
+    openvm_stark_backend::{
+        keygen::types::MultiStarkVerifyingKey, proof::Proof, prover::hal::DeviceDataTransporter,
+    },
 };
 
 use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -23,11 +30,76 @@ struct Args {
     skip_leaf: bool,
     #[clap(long, default_value = "false")]
     skip_internal: bool,
+    /// Write the timings of each verifier stage to this path as JSON.
+    #[clap(long)]
+    json: Option<PathBuf>,
+    /// Number of app proofs aggregated by each leaf verifier.
+    #[clap(long, default_value_t = DEFAULT_NUM_CHILDREN_LEAF, value_parser = parse_num_children)]
+    num_children_leaf: usize,
+    /// Number of leaf or internal proofs aggregated by each internal verifier.
+    #[clap(long, default_value_t = DEFAULT_NUM_CHILDREN_INTERNAL, value_parser = parse_num_children)]
+    num_children_internal: usize,
+    /// After preflight, run the STARK verifier on the app and leaf proofs of the fixtures, which
+    /// are the inputs of the aggregation. The proofs the aggregation would output are not checked.
+    #[clap(long, default_value = "false")]
+    verify_inputs: bool,
+    /// Directory containing the fixtures, defaults to `fixtures/` in this crate.
+    #[clap(long)]
+    fixtures_dir: Option<PathBuf>,
+}
+
+fn parse_num_children(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(num_children) => Ok(num_children),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Runs the STARK verifier on each of `proofs`, returning whether all of them are valid.
+fn verify_proofs(
+    fri_params: FriParameters,
+    vk: &MultiStarkVerifyingKey<SC>,
+    proofs: &[Proof<SC>],
+) -> bool {
+    let engine = BabyBearPoseidon2Engine::new(fri_params);
+    proofs.iter().all(|proof| engine.verify(vk, proof).is_ok())
+}
+
+/// Number of verifier chunks needed to aggregate `num_proofs` proofs, `num_children` at a time.
+fn num_chunks(num_proofs: usize, num_children: usize) -> usize {
+    num_proofs.div_ceil(num_children)
+}
+
+/// Preflight timings of one verifier stage, in seconds.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct StageTimings {
+    chunks: Vec<f64>,
+    total_secs: f64,
+}
+
+impl StageTimings {
+    fn record_chunk(&mut self, elapsed: Duration) {
+        self.chunks.push(elapsed.as_secs_f64());
+    }
+}
+
+/// Timings of the verifier stages that ran, written by `--json`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct VerifierTimings {
+    leaf: Option<StageTimings>,
+    internal: Option<StageTimings>,
 }
 
 fn main() {
     let args = Args::parse();
-    let Fixtures { app_proof, leaf_proofs, app_pk, agg_pk } = read_fixtures();
+    let mut timings = VerifierTimings::default();
+    let fixtures_dir = args.fixtures_dir.clone().unwrap_or_else(default_fixtures_dir);
+    let Fixtures { app_proof, leaf_proofs, app_pk, agg_pk } = read_fixtures(&fixtures_dir)
+        .unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(1);
+        });
     let AggProvingKey { leaf_vm_pk, internal_vm_pk, internal_committed_exe, .. } = agg_pk;
     if !args.skip_leaf {
         let start = std::time::Instant::now();
@@ -38,8 +110,10 @@ fn main() {
         let leaf_exe = app_pk.leaf_committed_exe.exe.clone();
         let mut interpreter = vm.preflight_interpreter(&leaf_exe).unwrap();
         let num_app_proofs = app_proof.per_segment.len();
+        let mut leaf_timings = StageTimings::default();
         let leaf_inputs =
-            LeafVmVerifierInput::chunk_continuation_vm_proof(&app_proof, DEFAULT_NUM_CHILDREN_LEAF);
+            LeafVmVerifierInput::chunk_continuation_vm_proof(&app_proof, args.num_children_leaf);
+        debug_assert_eq!(leaf_inputs.len(), num_chunks(num_app_proofs, args.num_children_leaf));
         for (i, leaf_input) in leaf_inputs.into_iter().enumerate() {
             let start = std::time::Instant::now();
             let input_stream = leaf_input.write_to_stream();
@@ -48,12 +122,16 @@ fn main() {
                 .execute_preflight(&mut interpreter, state, None, NATIVE_MAX_TRACE_HEIGHTS)
                 .expect("Failed to execute preflight");
             println!("end pc {}", out.to_state.pc());
-            println!("Time to aggregate app proof chunk {i}, {}s", start.elapsed().as_secs_f64());
+            let elapsed = start.elapsed();
+            println!("Time to aggregate app proof chunk {i}, {}s", elapsed.as_secs_f64());
+            leaf_timings.record_chunk(elapsed);
         }
+        leaf_timings.total_secs = start.elapsed().as_secs_f64();
         println!(
             "Preflight execution leaf verifier to aggregate {num_app_proofs} app proofs, {}s",
-            start.elapsed().as_secs_f64()
+            leaf_timings.total_secs
         );
+        timings.leaf = Some(leaf_timings);
     }
     if !args.skip_internal {
         let start = std::time::Instant::now();
@@ -65,10 +143,15 @@ fn main() {
         let internal_exe = internal_committed_exe.exe.clone();
         let mut interpreter = vm.preflight_interpreter(&internal_exe).unwrap();
         let num_leaf_proofs = leaf_proofs.len();
+        let mut internal_timings = StageTimings::default();
         let internal_inputs = InternalVmVerifierInput::chunk_leaf_or_internal_proofs(
             internal_committed_exe.get_program_commit().into(),
             &leaf_proofs,
-            DEFAULT_NUM_CHILDREN_INTERNAL,
+            args.num_children_internal,
+        );
+        debug_assert_eq!(
+            internal_inputs.len(),
+            num_chunks(num_leaf_proofs, args.num_children_internal)
         );
         for (i, internal_proof) in internal_inputs.into_iter().enumerate() {
             let start = std::time::Instant::now();
@@ -78,12 +161,42 @@ fn main() {
                 .execute_preflight(&mut interpreter, state, None, NATIVE_MAX_TRACE_HEIGHTS)
                 .expect("Failed to execute preflight");
             println!("end pc {}", out.to_state.pc());
-            println!("Time to aggregate leaf proof chunk {i}, {}s", start.elapsed().as_secs_f64());
+            let elapsed = start.elapsed();
+            println!("Time to aggregate leaf proof chunk {i}, {}s", elapsed.as_secs_f64());
+            internal_timings.record_chunk(elapsed);
         }
+        internal_timings.total_secs = start.elapsed().as_secs_f64();
         println!(
             "Preflight execution for internal verifier to aggregate {num_leaf_proofs} leaf proofs, {}s",
-            start.elapsed().as_secs_f64()
+            internal_timings.total_secs
         );
+        timings.internal = Some(internal_timings);
+    }
+    if args.verify_inputs {
+        let stages = [
+            (
+                "app",
+                verify_proofs(
+                    app_pk.app_vm_pk.fri_params,
+                    &app_pk.app_vm_pk.vm_pk.get_vk(),
+                    &app_proof.per_segment,
+                ),
+            ),
+            (
+                "leaf",
+                verify_proofs(leaf_vm_pk.fri_params, &leaf_vm_pk.vm_pk.get_vk(), &leaf_proofs),
+            ),
+        ];
+        for (stage, passed) in stages {
+            println!("Verification of {stage} proofs: {}", if passed { "PASS" } else { "FAIL" });
+        }
+        if stages.iter().any(|(_, passed)| !passed) {
+            std::process::exit(1);
+        }
+    }
+    if let Some(path) = &args.json {
+        std::fs::write(path, serde_json::to_vec_pretty(&timings).unwrap()).unwrap();
+        println!("Wrote timings to {}", path.display());
     }
 }
 
@@ -94,31 +207,101 @@ struct Fixtures {
     agg_pk: AggProvingKey,
 }
 
-fn read_fixtures() -> Fixtures {
-    let app_proof: ContinuationVmProof<SC> = {
-        let content =
-            std::fs::read(format!("{}/fixtures/app_proof.bitcode", env!("CARGO_MANIFEST_DIR")))
-                .unwrap();
-        bitcode::deserialize(&content).unwrap()
-    };
-    let leaf_proofs: Vec<Proof<SC>> = {
-        let content =
-            std::fs::read(format!("{}/fixtures/leaf_proofs.bitcode", env!("CARGO_MANIFEST_DIR")))
-                .unwrap();
-        bitcode::deserialize(&content).unwrap()
-    };
-    let app_pk: AppProvingKey<SdkVmConfig> = {
-        let content =
-            std::fs::read(format!("{}/fixtures/app_pk.bitcode", env!("CARGO_MANIFEST_DIR")))
-                .unwrap();
-        bitcode::deserialize(&content).unwrap()
-    };
-    let agg_pk: AggProvingKey = {
-        let content =
-            std::fs::read(format!("{}/fixtures/agg_pk.bitcode", env!("CARGO_MANIFEST_DIR")))
-                .unwrap();
-        bitcode::deserialize(&content).unwrap()
-    };
+fn default_fixtures_dir() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures"))
+}
+
+fn read_fixture<T: serde::de::DeserializeOwned>(dir: &Path, name: &str) -> Result<T, String> {
+    let path = dir.join(name);
+    let content = std::fs::read(&path)
+        .map_err(|err| format!("Failed to read fixture {}: {err}", path.display()))?;
+    bitcode::deserialize(&content)
+        .map_err(|err| format!("Failed to deserialize fixture {}: {err}", path.display()))
+}
+
+fn read_fixtures(dir: &Path) -> Result<Fixtures, String> {
+    Ok(Fixtures {
+        app_proof: read_fixture(dir, "app_proof.bitcode")?,
+        leaf_proofs: read_fixture(dir, "leaf_proofs.bitcode")?,
+        app_pk: read_fixture(dir, "app_pk.bitcode")?,
+        agg_pk: read_fixture(dir, "agg_pk.bitcode")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_num_children() {
+        assert_eq!(parse_num_children("2"), Ok(2));
+        assert!(parse_num_children("0").is_err());
+        assert!(Args::try_parse_from(["verifier-bench", "--num-children-leaf", "0"]).is_err());
+
+        let args =
+            Args::try_parse_from(["verifier-bench", "--num-children-internal", "1"]).unwrap();
+        assert_eq!(args.num_children_leaf, DEFAULT_NUM_CHILDREN_LEAF);
+        assert_eq!(args.num_children_internal, 1);
+
+        assert_eq!(num_chunks(7, 1), 7);
+        assert_eq!(num_chunks(7, 3), 3);
+        assert_eq!(num_chunks(6, 3), 2);
+        assert_eq!(num_chunks(1, 4), 1);
+    }
+
+    #[test]
+    fn test_verify_leaf_proofs() {
+        // Only runs when the fixtures have been downloaded with `fixtures.sh`.
+        if !default_fixtures_dir().exists() {
+            return;
+        }
+        let Fixtures { leaf_proofs, agg_pk, .. } = read_fixtures(&default_fixtures_dir()).unwrap();
+        let leaf_vm_pk = agg_pk.leaf_vm_pk;
+        let vk = leaf_vm_pk.vm_pk.get_vk();
+        assert!(verify_proofs(leaf_vm_pk.fri_params, &vk, &leaf_proofs));
+    }
+
+    #[test]
+    fn test_leaf_chunks() {
+        // Only runs when the fixtures have been downloaded with `fixtures.sh`.
+        let Ok(app_proof) =
+            read_fixture::<ContinuationVmProof<SC>>(&default_fixtures_dir(), "app_proof.bitcode")
+        else {
+            return;
+        };
+        let num_app_proofs = app_proof.per_segment.len();
+
+        let leaf_inputs = LeafVmVerifierInput::chunk_continuation_vm_proof(&app_proof, 1);
+        assert_eq!(leaf_inputs.len(), num_app_proofs);
+        let leaf_inputs = LeafVmVerifierInput::chunk_continuation_vm_proof(&app_proof, 3);
+        assert_eq!(leaf_inputs.len(), num_chunks(num_app_proofs, 3));
+    }
+
+    #[test]
+    fn test_verifier_timings_json() {
+        let mut leaf = StageTimings::default();
+        leaf.record_chunk(Duration::from_millis(1500));
+        leaf.record_chunk(Duration::from_millis(250));
+        leaf.total_secs = 2.0;
+        let timings = VerifierTimings { leaf: Some(leaf), internal: None };
 
-    Fixtures { app_proof, leaf_proofs, app_pk, agg_pk }
+        let json = serde_json::to_value(&timings).unwrap();
+        assert_eq!(json["leaf"]["chunks"], serde_json::json!([1.5, 0.25]));
+        assert_eq!(json["leaf"]["total_secs"], 2.0);
+        assert!(json["internal"].is_null());
+
+        let decoded: VerifierTimings = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, timings);
+    }
+
+    #[test]
+    fn test_missing_fixture() {
+        let dir = std::env::temp_dir().join("openvm-reth-benchmark-missing-fixtures");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let err = read_fixtures(&dir).err().unwrap();
+        assert!(err.contains(&dir.join("app_proof.bitcode").display().to_string()), "{err}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,11 +1,14 @@
 use alloy_consensus::crypto::RecoveryError;
 use alloy_primitives::BlockNumber;
 use reth_consensus::ConsensusError;
-use reth_evm::block::BlockExecutionError;
-use revm_primitives::B256;
+use reth_evm::{block::BlockExecutionError, execute::ProviderError};
+use revm_primitives::{Address, B256};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ClientExecutionError {
+    #[error("no ancestor headers: the input needs at least the parent header")]
+    MissingParentHeader,
+
     #[error("parent state root mismatch: got {actual}, expected {expected}")]
     ParentStateRootMismatch { actual: B256, expected: B256 },
 
@@ -30,12 +33,41 @@ pub enum ClientExecutionError {
     #[error("block post-execution validation failed: {0}")]
     InvalidBlockPostExecution(ConsensusError),
 
+    #[error("block hash of block {0} is read by the block, but older than the ancestor headers")]
+    MissingBlockHash(BlockNumber),
+
     #[error("block execution failed: {0}")]
     BlockExecutionError(#[from] BlockExecutionError),
 
-    #[error("state root mismatch: got {actual}, expected {expected}")]
-    StateRootMismatch { actual: B256, expected: B256 },
+    #[error("duplicate bytecode with code hash {code_hash}")]
+    DuplicateBytecode { code_hash: B256 },
+
+    #[error("state root mismatch: got {actual}, expected {expected}, first differing hashed account {hashed_account:?}")]
+    StateRootMismatch { actual: B256, expected: B256, hashed_account: Option<B256> },
 
     #[error("MPT error: {0}")]
     MptError(#[from] openvm_mpt::Error),
 }
+
+/// Data missing from an incomplete witness, found while executing against a
+/// [`WitnessDb`](crate::io::WitnessDb).
+#[derive(thiserror::Error, Debug)]
+pub enum WitnessDbError {
+    #[error("no storage trie for account {0}")]
+    MissingStorageTrie(Address),
+
+    #[error("no block hash for block number {0}")]
+    MissingBlockHash(BlockNumber),
+
+    #[error("no bytecode with code hash {0}")]
+    MissingBytecode(B256),
+
+    #[error("MPT error: {0}")]
+    MptError(#[from] openvm_mpt::Error),
+}
+
+impl From<WitnessDbError> for ProviderError {
+    fn from(err: WitnessDbError) -> Self {
+        Self::other(err)
+    }
+}
@@ -1,6 +1,6 @@
-use std::iter::once;
+use std::{cell::Cell, iter::once};
 
-use crate::error::ClientExecutionError;
+use crate::error::{ClientExecutionError, WitnessDbError};
 use bumpalo::Bump;
 use itertools::Itertools;
 use openvm_mpt::{EthereumState, EthereumStateBytes, Mpt};
@@ -11,12 +11,26 @@ use revm::{
     state::{AccountInfo, Bytecode},
     DatabaseRef,
 };
-use revm_primitives::{keccak256, map::DefaultHashBuilder, Address, HashMap, B256, U256};
+use revm_primitives::{keccak256, map::DefaultHashBuilder, Address, HashMap, HashSet, B256, U256};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
-/// Bump area size in bytes.
-const BUMP_AREA_SIZE: usize = 1000 * 1000;
+/// Minimum bump area size in bytes.
+const MIN_BUMP_AREA_SIZE: usize = 1000 * 1000;
+
+/// Bytes of serialized parent state per byte of bump area. The tries borrow their nodes from the
+/// input, so the arena only holds the updated nodes and the digests computed when hashing, which
+/// grow with the size of the state, but stay well below it.
+const STATE_BYTES_PER_BUMP_BYTE: usize = 2;
+
+/// Returns the initial size of the arena of the state of `input`: proportional to the size of the
+/// serialized parent state, and at least [`MIN_BUMP_AREA_SIZE`].
+fn bump_area_size(input: &ClientExecutorInput) -> usize {
+    let state_bytes = &input.parent_state_bytes;
+    let state_len = state_bytes.state_trie.1.len() +
+        state_bytes.storage_tries.iter().map(|(_, _, bytes)| bytes.len()).sum::<usize>();
+    (state_len / STATE_BYTES_PER_BUMP_BYTE).max(MIN_BUMP_AREA_SIZE)
+}
 
 /// The input for the client to execute a block and fully verify the STF (state transition
 /// function).
@@ -38,32 +52,77 @@ pub struct ClientExecutorInput {
     pub bytecodes: Vec<Bytecode>,
 }
 
+impl ClientExecutorInput {
+    /// Removes the bytecodes whose code hash is the same as an earlier one, which the guest would
+    /// otherwise read and hash again for nothing.
+    pub fn dedup_bytecodes(&mut self) {
+        let mut code_hashes =
+            HashSet::with_capacity_and_hasher(self.bytecodes.len(), DefaultHashBuilder::default());
+        self.bytecodes.retain(|code| code_hashes.insert(code.hash_slow()));
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct ClientExecutorInputWithState {
-    pub input: &'static ClientExecutorInput,
-    pub state: EthereumState,
+pub struct ClientExecutorInputWithState<'a> {
+    pub input: &'a ClientExecutorInput,
+    pub state: EthereumState<'a>,
 }
 
-impl ClientExecutorInputWithState {
+impl ClientExecutorInputWithState<'static> {
     /// Parses `input.parent_state_bytes` into `EthereumState` and verifies state and storage roots.
+    ///
+    /// The input and the arena of the state are leaked, see [`Self::build_in`] to reuse them.
     pub fn build(input: ClientExecutorInput) -> Result<Self, ClientExecutionError> {
+        let bump = Box::leak(Box::new(Bump::with_capacity(bump_area_size(&input))));
         let input = Box::leak(Box::new(input));
-        let bump = Box::leak(Box::new(Bump::with_capacity(BUMP_AREA_SIZE)));
+        Self::build_in(input, bump)
+    }
+}
+
+impl<'a> ClientExecutorInputWithState<'a> {
+    /// Same as [`ClientExecutorInputWithState::build`], but borrows `input` and allocates the
+    /// tries in `bump`, which can be reset and reused once the state is dropped.
+    pub fn build_in(
+        input: &'a ClientExecutorInput,
+        bump: &'a Bump,
+    ) -> Result<Self, ClientExecutionError> {
+        Self::build_inner(input, bump, false)
+    }
 
+    /// Same as [`Self::build_in`], but only verifies the state root. The storage tries stay
+    /// serialized until the execution first reads them, see
+    /// [`EthereumState::insert_lazy_storage_trie`], which lowers the peak memory when many of them
+    /// are never read. Their storage roots are verified when they are decoded.
+    pub fn build_lazy_in(
+        input: &'a ClientExecutorInput,
+        bump: &'a Bump,
+    ) -> Result<Self, ClientExecutionError> {
+        Self::build_inner(input, bump, true)
+    }
+
+    fn build_inner(
+        input: &'a ClientExecutorInput,
+        bump: &'a Bump,
+        lazy: bool,
+    ) -> Result<Self, ClientExecutionError> {
+        let Some(parent_header) = input.ancestor_headers.first() else {
+            return Err(ClientExecutionError::MissingParentHeader);
+        };
         let state = {
             let (state_num_nodes, state_bytes) = &input.parent_state_bytes.state_trie;
             let state_trie = Mpt::decode_trie(bump, &mut state_bytes.as_ref(), *state_num_nodes)?;
-            if state_trie.hash() != input.ancestor_headers[0].state_root {
+            if state_trie.hash() != parent_header.state_root {
                 return Err(ClientExecutionError::ParentStateRootMismatch {
                     actual: state_trie.hash(),
-                    expected: input.ancestor_headers[0].state_root,
+                    expected: parent_header.state_root,
                 });
             }
 
             let mut storage_tries = HashMap::with_capacity_and_hasher(
-                input.parent_state_bytes.storage_tries.len(),
+                if lazy { 0 } else { input.parent_state_bytes.storage_tries.len() },
                 DefaultHashBuilder::default(),
             );
+            let mut lazy_storage_tries = Vec::new();
             for (hashed_address, num_nodes, storage_trie_bytes) in
                 &input.parent_state_bytes.storage_tries
             {
@@ -72,6 +131,16 @@ impl ClientExecutorInputWithState {
                 let expected_storage_root =
                     account_in_trie.map_or(reth_trie::EMPTY_ROOT_HASH, |a| a.storage_root);
 
+                if lazy {
+                    lazy_storage_tries.push((
+                        *hashed_address,
+                        *num_nodes,
+                        storage_trie_bytes.as_ref(),
+                        expected_storage_root,
+                    ));
+                    continue;
+                }
+
                 let storage_trie =
                     Mpt::decode_trie(bump, &mut storage_trie_bytes.as_ref(), *num_nodes)?;
                 if storage_trie.hash() != expected_storage_root {
@@ -85,29 +154,33 @@ impl ClientExecutorInputWithState {
                 storage_tries.insert(*hashed_address, storage_trie);
             }
 
-            EthereumState { state_trie, storage_tries, bump }
+            let mut state = EthereumState::from_tries_in(state_trie, storage_tries, bump);
+            for (hashed_address, num_nodes, bytes, expected_root) in lazy_storage_tries {
+                state.insert_lazy_storage_trie(hashed_address, num_nodes, bytes, expected_root);
+            }
+            state
         };
 
         Ok(Self { input, state })
     }
 }
 
-impl ClientExecutorInputWithState {
-    /// Gets the immediate parent block's header.
+impl<'a> ClientExecutorInputWithState<'a> {
+    /// Gets the immediate parent block's header, which the build checked is present.
     #[inline(always)]
     pub fn parent_header(&self) -> &Header {
         &self.input.ancestor_headers[0]
     }
 
     /// Creates a [`WitnessDb`].
-    pub fn witness_db(&self) -> Result<WitnessDb<'_>, ClientExecutionError> {
-        <Self as WitnessInput>::witness_db(self)
+    pub fn witness_db(&self) -> Result<WitnessDb<'_, 'a>, ClientExecutionError> {
+        <Self as WitnessInput<'a>>::witness_db(self)
     }
 }
 
-impl WitnessInput for ClientExecutorInputWithState {
+impl<'a> WitnessInput<'a> for ClientExecutorInputWithState<'a> {
     #[inline(always)]
-    fn state(&self) -> &EthereumState {
+    fn state(&self) -> &EthereumState<'a> {
         &self.state
     }
 
@@ -132,10 +205,10 @@ impl WitnessInput for ClientExecutorInputWithState {
     }
 }
 
-/// A trait for constructing [`WitnessDb`].
-pub trait WitnessInput {
+/// A trait for constructing [`WitnessDb`]. `'a` is the lifetime of the data of the state.
+pub trait WitnessInput<'a> {
     /// Gets a reference to the state from which account info and storage slots are loaded.
-    fn state(&self) -> &EthereumState;
+    fn state(&self) -> &EthereumState<'a>;
 
     /// Gets the state trie root hash that the state referenced by
     /// [state()](trait.WitnessInput#tymethod.state) must conform to.
@@ -159,11 +232,16 @@ pub trait WitnessInput {
     /// implementing this trait causes a zkVM run to cost over 5M cycles more. To avoid this, define
     /// a method inside the type that calls this trait method instead.
     #[inline(always)]
-    fn witness_db(&self) -> Result<WitnessDb<'_>, ClientExecutionError> {
+    fn witness_db(&self) -> Result<WitnessDb<'_, 'a>, ClientExecutionError> {
         let state = self.state();
 
-        let bytecode_by_hash =
-            self.bytecodes().map(|code| (code.hash_slow(), code)).collect::<HashMap<_, _>>();
+        let mut bytecode_by_hash = HashMap::default();
+        for code in self.bytecodes() {
+            let code_hash = code.hash_slow();
+            if bytecode_by_hash.insert(code_hash, code).is_some() {
+                return Err(ClientExecutionError::DuplicateBytecode { code_hash });
+            }
+        }
 
         // Verify and build block hashes
         let mut block_hashes: HashMap<u64, B256, _> =
@@ -187,28 +265,76 @@ pub trait WitnessInput {
             block_hashes.insert(parent_header.number, child_header.parent_hash);
         }
 
-        Ok(WitnessDb { inner: state, block_hashes, bytecode_by_hash })
+        Ok(WitnessDb::new(state, block_hashes, bytecode_by_hash))
     }
 }
 
+/// A database reading accounts and storage from the tries of an [`EthereumState`] borrowed for
+/// `'a`, whose data lives for `'s`.
 #[derive(Debug)]
-pub struct WitnessDb<'a> {
-    inner: &'a EthereumState,
+pub struct WitnessDb<'a, 's> {
+    inner: &'a EthereumState<'s>,
     block_hashes: HashMap<u64, B256>,
     bytecode_by_hash: HashMap<B256, &'a Bytecode>,
+    /// The last block number whose hash was read but is not in `block_hashes`.
+    missing_block_hash: Cell<Option<u64>>,
 }
 
-impl<'a> WitnessDb<'a> {
+impl<'a, 's> WitnessDb<'a, 's> {
     pub fn new(
-        inner: &'a EthereumState,
+        inner: &'a EthereumState<'s>,
         block_hashes: HashMap<u64, B256>,
         bytecode_by_hash: HashMap<B256, &'a Bytecode>,
     ) -> Self {
-        Self { inner, block_hashes, bytecode_by_hash }
+        Self { inner, block_hashes, bytecode_by_hash, missing_block_hash: Cell::new(None) }
     }
+
+    /// Starts building a database reading from `inner`, with the block hashes and bytecodes added
+    /// to the builder. Unlike [`WitnessInput::witness_db`], nothing is checked against headers,
+    /// which lets tests read accounts and storage without a full [`ClientExecutorInput`].
+    pub fn builder(inner: &'a EthereumState<'s>) -> WitnessDbBuilder<'a, 's> {
+        WitnessDbBuilder {
+            inner,
+            block_hashes: HashMap::default(),
+            bytecode_by_hash: HashMap::default(),
+        }
+    }
+
+    /// Returns the last block number whose hash the execution read, with `BLOCKHASH`, but which is
+    /// older than the ancestor headers of the input. The EVM only reports a database error then.
+    pub fn missing_block_hash(&self) -> Option<u64> {
+        self.missing_block_hash.get()
+    }
+}
+
+/// Builder of a [`WitnessDb`], see [`WitnessDb::builder`].
+#[derive(Debug)]
+pub struct WitnessDbBuilder<'a, 's> {
+    inner: &'a EthereumState<'s>,
+    block_hashes: HashMap<u64, B256>,
+    bytecode_by_hash: HashMap<B256, &'a Bytecode>,
 }
 
-impl DatabaseRef for WitnessDb<'_> {
+impl<'a, 's> WitnessDbBuilder<'a, 's> {
+    /// Adds the hash of block `number`.
+    pub fn block_hash(mut self, number: u64, block_hash: B256) -> Self {
+        self.block_hashes.insert(number, block_hash);
+        self
+    }
+
+    /// Adds a bytecode, under its code hash.
+    pub fn bytecode(mut self, code: &'a Bytecode) -> Self {
+        self.bytecode_by_hash.insert(code.hash_slow(), code);
+        self
+    }
+
+    /// Builds the database.
+    pub fn build(self) -> WitnessDb<'a, 's> {
+        WitnessDb::new(self.inner, self.block_hashes, self.bytecode_by_hash)
+    }
+}
+
+impl DatabaseRef for WitnessDb<'_, '_> {
     /// The database error type.
     type Error = ProviderError;
 
@@ -216,8 +342,11 @@ impl DatabaseRef for WitnessDb<'_> {
     fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
         let hashed_address = keccak256(address);
 
-        let account_in_trie =
-            self.inner.state_trie.get_rlp::<TrieAccount>(hashed_address.as_slice()).unwrap();
+        let account_in_trie = self
+            .inner
+            .state_trie
+            .get_rlp::<TrieAccount>(hashed_address.as_slice())
+            .map_err(WitnessDbError::from)?;
 
         let account = account_in_trie.map(|account_in_trie| AccountInfo {
             balance: account_in_trie.balance,
@@ -232,7 +361,8 @@ impl DatabaseRef for WitnessDb<'_> {
     /// Get account code by its hash.
     fn code_by_hash_ref(&self, hash: B256) -> Result<Bytecode, Self::Error> {
         // Cloning here is fine as `Bytes` is cheap to clone.
-        Ok(self.bytecode_by_hash.get(&hash).map(|code| (*code).clone()).unwrap())
+        let code = self.bytecode_by_hash.get(&hash).ok_or(WitnessDbError::MissingBytecode(hash))?;
+        Ok((*code).clone())
     }
 
     /// Get storage value of address at index.
@@ -241,22 +371,238 @@ impl DatabaseRef for WitnessDb<'_> {
 
         let storage_trie = self
             .inner
-            .storage_tries
-            .get(&hashed_address)
-            .expect("A storage trie must be provided for each account");
+            .storage_trie(&hashed_address)
+            .map_err(WitnessDbError::from)?
+            .ok_or(WitnessDbError::MissingStorageTrie(address))?;
 
         let hashed_slot = keccak256(index.to_be_bytes::<32>());
-        Ok(storage_trie
-            .get_rlp::<U256>(hashed_slot.as_slice())
-            .expect("Can get from MPT")
-            .unwrap_or_default())
+        let mut value = U256::ZERO;
+        storage_trie
+            .get_rlp_into(hashed_slot.as_slice(), &mut value)
+            .map_err(WitnessDbError::from)?;
+        Ok(value)
     }
 
     /// Get block hash by block number.
     fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
-        Ok(*self
-            .block_hashes
-            .get(&number)
-            .expect("A block hash must be provided for each block number"))
+        match self.block_hashes.get(&number) {
+            Some(block_hash) => Ok(*block_hash),
+            None => {
+                self.missing_block_hash.set(Some(number));
+                Err(WitnessDbError::MissingBlockHash(number).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::database::BundleState;
+
+    /// Builds the input of a block whose parent state holds `num_accounts` accounts with one
+    /// storage slot each.
+    fn synthetic_input(block_number: u64, num_accounts: u64) -> ClientExecutorInput {
+        let mut builder = BundleState::builder(0..=0);
+        for i in 1..=num_accounts {
+            let address = Address::left_padding_from(&i.to_be_bytes());
+            let info =
+                AccountInfo { balance: U256::from(block_number), nonce: i, ..Default::default() };
+            let storage: HashMap<U256, (U256, U256)> =
+                HashMap::from_iter([(U256::from(i), (U256::ZERO, U256::from(block_number)))]);
+            builder =
+                builder.state_present_account_info(address, info).state_storage(address, storage);
+        }
+        let mut state = EthereumState::new();
+        state.update_from_bundle_state(&builder.build()).unwrap();
+
+        let parent_header = Header {
+            number: block_number - 1,
+            state_root: state.state_trie.hash(),
+            ..Default::default()
+        };
+        let header = Header {
+            number: block_number,
+            parent_hash: parent_header.hash_slow(),
+            ..Default::default()
+        };
+        ClientExecutorInput {
+            current_block: Block { header, ..Default::default() },
+            ancestor_headers: vec![parent_header],
+            parent_state_bytes: state.encode_to_state_bytes(),
+            bytecodes: vec![],
+        }
+    }
+
+    #[test]
+    fn test_dedup_bytecodes() {
+        let code = Bytecode::new_raw(vec![0x60, 0x00, 0x60, 0x00, 0xf3].into());
+        let other = Bytecode::new_raw(vec![0x00].into());
+        let mut input = synthetic_input(1, 4);
+        input.bytecodes = vec![code.clone(), other.clone(), code.clone()];
+
+        let bump = Bump::new();
+        let built = ClientExecutorInputWithState::build_in(&input, &bump).unwrap();
+        assert!(matches!(
+            built.witness_db(),
+            Err(ClientExecutionError::DuplicateBytecode { code_hash }) if code_hash == code.hash_slow()
+        ));
+
+        input.dedup_bytecodes();
+        assert_eq!(input.bytecodes, [code.clone(), other]);
+        let built = ClientExecutorInputWithState::build_in(&input, &bump).unwrap();
+        let db = built.witness_db().unwrap();
+        assert_eq!(db.code_by_hash_ref(code.hash_slow()).unwrap(), code);
+    }
+
+    #[test]
+    fn test_witness_db_incomplete_witness() {
+        let input = synthetic_input(2, 4);
+        let bump = Bump::new();
+        let built = ClientExecutorInputWithState::build_in(&input, &bump).unwrap();
+        let db = built.witness_db().unwrap();
+        let assert_err = |err: ProviderError, expected: WitnessDbError| {
+            assert_eq!(err.to_string(), expected.to_string());
+        };
+
+        let address = Address::left_padding_from(&99u64.to_be_bytes());
+        assert_eq!(db.basic_ref(address).unwrap(), None);
+        assert_err(
+            db.storage_ref(address, U256::from(1)).unwrap_err(),
+            WitnessDbError::MissingStorageTrie(address),
+        );
+
+        assert_eq!(db.block_hash_ref(1).unwrap(), input.current_block.header.parent_hash);
+        assert_err(db.block_hash_ref(0).unwrap_err(), WitnessDbError::MissingBlockHash(0));
+
+        let code_hash = B256::repeat_byte(0xc0);
+        assert_err(
+            db.code_by_hash_ref(code_hash).unwrap_err(),
+            WitnessDbError::MissingBytecode(code_hash),
+        );
+    }
+
+    #[test]
+    fn test_witness_db_builder() {
+        let address = Address::left_padding_from(&1u64.to_be_bytes());
+        let code = Bytecode::new_raw(vec![0x60, 0x01, 0x60, 0x00, 0x55].into());
+        let info = AccountInfo {
+            balance: U256::from(7),
+            nonce: 1,
+            code_hash: code.hash_slow(),
+            code: Some(code.clone()),
+        };
+        let bundle_state = BundleState::builder(0..=0)
+            .state_present_account_info(address, info)
+            .state_storage(
+                address,
+                HashMap::from_iter([(U256::from(3), (U256::ZERO, U256::from(4)))]),
+            )
+            .build();
+        let mut state = EthereumState::new();
+        state.update_from_bundle_state(&bundle_state).unwrap();
+
+        let block_hash = B256::repeat_byte(0xbb);
+        let db = WitnessDb::builder(&state).block_hash(41, block_hash).bytecode(&code).build();
+
+        let account = db.basic_ref(address).unwrap().unwrap();
+        assert_eq!((account.balance, account.nonce), (U256::from(7), 1));
+        assert_eq!(db.storage_ref(address, U256::from(3)).unwrap(), U256::from(4));
+        assert_eq!(db.code_by_hash_ref(account.code_hash).unwrap(), code);
+        assert_eq!(db.block_hash_ref(41).unwrap(), block_hash);
+        assert!(db.block_hash_ref(40).is_err());
+    }
+
+    #[test]
+    fn test_witness_db_missing_block_hash() {
+        // the ancestors of block 10 only go back to block 8
+        let mut input = synthetic_input(10, 4);
+        let grandparent = Header { number: 8, ..Default::default() };
+        input.ancestor_headers[0].parent_hash = grandparent.hash_slow();
+        input.ancestor_headers.push(grandparent);
+        input.current_block.header.parent_hash = input.ancestor_headers[0].hash_slow();
+        let bump = Bump::new();
+        let built = ClientExecutorInputWithState::build_in(&input, &bump).unwrap();
+        let db = built.witness_db().unwrap();
+
+        assert!(db.block_hash_ref(9).is_ok());
+        assert!(db.block_hash_ref(8).is_ok());
+        assert_eq!(db.missing_block_hash(), None);
+
+        // BLOCKHASH(7) is within the 256 block window of block 10
+        let err = db.block_hash_ref(7).unwrap_err();
+        assert_eq!(err.to_string(), WitnessDbError::MissingBlockHash(7).to_string());
+        assert_eq!(db.missing_block_hash(), Some(7));
+    }
+
+    #[test]
+    fn test_build_without_ancestor_headers() {
+        let mut input = synthetic_input(1, 4);
+        input.ancestor_headers.clear();
+        let bump = Bump::new();
+        for lazy in [false, true] {
+            let built = ClientExecutorInputWithState::build_inner(&input, &bump, lazy);
+            assert!(matches!(built, Err(ClientExecutionError::MissingParentHeader)));
+        }
+    }
+
+    #[test]
+    fn test_bump_area_size() {
+        assert_eq!(bump_area_size(&synthetic_input(1, 4)), MIN_BUMP_AREA_SIZE);
+
+        let mut large = synthetic_input(1, 4);
+        let state_len = 16 * MIN_BUMP_AREA_SIZE;
+        large.parent_state_bytes.state_trie.1 = vec![0; state_len].into();
+        assert!(bump_area_size(&large) >= state_len / STATE_BYTES_PER_BUMP_BYTE);
+        assert!(bump_area_size(&large) > MIN_BUMP_AREA_SIZE);
+    }
+
+    #[test]
+    fn test_build_in_reused_arena() {
+        let inputs = [synthetic_input(1, 16), synthetic_input(2, 32)];
+        let mut bump = Bump::new();
+        for input in &inputs {
+            bump.reset();
+            let built = ClientExecutorInputWithState::build_in(input, &bump).unwrap();
+            assert_eq!(built.state.state_trie.hash(), built.parent_header().state_root);
+            assert_eq!(
+                built.state.storage_tries.len(),
+                input.parent_state_bytes.storage_tries.len()
+            );
+
+            let db = built.witness_db().unwrap();
+            let account = db.basic_ref(Address::left_padding_from(&1u64.to_be_bytes())).unwrap();
+            assert_eq!(account.unwrap().balance, U256::from(input.current_block.header.number));
+        }
+    }
+
+    #[test]
+    fn test_build_lazy_in_decodes_on_access() {
+        let input = synthetic_input(1, 16);
+        let address = |i: u64| Address::left_padding_from(&i.to_be_bytes());
+        let bump = Bump::new();
+        let built = ClientExecutorInputWithState::build_lazy_in(&input, &bump).unwrap();
+        assert!(built.state.storage_tries.is_empty());
+        assert_eq!(built.state.num_encoded_storage_tries(), 16);
+
+        // reading the storage of one account only decodes its trie
+        let db = built.witness_db().unwrap();
+        assert_eq!(db.storage_ref(address(3), U256::from(3)).unwrap(), U256::from(1));
+        assert_eq!(db.storage_ref(address(3), U256::from(4)).unwrap(), U256::ZERO);
+        drop(db);
+        assert_eq!(built.state.num_encoded_storage_tries(), 15);
+
+        // updating an account decodes its trie too, and gives the same root as the eager state
+        let bundle_state = BundleState::builder(1..=1)
+            .state_present_account_info(address(5), AccountInfo::default())
+            .build();
+        let mut lazy = built.state;
+        lazy.update_from_bundle_state(&bundle_state).unwrap();
+        assert_eq!(lazy.num_encoded_storage_tries(), 14);
+
+        let eager_bump = Bump::new();
+        let mut eager = ClientExecutorInputWithState::build_in(&input, &eager_bump).unwrap().state;
+        eager.update_from_bundle_state(&bundle_state).unwrap();
+        assert_eq!(lazy.state_trie.hash(), eager.state_trie.hash());
     }
 }
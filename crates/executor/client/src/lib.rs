@@ -6,15 +6,20 @@ use std::{fmt::Debug, sync::Arc};
 
 use alloy_consensus::TxReceipt;
 use alloy_primitives::Bloom;
-use openvm_primitives::chain_spec::{dev, mainnet};
+use bumpalo::Bump;
+use openvm_mpt::EthereumState;
+use openvm_primitives::chain_spec::{dev, holesky, mainnet, sepolia};
+use reth_chainspec::ChainSpec;
 use reth_consensus::{Consensus, HeaderValidator};
 use reth_ethereum_consensus::{validate_block_post_execution, EthBeaconConsensus};
+use reth_ethereum_primitives::Receipt;
 use reth_evm::execute::{BasicBlockExecutor, Executor};
 use reth_evm_ethereum::EthEvmConfig;
 use reth_execution_types::ExecutionOutcome;
 use reth_primitives::Header;
 use reth_primitives_traits::block::Block as _;
 use reth_revm::db::CacheDB;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     error::ClientExecutionError,
@@ -24,6 +29,41 @@ use crate::{
 /// Chain ID for Ethereum Mainnet.
 pub const CHAIN_ID_ETH_MAINNET: u64 = 0x1;
 
+/// Chain ID for the Sepolia testnet.
+pub const CHAIN_ID_SEPOLIA: u64 = 11155111;
+
+/// Chain ID for the Holesky testnet.
+pub const CHAIN_ID_HOLESKY: u64 = 17000;
+
+/// The chain IDs with a [ChainVariant], see [ChainVariant::from_chain_id].
+pub const SUPPORTED_CHAIN_IDS: [u64; 3] =
+    [CHAIN_ID_ETH_MAINNET, CHAIN_ID_SEPOLIA, CHAIN_ID_HOLESKY];
+
+/// Lightweight summary of an executed block, derived from its receipts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSummary {
+    /// Total gas used by the transactions of the block.
+    pub gas_used: u64,
+    /// Number of receipts, one per transaction.
+    pub num_receipts: usize,
+    /// Number of logs emitted by all the transactions.
+    pub num_logs: usize,
+    /// Whether any transaction reverted.
+    pub any_reverted: bool,
+}
+
+impl BlockSummary {
+    /// Summarizes the receipts of the transactions of a single block.
+    pub fn from_receipts(receipts: &[Receipt]) -> Self {
+        Self {
+            gas_used: receipts.last().map_or(0, |receipt| receipt.cumulative_gas_used()),
+            num_receipts: receipts.len(),
+            num_logs: receipts.iter().map(|receipt| receipt.logs().len()).sum(),
+            any_reverted: receipts.iter().any(|receipt| !receipt.status()),
+        }
+    }
+}
+
 /// An executor that executes a block inside a zkVM.
 #[derive(Debug, Clone, Default)]
 pub struct ClientExecutor;
@@ -32,44 +72,175 @@ pub struct ClientExecutor;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ChainVariant {
     Mainnet,
+    Sepolia,
+    Holesky,
     Dev,
 }
 
+impl ChainVariant {
+    /// Returns the variant for a public chain ID, or `None` if the chain is not supported.
+    pub fn from_chain_id(chain_id: u64) -> Option<Self> {
+        match chain_id {
+            CHAIN_ID_ETH_MAINNET => Some(Self::Mainnet),
+            CHAIN_ID_SEPOLIA => Some(Self::Sepolia),
+            CHAIN_ID_HOLESKY => Some(Self::Holesky),
+            _ => None,
+        }
+    }
+
+    /// Returns the [ChainSpec] used to execute blocks of this variant.
+    pub fn chain_spec(&self) -> ChainSpec {
+        match self {
+            Self::Mainnet => mainnet(),
+            Self::Sepolia => sepolia(),
+            Self::Holesky => holesky(),
+            Self::Dev => dev(),
+        }
+    }
+}
+
 impl ClientExecutor {
     pub fn execute(
         &self,
         chain_variant: ChainVariant,
         pre_input: ClientExecutorInput,
     ) -> Result<Header, ClientExecutionError> {
-        let mut input = ClientExecutorInputWithState::build(pre_input)?;
+        self.execute_with_outcome(chain_variant, pre_input).map(|(header, _)| header)
+    }
+
+    /// Executes the block and also returns the [ExecutionOutcome] used to update the state trie.
+    pub fn execute_with_outcome(
+        &self,
+        chain_variant: ChainVariant,
+        pre_input: ClientExecutorInput,
+    ) -> Result<(Header, ExecutionOutcome), ClientExecutionError> {
+        self.execute_inner(Arc::new(chain_variant.chain_spec()), pre_input, true)
+    }
+
+    /// Executes the block and also returns a [BlockSummary] of its receipts.
+    pub fn execute_with_summary(
+        &self,
+        chain_variant: ChainVariant,
+        pre_input: ClientExecutorInput,
+    ) -> Result<(Header, BlockSummary), ClientExecutionError> {
+        let (header, outcome) = self.execute_with_outcome(chain_variant, pre_input)?;
+        Ok((header, BlockSummary::from_receipts(&outcome.receipts[0])))
+    }
+
+    /// Executes the block without any consensus validation, for profiling the EVM execution only.
+    ///
+    /// **This is unsound for proving.** The header is not validated, neither are the receipts,
+    /// gas used and requests after execution, and the post state root is not recomputed. The
+    /// header of the input is returned as-is. Only the parent state and ancestor headers are still
+    /// verified, as they are needed to build the witness database.
+    pub fn execute_unchecked(
+        &self,
+        chain_variant: ChainVariant,
+        pre_input: ClientExecutorInput,
+    ) -> Result<Header, ClientExecutionError> {
+        self.execute_inner(Arc::new(chain_variant.chain_spec()), pre_input, false)
+            .map(|(header, _)| header)
+    }
+
+    /// Executes the block with an arbitrary [ChainSpec], e.g. one loaded from a genesis file.
+    pub fn execute_with_spec(
+        &self,
+        spec: Arc<ChainSpec>,
+        pre_input: ClientExecutorInput,
+    ) -> Result<Header, ClientExecutionError> {
+        self.execute_inner(spec, pre_input, true).map(|(header, _)| header)
+    }
+
+    /// Same as [`Self::execute_with_spec`], but borrows `pre_input` and builds its state in
+    /// `bump`, which the caller can reset and reuse for the next block instead of leaking one
+    /// arena per block.
+    pub fn execute_with_spec_in(
+        &self,
+        spec: Arc<ChainSpec>,
+        pre_input: &ClientExecutorInput,
+        bump: &Bump,
+    ) -> Result<Header, ClientExecutionError> {
+        let input = ClientExecutorInputWithState::build_in(pre_input, bump)?;
+        self.execute_built(spec, input, true, None).map(|(header, _)| header)
+    }
+
+    /// Same as [`Self::execute_with_spec_in`], but builds the state with
+    /// [`ClientExecutorInputWithState::build_lazy_in`], so that only the storage tries the block
+    /// reads or updates are decoded into `bump`.
+    pub fn execute_with_spec_lazy_in(
+        &self,
+        spec: Arc<ChainSpec>,
+        pre_input: &ClientExecutorInput,
+        bump: &Bump,
+    ) -> Result<Header, ClientExecutionError> {
+        let input = ClientExecutorInputWithState::build_lazy_in(pre_input, bump)?;
+        self.execute_built(spec, input, true, None).map(|(header, _)| header)
+    }
+
+    /// Same as [`Self::execute_with_spec`], but a [`ClientExecutionError::StateRootMismatch`]
+    /// names the first account whose post state differs from `expected`, e.g. the state built by
+    /// the host from the proofs at the block.
+    pub fn execute_with_expected_state(
+        &self,
+        spec: Arc<ChainSpec>,
+        pre_input: ClientExecutorInput,
+        expected: &EthereumState<'_>,
+    ) -> Result<Header, ClientExecutionError> {
+        let input = ClientExecutorInputWithState::build(pre_input)?;
+        self.execute_built(spec, input, true, Some(expected)).map(|(header, _)| header)
+    }
+
+    /// Executes the block, validating it and its post state root only if `validate` is set.
+    fn execute_inner(
+        &self,
+        spec: Arc<ChainSpec>,
+        pre_input: ClientExecutorInput,
+        validate: bool,
+    ) -> Result<(Header, ExecutionOutcome), ClientExecutionError> {
+        let input = ClientExecutorInputWithState::build(pre_input)?;
+        self.execute_built(spec, input, validate, None)
+    }
 
-        // Install OpenVM crypto optimizations
+    /// Same as [`Self::execute_inner`], with the state of the input already built. A post state
+    /// root mismatch is diagnosed against `expected_state` if given.
+    fn execute_built(
+        &self,
+        spec: Arc<ChainSpec>,
+        mut input: ClientExecutorInputWithState<'_>,
+        validate: bool,
+        expected_state: Option<&EthereumState<'_>>,
+    ) -> Result<(Header, ExecutionOutcome), ClientExecutionError> {
+        // Install OpenVM crypto optimizations, only for the first executed block
         #[cfg(feature = "openvm")]
-        {
-            println!("Installing OpenVM crypto optimizations");
-            openvm_revm_crypto::install_openvm_crypto()
-                .expect("failed to install OpenVM crypto provider");
+        if let Some(report) = openvm_revm_crypto::install_openvm_crypto_once() {
+            println!("Installed OpenVM crypto optimizations");
+            if !report.alloy_provider {
+                println!("Alloy crypto provider was already installed");
+            }
+            if !report.revm_crypto {
+                println!("REVM crypto was already installed");
+            }
         }
 
         // Initialize the witnessed database with verified storage proofs.
         let witness_db = input.witness_db()?;
         let cache_db = CacheDB::new(&witness_db);
 
-        // Execute the block.
-        let spec = Arc::new(match chain_variant {
-            ChainVariant::Mainnet => mainnet(),
-            ChainVariant::Dev => dev(),
-        });
         // Recover senders
+        #[cfg(not(any(feature = "openvm", feature = "parallel")))]
         let current_block = input
             .input
             .current_block
             .clone()
             .try_into_recovered()
             .map_err(|err| ClientExecutionError::BlockSenderRecoveryError(err.into()))?;
+        #[cfg(all(feature = "parallel", not(feature = "openvm")))]
+        let current_block = recover_block_parallel(input.input.current_block.clone())?;
+        #[cfg(feature = "openvm")]
+        let current_block = recover_block_batched(input.input.current_block.clone())?;
 
         // validate the block pre-execution
-        {
+        if validate {
             let consensus = EthBeaconConsensus::new(spec.clone());
 
             consensus
@@ -79,19 +250,27 @@ impl ClientExecutor {
             consensus
                 .validate_block_pre_execution(&current_block)
                 .map_err(ClientExecutionError::InvalidBlockPreExecution)?;
-        };
+        }
 
         let block_executor = BasicBlockExecutor::new(EthEvmConfig::new(spec.clone()), cache_db);
-        let executor_output = block_executor.execute(&current_block)?;
+        let executor_output = block_executor.execute(&current_block).map_err(|err| {
+            // The EVM only sees a database error, so tell which ancestor header is missing
+            match witness_db.missing_block_hash() {
+                Some(block_number) => ClientExecutionError::MissingBlockHash(block_number),
+                None => err.into(),
+            }
+        })?;
 
         // Validate the block post execution.
-        validate_block_post_execution(
-            &current_block,
-            &spec,
-            &executor_output.receipts,
-            &executor_output.requests,
-        )
-        .map_err(ClientExecutionError::InvalidBlockPostExecution)?;
+        if validate {
+            validate_block_post_execution(
+                &current_block,
+                &spec,
+                &executor_output.receipts,
+                &executor_output.requests,
+            )
+            .map_err(ClientExecutionError::InvalidBlockPostExecution)?;
+        }
 
         // Accumulate the logs bloom.
         let mut logs_bloom = Bloom::default();
@@ -109,6 +288,10 @@ impl ClientExecutor {
 
         drop(witness_db);
 
+        if !validate {
+            return Ok((input.input.current_block.header.clone(), executor_outcome));
+        }
+
         // Verify the state root.
         let state_root = {
             input.state.update_from_bundle_state(&executor_outcome.bundle)?;
@@ -119,6 +302,8 @@ impl ClientExecutor {
             return Err(ClientExecutionError::StateRootMismatch {
                 actual: state_root,
                 expected: input.input.current_block.state_root,
+                hashed_account: expected_state
+                    .and_then(|expected| input.state.diff_account(expected)),
             });
         }
 
@@ -135,6 +320,106 @@ impl ClientExecutor {
         header.logs_bloom = logs_bloom;
         header.requests_hash = input.input.current_block.requests_hash;
 
-        Ok(header)
+        Ok((header, executor_outcome))
+    }
+}
+
+/// Recovers the block senders in one batch with the OpenVM k256 backend.
+///
+/// Equivalent to [`Block::try_into_recovered`](reth_primitives_traits::Block::try_into_recovered),
+/// including the EIP-2 rejection of high-s signatures.
+#[cfg(feature = "openvm")]
+fn recover_block_batched(
+    block: reth_primitives::Block,
+) -> Result<
+    reth_primitives_traits::block::RecoveredBlock<reth_primitives::Block>,
+    ClientExecutionError,
+> {
+    let mut items = Vec::with_capacity(block.body.transactions.len());
+    for tx in &block.body.transactions {
+        let signature = tx.signature();
+        if signature.normalize_s().is_some() {
+            return Err(alloy_consensus::crypto::RecoveryError::new().into());
+        }
+
+        // Same `r || s || v` layout that alloy passes to the crypto provider
+        let mut sig = [0u8; 65];
+        sig[..32].copy_from_slice(&signature.r().to_be_bytes::<32>());
+        sig[32..64].copy_from_slice(&signature.s().to_be_bytes::<32>());
+        sig[64] = signature.v() as u8;
+        items.push((sig, tx.signature_hash().0));
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    let senders =
+        openvm_revm_crypto::recover_signers_unchecked(items.iter().map(|(sig, msg)| (sig, msg)))?;
+    #[cfg(feature = "parallel")]
+    let senders = {
+        use rayon::prelude::*;
+
+        let chunk_size = items.len().div_ceil(rayon::current_num_threads()).max(1);
+        let chunks = items
+            .par_chunks(chunk_size)
+            .map(|chunk| {
+                openvm_revm_crypto::recover_signers_unchecked(
+                    chunk.iter().map(|(sig, msg)| (sig, msg)),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        chunks.into_iter().flatten().collect()
+    };
+    Ok(reth_primitives_traits::block::RecoveredBlock::new_unhashed(block, senders))
+}
+
+/// Recovers the block senders across the rayon thread pool.
+///
+/// Produces the same [`RecoveredBlock`](reth_primitives_traits::block::RecoveredBlock) as
+/// [`Block::try_into_recovered`](reth_primitives_traits::Block::try_into_recovered).
+#[cfg(feature = "parallel")]
+pub fn recover_block_parallel(
+    block: reth_primitives::Block,
+) -> Result<
+    reth_primitives_traits::block::RecoveredBlock<reth_primitives::Block>,
+    ClientExecutionError,
+> {
+    use rayon::prelude::*;
+    use reth_primitives_traits::SignerRecoverable;
+
+    let senders = block
+        .body
+        .transactions
+        .par_iter()
+        .map(|tx| tx.recover_signer())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(reth_primitives_traits::block::RecoveredBlock::new_unhashed(block, senders))
+}
+
+#[cfg(all(test, feature = "openvm"))]
+mod tests {
+    use super::*;
+    use reth_primitives::Block;
+
+    /// The input of the first block on top of an empty state. It executes under the Frontier
+    /// rules, which need neither system contracts nor witnessed accounts.
+    fn empty_block_input() -> ClientExecutorInput {
+        let state = EthereumState::new();
+        let parent_header = Header { state_root: state.state_trie.hash(), ..Default::default() };
+        let header =
+            Header { number: 1, parent_hash: parent_header.hash_slow(), ..Default::default() };
+        ClientExecutorInput {
+            current_block: Block { header, ..Default::default() },
+            ancestor_headers: vec![parent_header],
+            parent_state_bytes: state.encode_to_state_bytes(),
+            bytecodes: vec![],
+        }
+    }
+
+    #[test]
+    fn test_execute_installs_crypto_once() {
+        // the first execution installs through the one-shot guard, so that the next ones skip it
+        ClientExecutor.execute_unchecked(ChainVariant::Mainnet, empty_block_input()).unwrap();
+        assert_eq!(openvm_revm_crypto::install_openvm_crypto_once(), None);
+        ClientExecutor.execute_unchecked(ChainVariant::Mainnet, empty_block_input()).unwrap();
+        assert_eq!(openvm_revm_crypto::install_openvm_crypto_once(), None);
     }
 }
@@ -5,6 +5,9 @@ use alloy_primitives::Bloom;
 use alloy_provider::{network::Ethereum, Provider};
 use eyre::{eyre, Ok};
 use openvm_client_executor::io::ClientExecutorInput;
+#[cfg(feature = "parallel")]
+use openvm_mpt::from_proof::par_transition_proofs_to_tries as transition_proofs_to_tries;
+#[cfg(not(feature = "parallel"))]
 use openvm_mpt::from_proof::transition_proofs_to_tries;
 use openvm_primitives::account_proof::eip1186_proof_to_account_proof;
 use openvm_rpc_db::RpcDb;
@@ -17,19 +20,30 @@ use reth_execution_types::ExecutionOutcome;
 use reth_primitives::Block;
 use reth_primitives_traits::block::Block as _;
 use revm::database::CacheDB;
-use revm_primitives::B256;
+use revm_primitives::{keccak256, HashMap, B256};
 
 /// An executor that fetches data from a [Provider] to execute blocks in the [ClientExecutor].
 #[derive(Debug, Clone)]
 pub struct HostExecutor<P: Provider<Ethereum> + Clone> {
     /// The provider which fetches data.
     pub provider: P,
+    /// Whether to name the first differing account when the state root does not match, see
+    /// [`HostExecutor::with_state_root_diagnosis`].
+    pub diagnose_state_root: bool,
 }
 
 impl<P: Provider<Ethereum> + Clone + std::fmt::Debug> HostExecutor<P> {
     /// Create a new [`HostExecutor`] with a specific [Provider] and [Transport].
     pub fn new(provider: P) -> Self {
-        Self { provider }
+        Self { provider, diagnose_state_root: false }
+    }
+
+    /// Sets whether to diagnose a state root mismatch of the executed block, by naming the first
+    /// account that differs from the tries of the proofs at the block. This updates a copy of the
+    /// state a second time for every block, so it is off by default.
+    pub fn with_state_root_diagnosis(mut self, diagnose_state_root: bool) -> Self {
+        self.diagnose_state_root = diagnose_state_root;
+        self
     }
 
     /// Executes the block with the given block number.
@@ -144,15 +158,40 @@ impl<P: Provider<Ethereum> + Clone + std::fmt::Debug> HostExecutor<P> {
             after_storage_proofs.push(eip1186_proof_to_account_proof(storage_proof));
         }
 
+        let after_proofs: HashMap<_, _> =
+            after_storage_proofs.iter().map(|item| (item.address, item.clone())).collect();
         let state = transition_proofs_to_tries(
             previous_block.state_root,
             &before_storage_proofs.iter().map(|item| (item.address, item.clone())).collect(),
-            &after_storage_proofs.iter().map(|item| (item.address, item.clone())).collect(),
+            &after_proofs,
         )?;
 
         // Skip state root verification for now.
         // It works with Alchemy but for some reason not with Quicknode.
         // It is checked on the client (guest) side and works with all providers.
+        // A mismatch is only diagnosed here if enabled, by naming the first account that differs
+        // from the tries of the proofs at the current block.
+        if self.diagnose_state_root {
+            let mut post_state = state.clone();
+            post_state.update_from_bundle_state(&executor_outcome.bundle)?;
+            if post_state.state_trie.hash() != current_block.state_root {
+                let expected = transition_proofs_to_tries(
+                    current_block.state_root,
+                    &after_proofs,
+                    &after_proofs,
+                )?;
+                let hashed_address = post_state.diff_account(&expected);
+                let address = hashed_address
+                    .and_then(|hashed| state_requests.keys().find(|a| keccak256(a) == hashed));
+                tracing::warn!(
+                    "state root mismatch: got {}, expected {}, first differing account {:?} ({:?})",
+                    post_state.state_trie.hash(),
+                    current_block.state_root,
+                    address,
+                    hashed_address,
+                );
+            }
+        }
 
         // Derive the block header.
         //
@@ -190,12 +229,13 @@ impl<P: Provider<Ethereum> + Clone + std::fmt::Debug> HostExecutor<P> {
         let state_bytes = state.encode_to_state_bytes();
 
         // Create the client input.
-        let client_input = ClientExecutorInput {
+        let mut client_input = ClientExecutorInput {
             current_block,
             ancestor_headers,
             parent_state_bytes: state_bytes,
             bytecodes: rpc_db.get_bytecodes(),
         };
+        client_input.dedup_bytecodes();
         tracing::info!("successfully generated client input");
 
         Ok(client_input)
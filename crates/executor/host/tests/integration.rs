@@ -1,37 +1,132 @@
+use alloy_primitives::{keccak256, U256};
 use alloy_provider::RootProvider;
 use bincode::config::standard;
-use openvm_client_executor::{io::ClientExecutorInput, ChainVariant, ClientExecutor};
+use bumpalo::Bump;
+use openvm_client_executor::{
+    error::ClientExecutionError,
+    io::{ClientExecutorInput, ClientExecutorInputWithState},
+    recover_block_parallel, ChainVariant, ClientExecutor,
+};
 use openvm_host_executor::HostExecutor;
+use reth_primitives_traits::Block as _;
+use revm::{database::BundleState, state::AccountInfo};
+use std::sync::{Arc, OnceLock};
 use tracing_subscriber::{
     filter::EnvFilter, fmt, prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt,
 };
 use url::Url;
 
-#[tokio::test(flavor = "multi_thread")]
-async fn test_e2e_ethereum() {
-    let env_var_key = "RPC_1";
-    let block_number = 23992138;
+const MAINNET_BLOCK_NUMBER: u64 = 23992138;
 
-    // Initialize the environment variables.
+#[test]
+fn test_e2e_ethereum() {
+    run_e2e(ChainVariant::Mainnet, mainnet_input());
+}
+
+#[test]
+fn test_e2e_sepolia() {
+    // Only runs when a Sepolia RPC endpoint is configured.
     dotenv::dotenv().ok();
+    if std::env::var("RPC_11155111").is_err() {
+        return;
+    }
+    run_e2e(ChainVariant::Sepolia, fetch_input("RPC_11155111", 9000000));
+}
 
-    // Initialize the logger.
-    let _ = tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::from_default_env())
-        .try_init();
+#[test]
+fn test_execute_with_outcome() {
+    let client_input = mainnet_input();
+    let num_transactions = client_input.current_block.body.transactions.len();
+    let (_, outcome) = ClientExecutor
+        .execute_with_outcome(ChainVariant::Mainnet, client_input)
+        .expect("failed to execute client");
+    assert_eq!(outcome.receipts.len(), 1);
+    assert_eq!(outcome.receipts[0].len(), num_transactions);
+}
 
-    // Setup the provider.
-    let rpc_url =
-        Url::parse(std::env::var(env_var_key).unwrap().as_str()).expect("invalid rpc url");
-    let provider = RootProvider::new_http(rpc_url);
+#[test]
+fn test_recover_block_parallel() {
+    // Parallel sender recovery must match the serial path
+    let client_input = mainnet_input();
+    let serial = client_input.current_block.clone().try_into_recovered().unwrap();
+    let parallel = recover_block_parallel(client_input.current_block).unwrap();
+    assert!(serial.body().transactions.len() > 1);
+    assert_eq!(serial.senders(), parallel.senders());
+}
+
+#[test]
+fn test_execute_unchecked() {
+    // Skipping the validation must not change the header of a valid block
+    let header = ClientExecutor.execute(ChainVariant::Mainnet, mainnet_input()).unwrap();
+    let unchecked = ClientExecutor
+        .execute_unchecked(ChainVariant::Mainnet, mainnet_input())
+        .expect("failed to execute client without validation");
+    assert_eq!(unchecked.hash_slow(), header.hash_slow());
+}
+
+#[test]
+fn test_execute_with_summary() {
+    let client_input = mainnet_input();
+    let num_transactions = client_input.current_block.body.transactions.len();
+    let (header, summary) = ClientExecutor
+        .execute_with_summary(ChainVariant::Mainnet, client_input)
+        .expect("failed to execute client with summary");
+    assert_eq!(summary.gas_used, header.gas_used);
+    assert_eq!(summary.num_receipts, num_transactions);
+}
+
+#[test]
+fn test_execute_lazy() {
+    // Decoding only the storage tries the block accesses must give the same header
+    let client_input = mainnet_input();
+    let spec = Arc::new(ChainVariant::Mainnet.chain_spec());
+    let header = ClientExecutor.execute_with_spec(spec.clone(), client_input.clone()).unwrap();
+    let bump = Bump::new();
+    let lazy = ClientExecutor
+        .execute_with_spec_lazy_in(spec, &client_input, &bump)
+        .expect("failed to execute client with a lazy state");
+    assert_eq!(lazy.hash_slow(), header.hash_slow());
+}
 
-    // Setup the host executor.
-    let host_executor = HostExecutor::new(provider);
+#[test]
+fn test_state_root_mismatch_names_account() {
+    let mut client_input = mainnet_input();
+    let spec = Arc::new(ChainVariant::Mainnet.chain_spec());
+    let (_, outcome) =
+        ClientExecutor.execute_with_outcome(ChainVariant::Mainnet, client_input.clone()).unwrap();
 
-    // Execute the host.
-    let client_input = host_executor.execute(block_number).await.expect("failed to execute host");
+    // the state after the block, with a wrong balance for one of the accounts it updates
+    let parent_input = client_input.clone();
+    let bump = Bump::new();
+    let mut expected = ClientExecutorInputWithState::build_in(&parent_input, &bump).unwrap().state;
+    expected.update_from_bundle_state(&outcome.bundle).unwrap();
+    let (tampered, info) = outcome
+        .bundle
+        .state
+        .iter()
+        .find_map(|(address, account)| Some((*address, account.info.clone()?)))
+        .unwrap();
+    let bundle_state = BundleState::builder(0..=0)
+        .state_present_account_info(
+            tampered,
+            AccountInfo { balance: info.balance + U256::from(1), ..info },
+        )
+        .build();
+    expected.update_from_bundle_state(&bundle_state).unwrap();
+    client_input.current_block.header.state_root = expected.state_trie.hash();
 
+    let err = ClientExecutor
+        .execute_with_expected_state(spec, client_input, &expected)
+        .expect_err("the state root of the block was tampered with");
+    match err {
+        ClientExecutionError::StateRootMismatch { hashed_account, .. } => {
+            assert_eq!(hashed_account, Some(keccak256(tampered)));
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+fn run_e2e(chain_variant: ChainVariant, client_input: ClientExecutorInput) {
     // Setup the client executor.
     let client_executor = ClientExecutor;
 
@@ -42,10 +137,44 @@ async fn test_e2e_ethereum() {
         bincode::serde::decode_from_slice(&buffer, bincode_config).unwrap();
 
     // Execute the client with the original input
-    client_executor.execute(ChainVariant::Mainnet, client_input).expect("failed to execute client");
+    client_executor.execute(chain_variant, client_input).expect("failed to execute client");
 
     // Execute the client with the deserialized input to test round-trip
     client_executor
-        .execute(ChainVariant::Mainnet, deserialized_input)
+        .execute(chain_variant, deserialized_input)
         .expect("failed to execute client with deserialized input");
 }
+
+/// The input of the mainnet block shared by the tests, which is only fetched once.
+fn mainnet_input() -> ClientExecutorInput {
+    static INPUT: OnceLock<ClientExecutorInput> = OnceLock::new();
+    INPUT.get_or_init(|| fetch_input("RPC_1", MAINNET_BLOCK_NUMBER)).clone()
+}
+
+/// Fetches the input of `block_number` from the RPC endpoint in the `env_var_key` variable.
+fn fetch_input(env_var_key: &str, block_number: u64) -> ClientExecutorInput {
+    // Initialize the environment variables.
+    dotenv::dotenv().ok();
+
+    // Initialize the logger.
+    let _ = tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(EnvFilter::from_default_env())
+        .try_init();
+
+    let rpc_url =
+        Url::parse(std::env::var(env_var_key).unwrap().as_str()).expect("invalid rpc url");
+
+    // The tests are synchronous, so that the input can be shared between them.
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        // Setup the provider.
+        let provider = RootProvider::new_http(rpc_url);
+
+        // Setup the host executor.
+        let host_executor = HostExecutor::new(provider);
+
+        // Execute the host.
+        host_executor.execute(block_number).await.expect("failed to execute host")
+    })
+}
@@ -0,0 +1,115 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+/// Whether [`CountingAlloc`] is the global allocator, which is the case with the `count-allocs`
+/// feature.
+pub const INSTALLED: bool = cfg!(feature = "count-allocs");
+
+static COUNTING: AtomicBool = AtomicBool::new(false);
+static ALLOCS: AtomicU64 = AtomicU64::new(0);
+static BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// The system allocator, counting the allocations and allocated bytes while [`count_allocs`] runs.
+/// Reallocations count as allocations of their new size.
+#[derive(Debug)]
+pub struct CountingAlloc;
+
+impl CountingAlloc {
+    fn record(size: usize) {
+        if COUNTING.load(Ordering::Relaxed) {
+            ALLOCS.fetch_add(1, Ordering::Relaxed);
+            BYTES.fetch_add(size as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::record(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::record(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::record(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[cfg(all(feature = "count-allocs", any(feature = "mimalloc", feature = "jemalloc")))]
+compile_error!(
+    "the count-allocs feature replaces the global allocator, disable mimalloc and jemalloc"
+);
+
+#[cfg(feature = "count-allocs")]
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Allocations counted by [`count_allocs`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub allocs: u64,
+    pub bytes: u64,
+}
+
+/// Runs `f` and returns the allocations made meanwhile, on all threads. They are only counted if
+/// [`INSTALLED`], and calls must not overlap.
+pub fn count_allocs<R>(f: impl FnOnce() -> R) -> (R, AllocStats) {
+    ALLOCS.store(0, Ordering::Relaxed);
+    BYTES.store(0, Ordering::Relaxed);
+    COUNTING.store(true, Ordering::SeqCst);
+    let result = f();
+    COUNTING.store(false, Ordering::SeqCst);
+    let stats =
+        AllocStats { allocs: ALLOCS.load(Ordering::Relaxed), bytes: BYTES.load(Ordering::Relaxed) };
+    (result, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openvm_client_executor::{io::ClientExecutorInput, ChainVariant, ClientExecutor};
+    use openvm_mpt::EthereumState;
+    use reth_primitives::{Block, Header};
+
+    /// The input of the first block on top of an empty state. It executes under the Frontier
+    /// rules, which need neither system contracts nor witnessed accounts.
+    fn empty_block_input() -> ClientExecutorInput {
+        let state = EthereumState::new();
+        let parent_header = Header { state_root: state.state_trie.hash(), ..Default::default() };
+        let header =
+            Header { number: 1, parent_hash: parent_header.hash_slow(), ..Default::default() };
+        ClientExecutorInput {
+            current_block: Block { header, ..Default::default() },
+            ancestor_headers: vec![parent_header],
+            parent_state_bytes: state.encode_to_state_bytes(),
+            bytecodes: vec![],
+        }
+    }
+
+    /// Only counts with `--no-default-features --features metrics,count-allocs`, as the default
+    /// allocator is installed by a dependency otherwise.
+    #[test]
+    fn test_count_allocs_client_execution() {
+        let input = empty_block_input();
+        let (header, stats) =
+            count_allocs(|| ClientExecutor.execute_unchecked(ChainVariant::Mainnet, input));
+        assert_eq!(header.unwrap().number, 1);
+        if INSTALLED {
+            assert!(stats.allocs > 0, "{stats:?}");
+            assert!(stats.bytes > 0, "{stats:?}");
+        } else {
+            assert_eq!(stats, AllocStats::default());
+        }
+    }
+}
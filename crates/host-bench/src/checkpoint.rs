@@ -0,0 +1,85 @@
+use alloy_primitives::B256;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The directory holding the segment proofs of one block under `checkpoint_dir`. Segment proofs
+/// are only valid for the program and input they were generated for, so the directory is keyed by
+/// the APC setup name and the ELF hash, then by the block number and a hash of the input.
+pub fn segment_dir(
+    checkpoint_dir: &Path,
+    apc_setup_name: &str,
+    elf_hash: B256,
+    block_number: u64,
+    input_hash: B256,
+) -> PathBuf {
+    checkpoint_dir
+        .join(format!("{apc_setup_name}_{elf_hash}"))
+        .join(format!("block_{block_number}_{input_hash}"))
+}
+
+fn segment_proof_path(segment_dir: &Path, seg_idx: usize) -> PathBuf {
+    segment_dir.join(format!("segment_{seg_idx}.proof.bitcode"))
+}
+
+/// Reads the proof of segment `seg_idx` written by [`write_segment_proof`], or returns `None` if
+/// it was not checkpointed yet.
+pub fn read_segment_proof<T: DeserializeOwned>(
+    segment_dir: &Path,
+    seg_idx: usize,
+) -> eyre::Result<Option<T>> {
+    let path = segment_proof_path(segment_dir, seg_idx);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(bitcode::deserialize(&fs::read(path)?)?))
+}
+
+/// Writes the proof of segment `seg_idx` to a temporary file which is then renamed, so that a run
+/// killed while writing never leaves a truncated proof to resume from.
+pub fn write_segment_proof<T: Serialize>(
+    segment_dir: &Path,
+    seg_idx: usize,
+    proof: &T,
+) -> eyre::Result<()> {
+    fs::create_dir_all(segment_dir)?;
+    let path = segment_proof_path(segment_dir, seg_idx);
+    let tmp_path = path.with_extension("bitcode.tmp");
+    fs::write(&tmp_path, bitcode::serialize(proof)?)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_dir() {
+        let root = Path::new("checkpoints");
+        let dir = segment_dir(root, "reth", B256::ZERO, 1, B256::ZERO);
+        assert_ne!(dir, segment_dir(root, "reth_pgo", B256::ZERO, 1, B256::ZERO));
+        assert_ne!(dir, segment_dir(root, "reth", B256::repeat_byte(1), 1, B256::ZERO));
+        assert_ne!(dir, segment_dir(root, "reth", B256::ZERO, 2, B256::ZERO));
+        assert_ne!(dir, segment_dir(root, "reth", B256::ZERO, 1, B256::repeat_byte(1)));
+    }
+
+    #[test]
+    fn test_segment_proof_round_trip() {
+        let root = std::env::temp_dir().join("openvm-reth-benchmark-checkpoint");
+        let dir = segment_dir(&root, "reth", B256::ZERO, 1, B256::ZERO);
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(read_segment_proof::<Vec<u32>>(&dir, 0).unwrap(), None);
+
+        write_segment_proof(&dir, 0, &vec![1u32, 2, 3]).unwrap();
+        assert_eq!(read_segment_proof(&dir, 0).unwrap(), Some(vec![1u32, 2, 3]));
+        assert_eq!(read_segment_proof::<Vec<u32>>(&dir, 1).unwrap(), None);
+        // only the renamed proof is left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 use alloy_provider::{Provider as _, RootProvider};
+use alloy_transport::layers::RetryBackoffLayer;
 use clap::Parser;
 use url::Url;
 
@@ -12,11 +13,39 @@ pub struct ProviderArgs {
     /// The chain ID. If not provided, requires the rpc_url argument to be provided.
     #[clap(long)]
     chain_id: Option<u64>,
+    #[clap(flatten)]
+    retry: RpcRetryArgs,
+    /// When fetching a block, name the first account that differs from the proofs at the block
+    /// if the state root does not match. Slows down every fetch, so only use it for debugging.
+    #[clap(long, default_value_t = false)]
+    diagnose_state_root: bool,
+}
+
+/// Retry and backoff settings for RPC requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Parser)]
+pub struct RpcRetryArgs {
+    /// Maximum number of retries of a rate-limited RPC request.
+    #[clap(long, default_value_t = 5)]
+    rpc_max_retries: u32,
+    /// Initial backoff between retries, in milliseconds.
+    #[clap(long, default_value_t = 1000)]
+    rpc_backoff_ms: u64,
+    /// Compute units per second the RPC endpoint allows.
+    #[clap(long, default_value_t = 100)]
+    rpc_cups: u64,
+}
+
+impl RpcRetryArgs {
+    pub fn layer(&self) -> RetryBackoffLayer {
+        RetryBackoffLayer::new(self.rpc_max_retries, self.rpc_backoff_ms, self.rpc_cups)
+    }
 }
 
 pub struct ProviderConfig {
     pub rpc_url: Option<Url>,
     pub chain_id: u64,
+    pub retry: RpcRetryArgs,
+    pub diagnose_state_root: bool,
 }
 
 impl ProviderArgs {
@@ -51,6 +80,42 @@ impl ProviderArgs {
             }
         };
 
-        Ok(ProviderConfig { rpc_url, chain_id })
+        Ok(ProviderConfig {
+            rpc_url,
+            chain_id,
+            retry: self.retry,
+            diagnose_state_root: self.diagnose_state_root,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_retry_args() {
+        let args = ProviderArgs::try_parse_from(["host", "--chain-id", "1"]).unwrap();
+        assert_eq!(
+            args.retry,
+            RpcRetryArgs { rpc_max_retries: 5, rpc_backoff_ms: 1000, rpc_cups: 100 }
+        );
+
+        let args = ProviderArgs::try_parse_from([
+            "host",
+            "--chain-id",
+            "1",
+            "--rpc-max-retries",
+            "10",
+            "--rpc-backoff-ms",
+            "250",
+            "--rpc-cups",
+            "660",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.retry,
+            RpcRetryArgs { rpc_max_retries: 10, rpc_backoff_ms: 250, rpc_cups: 660 }
+        );
     }
 }
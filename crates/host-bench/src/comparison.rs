@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// The block hashes computed by each stage of a benchmark run, hex encoded. Stages that did not
+/// run are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHashComparison {
+    pub block_number: u64,
+    pub host: Option<String>,
+    pub execute: Option<String>,
+    pub proved: Option<String>,
+    /// Whether all the stages that ran agree on the block hash.
+    #[serde(rename = "match")]
+    pub is_match: bool,
+}
+
+impl BlockHashComparison {
+    pub fn new(block_number: u64) -> Self {
+        Self { block_number, is_match: true, ..Default::default() }
+    }
+
+    /// Recomputes [`Self::is_match`] from the recorded hashes.
+    pub fn update_match(&mut self) {
+        let mut hashes = [&self.host, &self.execute, &self.proved].into_iter().flatten();
+        self.is_match = match hashes.next() {
+            Some(first) => hashes.all(|hash| hash == first),
+            None => true,
+        };
+    }
+}
+
+/// Writes the comparisons to `path` as a JSON array if it is given, and fails if any of them
+/// mismatches either way.
+pub fn report_comparisons(
+    path: Option<&Path>,
+    comparisons: &[BlockHashComparison],
+) -> eyre::Result<()> {
+    if let Some(path) = path {
+        fs::write(path, serde_json::to_vec_pretty(comparisons)?)?;
+    }
+    if let Some(mismatch) = comparisons.iter().find(|comparison| !comparison.is_match) {
+        eyre::bail!("block hash mismatch for block {}", mismatch.block_number);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_hash_comparison() {
+        let dir = std::env::temp_dir().join("openvm-reth-benchmark-comparison");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("comparison.json");
+
+        let mut matching = BlockHashComparison::new(1);
+        matching.host = Some("aa".repeat(32));
+        matching.execute = Some("aa".repeat(32));
+        matching.update_match();
+        assert!(matching.is_match);
+        report_comparisons(Some(&path), std::slice::from_ref(&matching)).unwrap();
+
+        let mut mismatching = BlockHashComparison::new(2);
+        mismatching.host = Some("aa".repeat(32));
+        mismatching.execute = Some("aa".repeat(32));
+        mismatching.proved = Some("bb".repeat(32));
+        mismatching.update_match();
+        assert!(!mismatching.is_match);
+        let comparisons = [matching.clone(), mismatching.clone()];
+        let err = report_comparisons(Some(&path), &comparisons).unwrap_err();
+        assert_eq!(err.to_string(), "block hash mismatch for block 2");
+        // a mismatch fails the run even when the comparisons are not written
+        assert!(report_comparisons(None, &comparisons).is_err());
+
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(json[0]["match"], true);
+        assert_eq!(json[1]["match"], false);
+        assert_eq!(json[1]["proved"], "bb".repeat(32));
+        let decoded: Vec<BlockHashComparison> = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, vec![matching, mismatching]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
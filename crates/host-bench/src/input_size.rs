@@ -0,0 +1,118 @@
+use openvm_client_executor::io::ClientExecutorInput;
+use reth_primitives::TransactionSigned;
+use serde::{Deserialize, Serialize};
+use serde_with::ser::SerializeAsWrap;
+use std::{fs, path::Path};
+
+/// Bytes of the serialized guest input spent on each of its parts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputSizeBreakdown {
+    pub current_block: usize,
+    pub ancestor_headers: usize,
+    pub state_trie: usize,
+    /// The storage tries of all accounts together.
+    pub storage_tries: usize,
+    pub bytecodes: usize,
+    /// Length of the whole input, as serialized for the guest.
+    pub total: usize,
+}
+
+impl InputSizeBreakdown {
+    /// Serializes each part of `input` the way the guest reads it, with `openvm::serde`.
+    pub fn new(input: &ClientExecutorInput) -> eyre::Result<Self> {
+        type BlockRepr<'a> = reth_primitives_traits::serde_bincode_compat::Block<
+            'a,
+            TransactionSigned,
+            reth_primitives::Header,
+        >;
+        type HeaderRepr<'a> = alloy_consensus::serde_bincode_compat::Header<'a>;
+
+        let state_bytes = &input.parent_state_bytes;
+        Ok(Self {
+            current_block: serialized_len(&SerializeAsWrap::<_, BlockRepr<'_>>::new(
+                &input.current_block,
+            ))?,
+            ancestor_headers: serialized_len(&SerializeAsWrap::<_, Vec<HeaderRepr<'_>>>::new(
+                &input.ancestor_headers,
+            ))?,
+            state_trie: serialized_len(&state_bytes.state_trie)?,
+            storage_tries: serialized_len(&state_bytes.storage_tries)?,
+            bytecodes: serialized_len(&input.bytecodes)?,
+            total: serialized_len(input)?,
+        })
+    }
+
+    /// Prints the size of each part, and its share of the total.
+    pub fn print(&self) {
+        let parts = [
+            ("current_block", self.current_block),
+            ("ancestor_headers", self.ancestor_headers),
+            ("state_trie", self.state_trie),
+            ("storage_tries", self.storage_tries),
+            ("bytecodes", self.bytecodes),
+        ];
+        println!("{:<20} {:>12} {:>8}", "input part", "bytes", "share");
+        for (part, len) in parts {
+            let share = 100.0 * len as f64 / self.total.max(1) as f64;
+            println!("{part:<20} {len:>12} {share:>7.1}%");
+        }
+        println!("{:<20} {:>12}", "total", self.total);
+    }
+
+    /// Writes the breakdown to `path` as JSON.
+    pub fn write_json(&self, path: &Path) -> eyre::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Length in bytes of `value` serialized with `openvm::serde`.
+fn serialized_len<T: Serialize>(value: &T) -> eyre::Result<usize> {
+    let words: Vec<u32> =
+        openvm::serde::to_vec(value).map_err(|err| eyre::eyre!("failed to serialize: {err:?}"))?;
+    Ok(words.len() * size_of::<u32>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bumpalo::Bump;
+    use openvm_mpt::{EthereumState, Mpt};
+    use reth_primitives::{Block, Header};
+
+    #[test]
+    fn test_input_size_breakdown() {
+        let bump = Bump::new();
+        let mut state_trie = Mpt::new(&bump);
+        state_trie.insert(&[0x11; 32], b"account").unwrap();
+        let mut storage_trie = Mpt::new(&bump);
+        for slot in 0..16u8 {
+            storage_trie.insert_rlp(&[slot; 32], u64::from(slot) + 1).unwrap();
+        }
+        let state = EthereumState::from_tries_in(
+            state_trie,
+            [(alloy_primitives::B256::repeat_byte(0x11), storage_trie)],
+            &bump,
+        );
+
+        let input = ClientExecutorInput {
+            current_block: Block::default(),
+            ancestor_headers: vec![Header::default(), Header { number: 1, ..Default::default() }],
+            parent_state_bytes: state.encode_to_state_bytes(),
+            bytecodes: vec![Default::default(); 3],
+        };
+        let breakdown = InputSizeBreakdown::new(&input).unwrap();
+
+        assert_eq!(
+            breakdown.current_block +
+                breakdown.ancestor_headers +
+                breakdown.state_trie +
+                breakdown.storage_tries +
+                breakdown.bytecodes,
+            breakdown.total
+        );
+        assert!(breakdown.storage_tries > breakdown.state_trie);
+        let words: Vec<u32> = openvm::serde::to_vec(&input).unwrap();
+        assert_eq!(breakdown.total, words.len() * size_of::<u32>());
+    }
+}
@@ -3,24 +3,28 @@
 use alloy_primitives::hex::ToHexExt;
 use alloy_provider::RootProvider;
 use alloy_rpc_client::RpcClient;
-use alloy_transport::layers::RetryBackoffLayer;
+use bumpalo::Bump;
 use clap::Parser;
 use openvm_benchmarks_prove::util::BenchmarkCli;
 use openvm_circuit::{
-    arch::{execution_mode::Segment, *},
+    arch::{execution_mode::Segment, hasher::poseidon2::vm_poseidon2_hasher, *},
     openvm_stark_sdk::{
         bench::run_with_metric_collection, openvm_stark_backend::p3_field::PrimeField32,
     },
+    system::memory::merkle::public_values::UserPublicValuesProof,
 };
 use openvm_client_executor::{
     io::ClientExecutorInput, ChainVariant, ClientExecutor, CHAIN_ID_ETH_MAINNET,
+    SUPPORTED_CHAIN_IDS,
 };
 use openvm_host_executor::HostExecutor;
 pub use openvm_native_circuit::NativeConfig;
 use openvm_native_circuit::NativeCpuBuilder;
+use openvm_primitives::chain_spec::from_genesis_json;
 
 #[cfg(feature = "cuda")]
 pub use openvm_cuda_backend::engine::GpuBabyBearPoseidon2Engine;
+use openvm_instructions::program::DEFAULT_PC_STEP;
 use openvm_sdk::{
     config::{AppConfig, SdkVmConfig},
     keygen::{AggProvingKey, AppProvingKey},
@@ -29,17 +33,19 @@ use openvm_sdk::{
     DefaultStarkEngine, GenericSdk, StdIn,
 };
 use openvm_stark_sdk::{
-    config::baby_bear_poseidon2::BabyBearPoseidon2Engine, engine::StarkFriEngine,
+    config::baby_bear_poseidon2::BabyBearPoseidon2Engine,
+    engine::{StarkEngine, StarkFriEngine},
 };
 use openvm_transpiler::{elf::Elf, openvm_platform::memory::MEM_SIZE};
-use powdr_autoprecompiles::PgoType;
+use powdr_autoprecompiles::{execution_profile::execution_profile, PgoType};
 #[cfg(not(feature = "cuda"))]
 use powdr_openvm::PowdrSdkCpu;
 #[cfg(feature = "cuda")]
 use powdr_openvm::PowdrSdkGpu;
 use powdr_openvm::{
-    extraction_utils::OriginalVmConfig, CompiledProgram, OriginalCompiledProgram,
-    SpecializedConfig, SpecializedConfigCpuBuilder,
+    extraction_utils::OriginalVmConfig, BabyBearOpenVmApcAdapter, CompiledProgram,
+    OriginalCompiledProgram, PowdrExecutionProfileSdkCpu, Prog, SpecializedConfig,
+    SpecializedConfigCpuBuilder,
 };
 #[cfg(feature = "cuda")]
 use powdr_openvm_riscv::ExtendedVmConfigGpuBuilder;
@@ -50,14 +56,30 @@ pub use reth_primitives;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::{
-    fs::{self, File},
-    io::{BufReader, BufWriter},
-    path::PathBuf,
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
 };
 use tracing::{info, info_span};
 
+pub mod alloc_counter;
+pub mod checkpoint;
 mod cli;
 use cli::ProviderArgs;
+pub mod input_size;
+use input_size::InputSizeBreakdown;
+mod prover_cache;
+use prover_cache::CacheCompression;
+pub mod rss;
+use rss::PeakRssReport;
+pub mod comparison;
+pub mod timings;
+use comparison::BlockHashComparison;
+pub mod trace_stats;
+use timings::PhaseTimings;
 
 use crate::cli::ProviderConfig;
 
@@ -70,12 +92,18 @@ pub enum BenchMode {
     Execute,
     /// Execute the VM with metering to get segments information.
     ExecuteMetered,
+    /// Execute the original program (without APCs) and print the most executed instructions.
+    /// They are ranked by execution count, not by trace cells, as the execution profile does not
+    /// know the width of the AIR each instruction is traced in.
+    ExecuteProfile,
     /// Execute, generate trace, and check constraints and bus interactions without proving.
     ProveMock,
     /// Generate sequence of app proofs for continuation segments.
     ProveApp,
     /// Generate a full end-to-end STARK proof with aggregation.
     ProveStark,
+    /// Verify a STARK proof previously written by `prove-stark`.
+    VerifyProof,
     /// Generate a full end-to-end halo2 proof for EVM verifier.
     #[cfg(feature = "evm-verify")]
     ProveEvm,
@@ -93,9 +121,11 @@ impl std::fmt::Display for BenchMode {
             Self::ExecuteHost => write!(f, "execute_host"),
             Self::Execute => write!(f, "execute"),
             Self::ExecuteMetered => write!(f, "execute_metered"),
+            Self::ExecuteProfile => write!(f, "execute_profile"),
             Self::ProveMock => write!(f, "prove_mock"),
             Self::ProveApp => write!(f, "prove_app"),
             Self::ProveStark => write!(f, "prove_stark"),
+            Self::VerifyProof => write!(f, "verify_proof"),
             #[cfg(feature = "evm-verify")]
             Self::ProveEvm => write!(f, "prove_evm"),
             Self::MakeInput => write!(f, "make_input"),
@@ -108,11 +138,16 @@ impl std::fmt::Display for BenchMode {
 #[derive(Debug, Parser)]
 pub struct HostArgs {
     /// The block number of the block to execute.
-    #[clap(long)]
-    block_number: u64,
+    #[clap(long, required_unless_present_any = ["block_range", "input_path", "proof_path"])]
+    block_number: Option<u64>,
+
+    /// Run the benchmark on each block in `START..END` in order, instead of a single block.
+    #[clap(long, conflicts_with = "block_number", value_parser = parse_block_range)]
+    block_range: Option<Range<u64>>,
 
-    /// The block numbers to do PGO on (comma-separated).
-    #[clap(long, value_delimiter = ',')]
+    /// The block numbers to do PGO on (comma-separated). Defaults to block 24171377 when APCs are
+    /// selected with PGO, and to none otherwise.
+    #[clap(long, alias = "pgo-blocks", value_delimiter = ',')]
     pgo_block_numbers: Vec<u64>,
 
     #[clap(flatten)]
@@ -134,6 +169,10 @@ pub struct HostArgs {
     #[clap(long)]
     apc_setup_name: String,
 
+    /// Compression of newly written prover data cache files. Reads detect the compression used.
+    #[clap(long, value_enum, default_value_t = CacheCompression::None)]
+    cache_compression: CacheCompression,
+
     /// The path to the CSV file containing the execution data.
     #[clap(long, default_value = "report.csv")]
     report_path: PathBuf,
@@ -141,8 +180,9 @@ pub struct HostArgs {
     #[clap(flatten)]
     benchmark: BenchmarkCli,
 
-    /// Optional path to the input file.
-    #[arg(long)]
+    /// Optional path to an input JSON written in make_input mode, or `-` to read it from stdin.
+    /// The input is used as is instead of fetching the block from the cache or RPC.
+    #[arg(long, conflicts_with_all = ["block_number", "block_range"])]
     pub input_path: Option<PathBuf>,
 
     #[arg(long)]
@@ -161,10 +201,69 @@ pub struct HostArgs {
     #[arg(long)]
     pub generated_input_path: Option<PathBuf>,
 
+    /// In make_input mode, the bytes of the input spent on the block, the ancestor headers, the
+    /// state and storage tries and the bytecodes are also written to this JSON file.
+    #[arg(long)]
+    pub input_size_json: Option<PathBuf>,
+
     /// If specificed, the proof and other output is written to this dir.
     #[arg(long)]
     pub output_dir: Option<PathBuf>,
 
+    /// In prove_mock and prove_app modes, the per-segment, per-AIR trace stats are written to this
+    /// JSON file.
+    #[arg(long)]
+    pub trace_stats_path: Option<PathBuf>,
+
+    /// In execute_metered mode, the start, instruction count and trace heights of each segment
+    /// are written to this JSON file.
+    #[arg(long)]
+    pub segments_json: Option<PathBuf>,
+
+    /// In prove_mock and prove_app modes, fail before proving if the trace of any AIR in any
+    /// segment is taller than this.
+    #[arg(long)]
+    pub max_trace_height: Option<u32>,
+
+    /// In execute_profile mode, the number of most executed instructions to print, ranked by
+    /// execution count rather than by trace cells.
+    #[arg(long, default_value_t = 20)]
+    pub profile_top_n: usize,
+
+    /// If specified, the block hashes from host execution, VM execution and proving are written to
+    /// this JSON file. The run fails if they do not match, whether or not this is set.
+    #[arg(long)]
+    pub comparison_json: Option<PathBuf>,
+
+    /// If specified, the duration of each benchmark phase is written to this JSON file.
+    #[arg(long)]
+    pub timings_json: Option<PathBuf>,
+
+    /// Print the peak resident memory after input load, proving key load and each block.
+    #[arg(long, default_value_t = false)]
+    pub report_peak_rss: bool,
+
+    /// In prove_app mode, each segment proof is written to this directory as soon as it is
+    /// generated, and segments whose proofs already exist there are not proven again. Proofs are
+    /// kept apart per APC setup, ELF, block and input, so a stale checkpoint is never resumed.
+    #[arg(long)]
+    pub checkpoint_dir: Option<PathBuf>,
+
+    /// In verify_proof mode, the `proof.json` to verify. No block is needed in this mode.
+    #[arg(long)]
+    pub proof_path: Option<PathBuf>,
+
+    /// Count the allocations of the host execution, as an approximation of the allocation pressure
+    /// in the guest. Requires the `count-allocs` feature, which replaces the global allocator, so
+    /// build with `--no-default-features --features metrics,count-allocs`.
+    #[arg(long, default_value_t = false)]
+    pub count_allocs: bool,
+
+    /// In execute mode, also print the total executed instruction count. It comes from a separate
+    /// metered execution, after the timed one.
+    #[arg(long, default_value_t = false)]
+    pub count_instructions: bool,
+
     /// If specified, loads the app proving key from this path.
     #[arg(long)]
     pub app_pk_path: Option<PathBuf>,
@@ -175,6 +274,11 @@ pub struct HostArgs {
 
     #[arg(long, default_value_t = false)]
     pub skip_comparison: bool,
+
+    /// If specified, executes with the chain spec from this genesis JSON instead of a known
+    /// chain. Only supported in execute-host and make-input modes.
+    #[arg(long)]
+    pub chain_spec: Option<PathBuf>,
 }
 
 pub fn reth_vm_config(app_log_blowup: usize) -> ExtendedVmConfig {
@@ -195,10 +299,14 @@ pub const RETH_DEFAULT_APP_LOG_BLOWUP: usize = 1;
 pub const RETH_DEFAULT_LEAF_LOG_BLOWUP: usize = 1;
 
 const PGO_CHAIN_ID: u64 = CHAIN_ID_ETH_MAINNET;
+/// The blocks to do PGO on if `--pgo-block-numbers` is not given, see [`complete_args`].
+pub const DEFAULT_PGO_BLOCK_NUMBERS: [u64; 1] = [24171377];
 const APP_LOG_BLOWUP: usize = 1;
 
 #[derive(Serialize, Deserialize)]
 pub struct PrecomputedProverData {
+    /// Keccak hash of the ELF the program was compiled from, see [`prover_cache::check_elf_hash`].
+    elf_hash: alloy_primitives::B256,
     program: CompiledProgram<RiscvISA>,
     app_pk: AppProvingKey<SpecializedConfig<RiscvISA>>,
     agg_pk: AggProvingKey,
@@ -218,13 +326,13 @@ async fn get_client_input(
         (None, Some(rpc_url)) => {
             // Cache not found but we have RPC
             // Setup the provider.
-            let client = RpcClient::builder()
-                .layer(RetryBackoffLayer::new(5, 1000, 100))
-                .http(rpc_url.clone());
+            let client =
+                RpcClient::builder().layer(provider_config.retry.layer()).http(rpc_url.clone());
             let provider = RootProvider::new(client);
 
             // Setup the host executor.
-            let host_executor = HostExecutor::new(provider);
+            let host_executor = HostExecutor::new(provider)
+                .with_state_root_diagnosis(provider_config.diagnose_state_root);
 
             // Execute the host.
             let client_input =
@@ -254,15 +362,113 @@ async fn get_client_input(
     }
 }
 
+impl HostArgs {
+    /// The path of the prover data cache file. The key is the APC setup name, followed by the PGO
+    /// blocks if there are any, so that profiling other blocks does not reuse stale APCs.
+    fn apc_cache_path(&self) -> PathBuf {
+        let mut key = self.apc_setup_name.clone();
+        for block_number in &self.pgo_block_numbers {
+            key.push_str(&format!("_{block_number}"));
+        }
+        self.apc_cache_dir.join(key).with_extension("bin")
+    }
+
+    /// The blocks to run the benchmark on, in order.
+    fn block_numbers(&self) -> Vec<u64> {
+        match (&self.block_range, self.block_number) {
+            (Some(range), _) => range.clone().collect(),
+            (None, Some(block_number)) => vec![block_number],
+            (None, None) => vec![],
+        }
+    }
+}
+
+/// Encodes `client_input` as the input JSON of the guest, as read by `openvm run --input`.
+fn encode_input_json(client_input: &ClientExecutorInput) -> String {
+    let words: Vec<u32> = openvm::serde::to_vec(client_input).unwrap();
+    let bytes: Vec<u8> = words.into_iter().flat_map(|w| w.to_le_bytes()).collect();
+    let hex_bytes = String::from("0x01") + &hex::encode(&bytes);
+    let input = json!({
+        "input": [hex_bytes]
+    });
+    serde_json::to_string(&input).unwrap()
+}
+
+/// Decodes an input JSON written by [`encode_input_json`].
+fn decode_input_json(input: &str) -> eyre::Result<ClientExecutorInput> {
+    let input: serde_json::Value = serde_json::from_str(input)?;
+    let Some(hex_bytes) = input["input"][0].as_str() else {
+        eyre::bail!("expected an input JSON of the form {{\"input\": [\"0x01...\"]}}");
+    };
+    let Some(hex_bytes) = hex_bytes.strip_prefix("0x01") else {
+        eyre::bail!("expected the input to start with 0x01");
+    };
+    let bytes = hex::decode(hex_bytes)?;
+    if bytes.len() % 4 != 0 {
+        eyre::bail!("input length {} is not a multiple of 4 bytes", bytes.len());
+    }
+    let words: Vec<u32> =
+        bytes.chunks_exact(4).map(|w| u32::from_le_bytes(w.try_into().unwrap())).collect();
+    let mut client_input: ClientExecutorInput = openvm::serde::from_slice(&words)
+        .map_err(|err| eyre::eyre!("failed to decode input: {err}"))?;
+    // Inputs written before the bytecodes were deduplicated would otherwise fail in the guest
+    client_input.dedup_bytecodes();
+    Ok(client_input)
+}
+
+/// Reads an input JSON from `path`, or from stdin if `path` is `-`.
+fn read_input_json(path: &Path) -> eyre::Result<ClientExecutorInput> {
+    let input = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        fs::read_to_string(path)?
+    };
+    decode_input_json(&input)
+}
+
+/// Parses a `START..END` block range, with `END` exclusive.
+fn parse_block_range(s: &str) -> Result<Range<u64>, String> {
+    let (start, end) = s.split_once("..").ok_or_else(|| format!("expected START..END, got {s}"))?;
+    let start = start.parse::<u64>().map_err(|err| format!("invalid range start: {err}"))?;
+    let end = end.parse::<u64>().map_err(|err| format!("invalid range end: {err}"))?;
+    if start >= end {
+        return Err(format!("empty block range {s}"));
+    }
+    Ok(start..end)
+}
+
 /// Complete the host arguments with defaults
-pub fn complete_args(mut args: HostArgs) -> HostArgs {
+pub fn complete_args(mut args: HostArgs) -> eyre::Result<HostArgs> {
     let app_log_blowup = args.benchmark.app_log_blowup.unwrap_or(RETH_DEFAULT_APP_LOG_BLOWUP);
-    assert_eq!(app_log_blowup, APP_LOG_BLOWUP, "App log blowup must be {RETH_DEFAULT_APP_LOG_BLOWUP} because it must match the one used when compiling this benchmark");
+    check_app_log_blowup(app_log_blowup)?;
     args.benchmark.app_log_blowup = Some(app_log_blowup);
     let leaf_log_blowup = args.benchmark.leaf_log_blowup.unwrap_or(RETH_DEFAULT_LEAF_LOG_BLOWUP);
     args.benchmark.leaf_log_blowup = Some(leaf_log_blowup);
+    // Without APCs or PGO, no block is profiled, so none needs to be fetched.
+    if args.pgo_block_numbers.is_empty() && args.apc > 0 && !matches!(args.pgo_type, PgoType::None)
+    {
+        args.pgo_block_numbers = DEFAULT_PGO_BLOCK_NUMBERS.to_vec();
+    }
+
+    Ok(args)
+}
+
+fn check_app_log_blowup(app_log_blowup: usize) -> eyre::Result<()> {
+    if app_log_blowup != APP_LOG_BLOWUP {
+        eyre::bail!(
+            "--app-log-blowup {app_log_blowup} is not supported: the app log blowup must be \
+             {APP_LOG_BLOWUP} to match the one used when compiling this benchmark"
+        );
+    }
+    Ok(())
+}
 
-    args
+fn unknown_chain_id_message(chain_id: u64) -> String {
+    let supported = SUPPORTED_CHAIN_IDS.map(|id| id.to_string()).join(", ");
+    format!(
+        "unknown chain ID: {chain_id}. Supported chain IDs are {supported}; for other chains, \
+         pass the genesis JSON of the chain with --chain-spec"
+    )
 }
 
 /// Precompute the prover data, in particular the specialized config taking into account APCs, as
@@ -278,21 +484,17 @@ pub async fn precompute_prover_data(
         tracing_subscriber::FmtSubscriber::builder().with_max_level(tracing::Level::DEBUG).finish();
     let _guard = tracing::subscriber::set_default(subscriber);
 
-    let cache_file_path = args.apc_cache_dir.join(&args.apc_setup_name).with_extension("bin");
+    let cache_file_path = args.apc_cache_path();
 
-    if let Some(compiled_program) =
-        File::open(&cache_file_path).ok().map(BufReader::new).map(|mut file| {
-            bincode::serde::decode_from_std_read(&mut file, bincode::config::standard())
-                .expect("Found cached precomputed prover data, but deserialization failed")
-        })
-    {
-        tracing::info!("Precomputed prover data for key {} found in cache", args.apc_setup_name);
+    if let Some((compiled_program, elapsed)) = prover_cache::read_cache_timed(&cache_file_path) {
+        tracing::info!("Precomputed prover data {} found in cache", cache_file_path.display());
+        println!(">>> Time to load precomputed prover data: {elapsed:?}");
         return Ok(compiled_program);
     }
 
     tracing::info!(
-        "Precomputed prover data for key {} not found in cache. Precomputing prover data.",
-        args.apc_setup_name
+        "Precomputed prover data {} not found in cache. Precomputing prover data.",
+        cache_file_path.display()
     );
 
     let provider_config = args.provider.clone().into_provider().await?;
@@ -345,16 +547,12 @@ pub async fn precompute_prover_data(
     tracing::info!("Run agg keygen");
     let (agg_pk, _) = specialized_sdk.agg_keygen().unwrap();
 
-    let setup = PrecomputedProverData { program, app_pk, agg_pk };
+    let elf_hash = alloy_primitives::keccak256(openvm_client_eth_elf);
+    let setup = PrecomputedProverData { elf_hash, program, app_pk, agg_pk };
 
     tracing::info!("Saving prover data to cache at {}", cache_file_path.display());
     std::fs::create_dir_all(&args.apc_cache_dir).unwrap();
-    bincode::serde::encode_into_std_write(
-        &setup,
-        &mut BufWriter::new(File::create(cache_file_path).unwrap()),
-        bincode::config::standard(),
-    )
-    .unwrap();
+    prover_cache::write_cache(&cache_file_path, &setup, args.cache_compression).unwrap();
 
     Ok(setup)
 }
@@ -373,35 +571,102 @@ pub async fn run_reth_benchmark(
 
     // Parse the command line arguments.
     let mut args = args;
+    if args.count_allocs && !alloc_counter::INSTALLED {
+        eyre::bail!("--count-allocs requires a build with the count-allocs feature");
+    }
     let provider_config = args.provider.into_provider().await?;
 
-    match provider_config.chain_id {
-        #[allow(non_snake_case)]
-        CHAIN_ID_ETH_MAINNET => (),
-        _ => {
-            eyre::bail!("unknown chain ID: {}", provider_config.chain_id);
+    let chain_spec = match &args.chain_spec {
+        Some(path) => {
+            let spec = from_genesis_json(&fs::read_to_string(path)?)?;
+            if spec.chain.id() != provider_config.chain_id {
+                eyre::bail!(
+                    "chain spec {} has chain ID {}, but the provider is on chain ID {}",
+                    path.display(),
+                    spec.chain.id(),
+                    provider_config.chain_id
+                );
+            }
+            spec
+        }
+        None => {
+            let Some(chain_variant) = ChainVariant::from_chain_id(provider_config.chain_id) else {
+                eyre::bail!(unknown_chain_id_message(provider_config.chain_id));
+            };
+            chain_variant.chain_spec()
         }
     };
+    let chain_spec = Arc::new(chain_spec);
+    // The guest program always executes with the mainnet chain spec.
+    let guest_supported = args.chain_spec.is_none() &&
+        ChainVariant::from_chain_id(provider_config.chain_id) == Some(ChainVariant::Mainnet);
+    if !guest_supported && !matches!(args.mode, BenchMode::ExecuteHost | BenchMode::MakeInput) {
+        eyre::bail!(
+            "chain ID {} is only supported in execute-host and make-input modes",
+            provider_config.chain_id
+        );
+    }
 
     let chain_id = provider_config.chain_id;
 
-    let client_input =
-        get_client_input(&provider_config, &args.cache_dir, chain_id, args.block_number).await?;
+    let block_numbers = args.block_numbers();
+    let single_block = block_numbers.len() == 1 || args.input_path.is_some();
+    if block_numbers.len() > 1 &&
+        matches!(
+            args.mode,
+            BenchMode::MakeInput | BenchMode::GenerateFixtures | BenchMode::VerifyProof
+        )
+    {
+        eyre::bail!("mode {} does not support --block-range", args.mode);
+    }
+    // Verifying a proof from disk does not execute any block
+    let verify_only = matches!(args.mode, BenchMode::VerifyProof);
+    if block_numbers.is_empty() && args.input_path.is_none() && !verify_only {
+        eyre::bail!("mode {} requires --block-number, --block-range or --input-path", args.mode);
+    }
+
+    let mut timings = PhaseTimings::default();
+    let mut rss_report = args.report_peak_rss.then(PeakRssReport::default);
 
-    let mut stdin = StdIn::default();
-    stdin.write(&client_input);
-    info!("input loaded");
+    // The input read from `--input-path`, if any, is the only block to run. Otherwise the input
+    // of each block is fetched right before the block runs, so that only one is held at a time.
+    let mut path_input = match &args.input_path {
+        Some(input_path) => {
+            if matches!(args.mode, BenchMode::MakeInput) {
+                eyre::bail!("--input-path cannot be used in make-input mode");
+            }
+            if verify_only {
+                eyre::bail!("--input-path cannot be used in verify-proof mode");
+            }
+            let start = Instant::now();
+            let client_input = read_input_json(input_path)?;
+            timings.record("get_client_input", start.elapsed());
+            info!("input loaded");
+            if let Some(report) = rss_report.as_mut() {
+                report.sample("input load");
+            }
+            Some(client_input)
+        }
+        None => None,
+    };
+    let block_numbers = match &path_input {
+        Some(client_input) => vec![client_input.current_block.header.number],
+        None => block_numbers,
+    };
 
     if matches!(args.mode, BenchMode::MakeInput) {
-        let words: Vec<u32> = openvm::serde::to_vec(&client_input).unwrap();
-        let bytes: Vec<u8> = words.into_iter().flat_map(|w| w.to_le_bytes()).collect();
-        let hex_bytes = String::from("0x01") + &hex::encode(&bytes);
-        let input = json!({
-            "input": [hex_bytes]
-        });
-        let input = serde_json::to_string(&input).unwrap();
-        fs::write(args.generated_input_path.unwrap(), input)?;
-        return Ok(());
+        let start = Instant::now();
+        let client_input =
+            get_client_input(&provider_config, &args.cache_dir, chain_id, block_numbers[0]).await?;
+        timings.record("get_client_input", start.elapsed());
+        info!("input loaded");
+        fs::write(args.generated_input_path.as_ref().unwrap(), encode_input_json(&client_input))?;
+        let breakdown = InputSizeBreakdown::new(&client_input)?;
+        breakdown.print();
+        if let Some(path) = args.input_size_json.as_ref() {
+            breakdown.write_json(path)?;
+        }
+        return write_timings(&args, &timings);
     }
 
     let app_log_blowup = args.benchmark.app_log_blowup.unwrap();
@@ -409,12 +674,18 @@ pub async fn run_reth_benchmark(
     let vm_config = reth_vm_config(app_log_blowup);
     let app_config = args.benchmark.app_config(vm_config.clone());
 
-    let elf = Elf::decode(openvm_client_eth_elf, MEM_SIZE as u32)?;
+    let elf = timings.time("elf_decode", || Elf::decode(openvm_client_eth_elf, MEM_SIZE as u32))?;
 
-    let PrecomputedProverData { program: CompiledProgram { exe, vm_config }, app_pk, agg_pk } =
-        setup;
+    prover_cache::check_elf_hash(setup.elf_hash, openvm_client_eth_elf)?;
+    let PrecomputedProverData {
+        elf_hash,
+        program: CompiledProgram { exe, vm_config },
+        app_pk,
+        agg_pk,
+    } = setup;
 
     // Create an SDK based on the `SpecializedConfig` we generated
+    let start = Instant::now();
     #[cfg(feature = "cuda")]
     let generic_sdk = PowdrSdkGpu::new(args.benchmark.app_config(vm_config.clone()))?;
     #[cfg(not(feature = "cuda"))]
@@ -422,208 +693,587 @@ pub async fn run_reth_benchmark(
     let specialized_sdk = generic_sdk
         .with_agg_config(args.benchmark.agg_config())
         .with_agg_tree_config(args.benchmark.agg_tree_config);
+    timings.record("create_sdk", start.elapsed());
 
     // Load the precomputed proving keys
-    tracing::info!("Load app pk");
-    specialized_sdk.set_app_pk(app_pk).map_err(|_| ()).unwrap();
-    tracing::info!("Load agg pk");
-    specialized_sdk.set_agg_pk(agg_pk).map_err(|_| ()).unwrap();
+    timings.time("load_proving_keys", || {
+        tracing::info!("Load app pk");
+        specialized_sdk.set_app_pk(app_pk).map_err(|_| ()).unwrap();
+        tracing::info!("Load agg pk");
+        specialized_sdk.set_agg_pk(agg_pk).map_err(|_| ()).unwrap();
+    });
+    if let Some(report) = rss_report.as_mut() {
+        report.sample("proving key load");
+    }
 
-    let program_name = format!("reth.{}.block_{}", args.mode, args.block_number);
     // NOTE: args.benchmark.app_config resets SegmentationLimits if max_segment_length is set
     args.benchmark.max_segment_length = None;
 
+    // `ExecuteProfile` profiles the original program, which is the only thing `elf` is used for.
+    let original_exe = match args.mode {
+        BenchMode::ExecuteProfile => {
+            let sdk: GenericSdk<
+                BabyBearPoseidon2Engine,
+                ExtendedVmConfigCpuBuilder,
+                NativeCpuBuilder,
+            > = GenericSdk::new(app_config.clone())?;
+            Some(sdk.convert_to_exe(elf.clone())?)
+        }
+        _ => None,
+    };
+
     // `prover` can be called over both `elf` and `exe`.
     // We had a bug before where `prover(elf)` was called and silently didn't use any apcs.
     // So we drop `elf` here to make sure it's never used later.
     drop(elf);
 
-    run_with_metric_collection("OUTPUT_PATH", || {
-        info_span!("reth-block", block_number = args.block_number).in_scope(
-            || -> eyre::Result<()> {
-                // Run host execution for comparison
-                if !args.skip_comparison {
-                    let block_hash = info_span!("host.execute", group = program_name).in_scope(
-                        || -> eyre::Result<_> {
-                            let executor = ClientExecutor;
-                            // Create a child span to get the group label propagated
-                            let header = info_span!("client.execute").in_scope(|| {
-                                executor.execute(ChainVariant::Mainnet, client_input.clone())
-                            })?;
-                            let block_hash =
-                                info_span!("header.hash_slow").in_scope(|| header.hash_slow());
-                            Ok(block_hash)
-                        },
-                    )?;
-                    println!("block_hash (execute-host): {}", ToHexExt::encode_hex(&block_hash));
-                }
+    if verify_only {
+        let proof_path = args
+            .proof_path
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("--proof-path is required in verify-proof mode"))?;
+        let versioned_proof: VersionedVmStarkProof =
+            serde_json::from_slice(&fs::read(proof_path)?)?;
+        let block_hash = versioned_proof.user_public_values.clone();
+        let proof = versioned_proof.try_into()?;
+
+        let app_commit = specialized_sdk.prover(exe)?.app_commit();
+        let agg_vk = specialized_sdk.agg_pk().get_agg_vk();
+        #[cfg(feature = "cuda")]
+        let verified = PowdrSdkGpu::verify_proof(&agg_vk, app_commit, &proof);
+        #[cfg(not(feature = "cuda"))]
+        let verified = PowdrSdkCpu::verify_proof(&agg_vk, app_commit, &proof);
+        match verified {
+            Ok(_) => println!("proof verification: pass"),
+            Err(err) => {
+                println!("proof verification: fail");
+                return Err(err.into());
+            }
+        }
+        println!("block_hash (verify_proof): {}", ToHexExt::encode_hex(&block_hash));
+        return write_timings(&args, &timings);
+    }
 
-                // For ExecuteHost mode, only do host execution
-                if matches!(args.mode, BenchMode::ExecuteHost) {
-                    return Ok(());
+    let mut comparisons = Vec::new();
+
+    // Metric collection is set up once, with one `reth-block` span per block.
+    run_with_metric_collection("OUTPUT_PATH", || -> eyre::Result<()> {
+        // The state of each block is built lazily in this arena by host execution, and reset for
+        // the next block rather than leaked.
+        let mut host_bump = Bump::new();
+        for block_number in block_numbers {
+            host_bump.reset();
+            let client_input = match path_input.take() {
+                Some(client_input) => client_input,
+                None => {
+                    let start = Instant::now();
+                    // The loop is synchronous, as metric collection only wraps a closure, so the
+                    // fetch blocks this runtime thread.
+                    let client_input = tokio::task::block_in_place(|| {
+                        tokio::runtime::Handle::current().block_on(get_client_input(
+                            &provider_config,
+                            &args.cache_dir,
+                            chain_id,
+                            block_number,
+                        ))
+                    })?;
+                    let phase = if single_block {
+                        "get_client_input".to_string()
+                    } else {
+                        format!("get_client_input_block_{block_number}")
+                    };
+                    timings.record(phase, start.elapsed());
+                    info!("input loaded");
+                    if let Some(report) = rss_report.as_mut() {
+                        report.sample(format!("block {block_number} input load"));
+                    }
+                    client_input
                 }
+            };
+
+            let mut stdin = StdIn::default();
+            stdin.write(&client_input);
+            let exe = exe.clone();
+            let app_config = app_config.clone();
+            let program_name = format!("reth.{}.block_{}", args.mode, block_number);
+
+            let mut comparison = BlockHashComparison::new(block_number);
+            let range_block = (!single_block).then_some(block_number);
+            let start = Instant::now();
+            info_span!("reth-block", block_number = block_number).in_scope(
+                || -> eyre::Result<()> {
+                    // Run host execution for comparison
+                    if !args.skip_comparison {
+                        let block_hash = info_span!("host.execute", group = program_name)
+                            .in_scope(|| -> eyre::Result<_> {
+                                let executor = ClientExecutor;
+                                // Create a child span to get the group label propagated
+                                let header = info_span!("client.execute").in_scope(|| {
+                                    let execute = || {
+                                        executor.execute_with_spec_lazy_in(
+                                            chain_spec.clone(),
+                                            &client_input,
+                                            &host_bump,
+                                        )
+                                    };
+                                    if !args.count_allocs {
+                                        return execute();
+                                    }
+                                    let (header, stats) = alloc_counter::count_allocs(execute);
+                                    println!(
+                                        "allocations (execute-host): {} ({} bytes)",
+                                        stats.allocs, stats.bytes
+                                    );
+                                    header
+                                })?;
+                                let block_hash =
+                                    info_span!("header.hash_slow").in_scope(|| header.hash_slow());
+                                Ok(block_hash)
+                            })?;
+                        println!(
+                            "block_hash (execute-host): {}",
+                            ToHexExt::encode_hex(&block_hash)
+                        );
+                        comparison.host = Some(ToHexExt::encode_hex(&block_hash));
+                    }
 
-                // Execute for benchmarking:
-                if !args.skip_comparison {
-                    let pvs = info_span!("sdk.execute", group = program_name)
-                        .in_scope(|| specialized_sdk.execute(exe.clone(), stdin.clone()))?;
-                    let block_hash = pvs;
-                    println!("block_hash (execute): {}", ToHexExt::encode_hex(&block_hash));
-                }
+                    // For ExecuteHost mode, only do host execution
+                    if matches!(args.mode, BenchMode::ExecuteHost) {
+                        return Ok(());
+                    }
 
-                match args.mode {
-                    BenchMode::Compile => {
-                        // This mode is used to compile the program with APCs, no execution.
-                        println!("Compiled program with APCs");
+                    // Execute for benchmarking, always in execute mode as it is what it measures
+                    if !args.skip_comparison || matches!(args.mode, BenchMode::Execute) {
+                        let pvs = info_span!("sdk.execute", group = program_name)
+                            .in_scope(|| specialized_sdk.execute(exe.clone(), stdin.clone()))?;
+                        let block_hash = pvs;
+                        println!("block_hash (execute): {}", ToHexExt::encode_hex(&block_hash));
+                        comparison.execute = Some(ToHexExt::encode_hex(&block_hash));
                     }
-                    BenchMode::Execute => {}
-                    BenchMode::ExecuteMetered => {
-                        let engine = DefaultStarkEngine::new(app_config.app_fri_params.fri_params);
-                        let (vm, _) = VirtualMachine::new_with_keygen(
-                            engine,
+
+                    match args.mode {
+                        BenchMode::Compile => {
+                            // This mode is used to compile the program with APCs, no execution.
+                            println!("Compiled program with APCs");
+                        }
+                        BenchMode::Execute if args.count_instructions => {
+                            // The public values are all `execute` returns, so the instruction
+                            // count comes from metered execution.
+                            let vm_builder = specialized_sdk.app_vm_builder().clone();
+                            let vm_pk = specialized_sdk.app_pk().app_vm_pk.clone();
+                            let exe = specialized_sdk.convert_to_exe(exe.clone())?;
+                            let vm_instance: VmInstance<_, _> =
+                                new_local_prover(vm_builder, &vm_pk, exe.clone())?;
+                            let metered_ctx = vm_instance.vm.build_metered_ctx(&exe);
+                            let (segments, _) = vm_instance
+                                .vm
+                                .metered_interpreter(vm_instance.exe())?
+                                .execute_metered(stdin.clone(), metered_ctx)?;
+                            println!(
+                                "Total executed instructions: {}",
+                                trace_stats::total_instructions(&segments)
+                            );
+                        }
+                        BenchMode::Execute => {}
+                        BenchMode::ExecuteProfile => {
+                            let original_exe = original_exe.clone().unwrap();
+                            let sdk =
+                                PowdrExecutionProfileSdkCpu::<RiscvISA>::new(app_config.clone())?;
+                            let program = Prog::from(&original_exe.program);
+                            let profile = info_span!("execute_profile", group = program_name)
+                                .in_scope(|| {
+                                    execution_profile::<BabyBearOpenVmApcAdapter<RiscvISA>>(
+                                        &program,
+                                        || {
+                                            sdk.execute_interpreted(
+                                                original_exe.clone(),
+                                                stdin.clone(),
+                                            )
+                                            .unwrap();
+                                        },
+                                    )
+                                });
+
+                            let (top, total) = top_executed_pcs(&profile, args.profile_top_n);
+                            println!("Total executed instructions: {total}");
+                            println!("{:>12} {:>14} {:>8}  instruction", "pc", "count", "share");
+                            for (pc, count) in top {
+                                let instruction = original_exe
+                                    .program
+                                    .get_instruction_and_debug_info(
+                                        ((pc - original_exe.program.pc_base as u64) /
+                                            DEFAULT_PC_STEP as u64)
+                                            as usize,
+                                    )
+                                    .map(|(instruction, _)| format!("{:?}", instruction.opcode))
+                                    .unwrap_or_default();
+                                println!(
+                                    "{pc:>#12x} {count:>14} {:>7.2}%  {instruction}",
+                                    count as f64 * 100.0 / total as f64
+                                );
+                            }
+                        }
+                        BenchMode::ExecuteMetered => {
+                            let engine =
+                                DefaultStarkEngine::new(app_config.app_fri_params.fri_params);
+                            let (vm, _) = VirtualMachine::new_with_keygen(
+                                engine,
+                                #[cfg(feature = "cuda")]
+                                ExtendedVmConfigGpuBuilder,
+                                #[cfg(not(feature = "cuda"))]
+                                ExtendedVmConfigCpuBuilder,
+                                app_config.app_vm_config,
+                            )?;
+                            let executor_idx_to_air_idx = vm.executor_idx_to_air_idx();
+                            let interpreter =
+                                vm.executor().metered_instance(&exe, &executor_idx_to_air_idx)?;
+                            let metered_ctx = vm.build_metered_ctx(&exe);
+                            let (segments, _) =
+                                info_span!("interpreter.execute_metered", group = program_name)
+                                    .in_scope(|| interpreter.execute_metered(stdin, metered_ctx))?;
+                            println!("Number of segments: {}", segments.len());
+                            println!(
+                                "Total executed instructions: {}",
+                                trace_stats::total_instructions(&segments)
+                            );
+                            if let Some(path) = args.segments_json.as_ref() {
+                                trace_stats::write_segments_json(path, &segments)?;
+                            }
+                        }
+                        BenchMode::ProveMock => {
+                            // Build owned vm instance, so we can mutate it later
+                            let vm_builder = specialized_sdk.app_vm_builder().clone();
+                            let vm_pk = specialized_sdk.app_pk().app_vm_pk.clone();
+                            let exe = specialized_sdk.convert_to_exe(exe.clone())?;
+                            let mut vm_instance: VmInstance<_, _> =
+                                new_local_prover(vm_builder, &vm_pk, exe.clone())?;
+
+                            vm_instance.reset_state(stdin.clone());
+                            let metered_ctx = vm_instance.vm.build_metered_ctx(&exe);
+                            let metered_interpreter =
+                                vm_instance.vm.metered_interpreter(vm_instance.exe())?;
+                            let (segments, _) =
+                                metered_interpreter.execute_metered(stdin.clone(), metered_ctx)?;
+                            if let Some(path) = args.trace_stats_path.as_ref() {
+                                trace_stats::write_trace_stats(path, &vm_pk.vm_pk, &segments)?;
+                            }
+                            if let Some(max_height) = args.max_trace_height {
+                                trace_stats::check_max_trace_height(
+                                    &vm_pk.vm_pk,
+                                    &segments,
+                                    max_height,
+                                )?;
+                            }
+                            let mut state = vm_instance.state_mut().take();
+
+                            // Get reusable inputs for `debug_proving_ctx`, the mock prover API from
+                            // OVM.
+                            let vm = &mut vm_instance.vm;
+                            let air_inv = vm.config().create_airs().unwrap();
                             #[cfg(feature = "cuda")]
-                            ExtendedVmConfigGpuBuilder,
+                            let pk = air_inv.keygen::<GpuBabyBearPoseidon2Engine>(&vm.engine);
                             #[cfg(not(feature = "cuda"))]
-                            ExtendedVmConfigCpuBuilder,
-                            app_config.app_vm_config,
-                        )?;
-                        let executor_idx_to_air_idx = vm.executor_idx_to_air_idx();
-                        let interpreter =
-                            vm.executor().metered_instance(&exe, &executor_idx_to_air_idx)?;
-                        let metered_ctx = vm.build_metered_ctx(&exe);
-                        let (segments, _) =
-                            info_span!("interpreter.execute_metered", group = program_name)
-                                .in_scope(|| interpreter.execute_metered(stdin, metered_ctx))?;
-                        println!("Number of segments: {}", segments.len());
-                    }
-                    BenchMode::ProveMock => {
-                        // Build owned vm instance, so we can mutate it later
-                        let vm_builder = specialized_sdk.app_vm_builder().clone();
-                        let vm_pk = specialized_sdk.app_pk().app_vm_pk.clone();
-                        let exe = specialized_sdk.convert_to_exe(exe.clone())?;
-                        let mut vm_instance: VmInstance<_, _> =
-                            new_local_prover(vm_builder, &vm_pk, exe.clone())?;
-
-                        vm_instance.reset_state(stdin.clone());
-                        let metered_ctx = vm_instance.vm.build_metered_ctx(&exe);
-                        let metered_interpreter =
-                            vm_instance.vm.metered_interpreter(vm_instance.exe())?;
-                        let (segments, _) =
-                            metered_interpreter.execute_metered(stdin.clone(), metered_ctx)?;
-                        let mut state = vm_instance.state_mut().take();
-
-                        // Get reusable inputs for `debug_proving_ctx`, the mock prover API from
-                        // OVM.
-                        let vm = &mut vm_instance.vm;
-                        let air_inv = vm.config().create_airs().unwrap();
-                        #[cfg(feature = "cuda")]
-                        let pk = air_inv.keygen::<GpuBabyBearPoseidon2Engine>(&vm.engine);
-                        #[cfg(not(feature = "cuda"))]
-                        let pk = air_inv.keygen::<BabyBearPoseidon2Engine>(&vm.engine);
-
-                        for (seg_idx, segment) in segments.into_iter().enumerate() {
-                            let _segment_span =
-                                info_span!("prove_segment", segment = seg_idx).entered();
-                            // We need a separate span so the metric label includes "segment" from
-                            // _segment_span
-                            let _prove_span = info_span!("total_proof").entered();
-                            let Segment { instret_start: _, num_insns, trace_heights } = segment;
-                            let from_state = Option::take(&mut state).unwrap();
-                            vm.transport_init_memory_to_device(&from_state.memory);
-                            let PreflightExecutionOutput {
-                                system_records,
-                                record_arenas,
-                                to_state,
-                            } = vm.execute_preflight(
-                                &mut vm_instance.interpreter,
-                                from_state,
-                                Some(num_insns),
-                                &trace_heights,
+                            let pk = air_inv.keygen::<BabyBearPoseidon2Engine>(&vm.engine);
+
+                            for (seg_idx, segment) in segments.into_iter().enumerate() {
+                                let _segment_span =
+                                    info_span!("prove_segment", segment = seg_idx).entered();
+                                // We need a separate span so the metric label includes "segment"
+                                // from _segment_span
+                                let _prove_span = info_span!("total_proof").entered();
+                                let Segment { instret_start: _, num_insns, trace_heights } =
+                                    segment;
+                                let from_state = Option::take(&mut state).unwrap();
+                                vm.transport_init_memory_to_device(&from_state.memory);
+                                let PreflightExecutionOutput {
+                                    system_records,
+                                    record_arenas,
+                                    to_state,
+                                } = vm.execute_preflight(
+                                    &mut vm_instance.interpreter,
+                                    from_state,
+                                    Some(num_insns),
+                                    &trace_heights,
+                                )?;
+                                state = Some(to_state);
+
+                                // Generate proving context for each segment
+                                let ctx = vm.generate_proving_ctx(system_records, record_arenas)?;
+
+                                // Run the mock prover for each segment
+                                debug_proving_ctx(vm, &pk, &ctx);
+                            }
+                        }
+                        BenchMode::ProveApp => {
+                            if args.trace_stats_path.is_some() || args.max_trace_height.is_some() {
+                                let vm_builder = specialized_sdk.app_vm_builder().clone();
+                                let vm_pk = specialized_sdk.app_pk().app_vm_pk.clone();
+                                let exe = specialized_sdk.convert_to_exe(exe.clone())?;
+                                let vm_instance: VmInstance<_, _> =
+                                    new_local_prover(vm_builder, &vm_pk, exe.clone())?;
+                                let metered_ctx = vm_instance.vm.build_metered_ctx(&exe);
+                                let (segments, _) = vm_instance
+                                    .vm
+                                    .metered_interpreter(vm_instance.exe())?
+                                    .execute_metered(stdin.clone(), metered_ctx)?;
+                                if let Some(path) = args.trace_stats_path.as_ref() {
+                                    trace_stats::write_trace_stats(path, &vm_pk.vm_pk, &segments)?;
+                                }
+                                if let Some(max_height) = args.max_trace_height {
+                                    trace_stats::check_max_trace_height(
+                                        &vm_pk.vm_pk,
+                                        &segments,
+                                        max_height,
+                                    )?;
+                                }
+                            }
+                            let (_, app_vk) = specialized_sdk.app_keygen();
+                            let proof = match args.checkpoint_dir.as_ref() {
+                                Some(checkpoint_dir) => {
+                                    let input_hash =
+                                        alloy_primitives::keccak256(bincode::serde::encode_to_vec(
+                                            &client_input,
+                                            bincode::config::standard(),
+                                        )?);
+                                    let segment_dir = checkpoint::segment_dir(
+                                        checkpoint_dir,
+                                        &args.apc_setup_name,
+                                        elf_hash,
+                                        block_number,
+                                        input_hash,
+                                    );
+                                    let vm_builder = specialized_sdk.app_vm_builder().clone();
+                                    let vm_pk = specialized_sdk.app_pk().app_vm_pk.clone();
+                                    let exe = specialized_sdk.convert_to_exe(exe.clone())?;
+                                    let mut vm_instance: VmInstance<_, _> =
+                                        new_local_prover(vm_builder, &vm_pk, exe.clone())?;
+
+                                    vm_instance.reset_state(stdin.clone());
+                                    let metered_ctx = vm_instance.vm.build_metered_ctx(&exe);
+                                    let metered_interpreter =
+                                        vm_instance.vm.metered_interpreter(vm_instance.exe())?;
+                                    let (segments, _) = metered_interpreter
+                                        .execute_metered(stdin.clone(), metered_ctx)?;
+                                    let mut state = vm_instance.state_mut().take();
+
+                                    let vm = &mut vm_instance.vm;
+                                    let mut per_segment = Vec::with_capacity(segments.len());
+                                    for (seg_idx, segment) in segments.into_iter().enumerate() {
+                                        let Segment { instret_start: _, num_insns, trace_heights } =
+                                            segment;
+                                        // The VM state is not serializable, so segments that are
+                                        // already proven are still executed to reach the next
+                                        // segment's starting state.
+                                        let from_state = Option::take(&mut state).unwrap();
+                                        vm.transport_init_memory_to_device(&from_state.memory);
+                                        let PreflightExecutionOutput {
+                                            system_records,
+                                            record_arenas,
+                                            to_state,
+                                        } = vm.execute_preflight(
+                                            &mut vm_instance.interpreter,
+                                            from_state,
+                                            Some(num_insns),
+                                            &trace_heights,
+                                        )?;
+                                        state = Some(to_state);
+
+                                        let checkpointed =
+                                            checkpoint::read_segment_proof(&segment_dir, seg_idx)?;
+                                        let proof = if let Some(proof) = checkpointed {
+                                            println!("Resuming segment {seg_idx} from checkpoint");
+                                            proof
+                                        } else {
+                                            let _segment_span =
+                                                info_span!("prove_segment", segment = seg_idx)
+                                                    .entered();
+                                            let ctx = vm.generate_proving_ctx(
+                                                system_records,
+                                                record_arenas,
+                                            )?;
+                                            let proof = vm.engine.prove(vm.pk(), ctx);
+                                            checkpoint::write_segment_proof(
+                                                &segment_dir,
+                                                seg_idx,
+                                                &proof,
+                                            )?;
+                                            proof
+                                        };
+                                        per_segment.push(proof);
+                                    }
+
+                                    let final_memory = &state.unwrap().memory.memory;
+                                    let system_config: &SystemConfig = vm.config().as_ref();
+                                    let user_public_values = UserPublicValuesProof::compute(
+                                        system_config.memory_config.memory_dimensions(),
+                                        system_config.num_public_values,
+                                        &vm_poseidon2_hasher(),
+                                        final_memory,
+                                    );
+                                    ContinuationVmProof { per_segment, user_public_values }
+                                }
+                                None => {
+                                    let mut prover = specialized_sdk
+                                        .app_prover(exe)?
+                                        .with_program_name(program_name);
+                                    prover.prove(stdin)?
+                                }
+                            };
+                            let proof_size = bitcode::serialize(&proof)?.len();
+                            report_proof_size(
+                                args.output_dir.as_ref(),
+                                &args.mode,
+                                range_block,
+                                proof_size,
                             )?;
-                            state = Some(to_state);
-
-                            // Generate proving context for each segment
-                            let ctx = vm.generate_proving_ctx(system_records, record_arenas)?;
-
-                            // Run the mock prover for each segment
-                            debug_proving_ctx(vm, &pk, &ctx);
+                            verify_app_proof(&app_vk, &proof)?;
                         }
-                    }
-                    BenchMode::ProveApp => {
-                        let mut prover =
-                            specialized_sdk.app_prover(exe)?.with_program_name(program_name);
-                        let (_, app_vk) = specialized_sdk.app_keygen();
-                        let proof = prover.prove(stdin)?;
-                        verify_app_proof(&app_vk, &proof)?;
-                    }
-                    BenchMode::ProveStark => {
-                        let mut prover =
-                            specialized_sdk.prover(exe)?.with_program_name(program_name);
-                        let proof = prover.prove(stdin)?;
-                        let block_hash = proof
-                            .user_public_values
-                            .iter()
-                            .map(|pv| pv.as_canonical_u32() as u8)
-                            .collect::<Vec<u8>>();
-                        println!("block_hash (prove_stark): {}", ToHexExt::encode_hex(&block_hash));
-
-                        if let Some(output_dir) = args.output_dir.as_ref() {
+                        BenchMode::ProveStark => {
+                            let mut prover =
+                                specialized_sdk.prover(exe)?.with_program_name(program_name);
+                            let proof = prover.prove(stdin)?;
+                            let block_hash = proof
+                                .user_public_values
+                                .iter()
+                                .map(|pv| pv.as_canonical_u32() as u8)
+                                .collect::<Vec<u8>>();
+                            println!(
+                                "block_hash (prove_stark): {}",
+                                ToHexExt::encode_hex(&block_hash)
+                            );
+                            comparison.proved = Some(ToHexExt::encode_hex(&block_hash));
+
                             let versioned_proof = VersionedVmStarkProof::new(proof)?;
                             let json = serde_json::to_vec_pretty(&versioned_proof)?;
-                            fs::write(output_dir.join("proof.json"), json)?;
-                            println!("wrote proof json to {}", output_dir.display());
+                            report_proof_size(
+                                args.output_dir.as_ref(),
+                                &args.mode,
+                                range_block,
+                                json.len(),
+                            )?;
+                            if let Some(output_dir) = args.output_dir.as_ref() {
+                                let file_name = if single_block {
+                                    "proof.json".to_string()
+                                } else {
+                                    format!("proof_{block_number}.json")
+                                };
+                                fs::write(output_dir.join(file_name), json)?;
+                                println!("wrote proof json to {}", output_dir.display());
+                            }
+                        }
+                        #[cfg(feature = "evm-verify")]
+                        BenchMode::ProveEvm => {
+                            let mut prover =
+                                specialized_sdk.evm_prover(exe)?.with_program_name(program_name);
+                            let halo2_pk = specialized_sdk.halo2_pk();
+                            tracing::info!(
+                                "halo2_outer_k: {}",
+                                halo2_pk.verifier.pinning.metadata.config_params.k
+                            );
+                            tracing::info!(
+                                "halo2_wrapper_k: {}",
+                                halo2_pk.wrapper.pinning.metadata.config_params.k
+                            );
+                            let proof = prover.prove_evm(stdin)?;
+                            let proof_size = serde_json::to_vec(&proof)?.len();
+                            report_proof_size(
+                                args.output_dir.as_ref(),
+                                &args.mode,
+                                range_block,
+                                proof_size,
+                            )?;
+                            let block_hash = &proof.user_public_values;
+                            println!(
+                                "block_hash (prove_evm): {}",
+                                ToHexExt::encode_hex(block_hash)
+                            );
+                            comparison.proved = Some(ToHexExt::encode_hex(block_hash));
+                        }
+                        BenchMode::GenerateFixtures => {
+                            let mut prover =
+                                specialized_sdk.prover(exe)?.with_program_name(program_name);
+                            let app_proof = prover.app_prover.prove(stdin)?;
+                            let leaf_proofs = prover.agg_prover.generate_leaf_proofs(&app_proof)?;
+                            let fixture_path = args.fixtures_path.clone().unwrap();
+
+                            let mut app_proof_path = fixture_path.clone();
+                            app_proof_path.push("app_proof.bitcode");
+                            fs::write(app_proof_path, bitcode::serialize(&app_proof)?)?;
+
+                            let mut leaf_proofs_path = fixture_path.clone();
+                            leaf_proofs_path.push("leaf_proofs.bitcode");
+                            fs::write(leaf_proofs_path, bitcode::serialize(&leaf_proofs)?)?;
+
+                            let mut app_pk_path = fixture_path.clone();
+                            app_pk_path.push("app_pk.bitcode");
+                            fs::write(app_pk_path, bitcode::serialize(specialized_sdk.app_pk())?)?;
+
+                            let mut agg_pk_path = fixture_path.clone();
+                            agg_pk_path.push("agg_pk.bitcode");
+                            fs::write(agg_pk_path, bitcode::serialize(specialized_sdk.agg_pk())?)?;
+                        }
+                        _ => {
+                            // This case is handled earlier and should not reach here
+                            unreachable!();
                         }
                     }
-                    #[cfg(feature = "evm-verify")]
-                    BenchMode::ProveEvm => {
-                        let mut prover =
-                            specialized_sdk.evm_prover(exe)?.with_program_name(program_name);
-                        let halo2_pk = specialized_sdk.halo2_pk();
-                        tracing::info!(
-                            "halo2_outer_k: {}",
-                            halo2_pk.verifier.pinning.metadata.config_params.k
-                        );
-                        tracing::info!(
-                            "halo2_wrapper_k: {}",
-                            halo2_pk.wrapper.pinning.metadata.config_params.k
-                        );
-                        let proof = prover.prove_evm(stdin)?;
-                        let block_hash = &proof.user_public_values;
-                        println!("block_hash (prove_evm): {}", ToHexExt::encode_hex(block_hash));
-                    }
-                    BenchMode::GenerateFixtures => {
-                        let mut prover =
-                            specialized_sdk.prover(exe)?.with_program_name(program_name);
-                        let app_proof = prover.app_prover.prove(stdin)?;
-                        let leaf_proofs = prover.agg_prover.generate_leaf_proofs(&app_proof)?;
-                        let fixture_path = args.fixtures_path.unwrap();
-
-                        let mut app_proof_path = fixture_path.clone();
-                        app_proof_path.push("app_proof.bitcode");
-                        fs::write(app_proof_path, bitcode::serialize(&app_proof)?)?;
-
-                        let mut leaf_proofs_path = fixture_path.clone();
-                        leaf_proofs_path.push("leaf_proofs.bitcode");
-                        fs::write(leaf_proofs_path, bitcode::serialize(&leaf_proofs)?)?;
-
-                        let mut app_pk_path = fixture_path.clone();
-                        app_pk_path.push("app_pk.bitcode");
-                        fs::write(app_pk_path, bitcode::serialize(specialized_sdk.app_pk())?)?;
-
-                        let mut agg_pk_path = fixture_path.clone();
-                        agg_pk_path.push("agg_pk.bitcode");
-                        fs::write(agg_pk_path, bitcode::serialize(specialized_sdk.agg_pk())?)?;
-                    }
-                    _ => {
-                        // This case is handled earlier and should not reach here
-                        unreachable!();
-                    }
-                }
 
-                Ok(())
-            },
-        )
+                    Ok(())
+                },
+            )?;
+            timings.record(format!("{}_block_{block_number}", args.mode), start.elapsed());
+            comparison.update_match();
+            comparisons.push(comparison);
+            if let Some(report) = rss_report.as_mut() {
+                report.sample(format!("block {block_number} {}", args.mode));
+            }
+        }
+        Ok(())
     })?;
+
+    if let Some(report) = rss_report.as_ref() {
+        report.print_summary();
+    }
+    write_timings(&args, &timings)?;
+    comparison::report_comparisons(args.comparison_json.as_deref(), &comparisons)
+}
+
+fn write_timings(args: &HostArgs, timings: &PhaseTimings) -> eyre::Result<()> {
+    if let Some(path) = args.timings_json.as_ref() {
+        timings.write_json(path)?;
+    }
+    Ok(())
+}
+
+/// Returns the `n` most executed pcs of an execution profile, most executed first with ties broken
+/// by pc, along with the total number of executed instructions.
+fn top_executed_pcs(profile: &HashMap<u64, u32>, n: usize) -> (Vec<(u64, u32)>, u64) {
+    let total = profile.values().map(|&count| count as u64).sum();
+    let mut pcs = profile.iter().map(|(&pc, &count)| (pc, count)).collect::<Vec<_>>();
+    pcs.sort_unstable_by(|(pc_a, count_a), (pc_b, count_b)| {
+        count_b.cmp(count_a).then(pc_a.cmp(pc_b))
+    });
+    pcs.truncate(n);
+    (pcs, total)
+}
+
+/// Logs the serialized proof size and, if an output dir is set, writes it to `proof_size` there.
+/// In a block range, `range_block` is the block the proof is for, and the size is written to
+/// `proof_size_{range_block}` instead.
+fn report_proof_size(
+    output_dir: Option<&PathBuf>,
+    mode: &BenchMode,
+    range_block: Option<u64>,
+    proof_size: usize,
+) -> eyre::Result<()> {
+    println!("proof size ({mode}): {proof_size} bytes");
+    if let Some(output_dir) = output_dir {
+        let file_name = match range_block {
+            Some(block_number) => format!("proof_size_{block_number}"),
+            None => "proof_size".to_string(),
+        };
+        fs::write(output_dir.join(file_name), proof_size.to_string())?;
+    }
     Ok(())
 }
 
@@ -638,8 +1288,11 @@ fn try_load_input_from_cache(
         if cache_path.exists() {
             // TODO: prune the cache if invalid instead
             let mut cache_file = std::fs::File::open(cache_path)?;
-            let client_input: ClientExecutorInput =
+            let mut client_input: ClientExecutorInput =
                 bincode::serde::decode_from_std_read(&mut cache_file, bincode::config::standard())?;
+            // Inputs cached before the bytecodes were deduplicated would otherwise fail in the
+            // guest
+            client_input.dedup_bytecodes();
 
             Some(client_input)
         } else {
@@ -673,7 +1326,14 @@ mod powdr {
     /// - `original_program`: The original program, including the original vm config.
     /// - `apc`: The number of apcs to generate
     /// - `apc_skip`: The number of apcs to skip when selecting. Used for debugging.
-    /// - `pgo_type`: The PGO strategy to use when choosing the blocks to accelerate.
+    /// - `pgo_type`: The PGO strategy to use when choosing the blocks to accelerate. It also fixes
+    ///   the order in which candidate blocks are ranked, which is deterministic for a given program
+    ///   and PGO input:
+    ///   - `none`: no execution profile, blocks are ranked by their static size.
+    ///   - `instruction`: blocks are ranked by the number of instructions they executed in the PGO
+    ///     runs, i.e. execution count times block size.
+    ///   - `cell`: blocks are ranked by the estimated number of trace cells saved by their APC,
+    ///     which is what the "percent saved" figures in the candidate dumps refer to.
     /// - `pgo_stdin`: The standard inputs to the program used for PGO data generation to choose
     ///   which basic blocks to accelerate.
     pub fn apc(
@@ -764,3 +1424,127 @@ mod powdr {
         empirical_constraints
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(extra_args: &[&str]) -> HostArgs {
+        let args = [
+            "host",
+            "--block-number",
+            "23992138",
+            "--mode",
+            "execute",
+            "--apc-cache-dir",
+            "apc-cache",
+            "--apc-setup-name",
+            "setup",
+            "--apc-skip",
+            "0",
+        ];
+        complete_args(HostArgs::try_parse_from(args.iter().chain(extra_args)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_pgo_block_numbers() {
+        // nothing is profiled without APCs or without PGO
+        let args = parse_args(&["--apc", "0", "--pgo-type", "cell"]);
+        assert!(args.pgo_block_numbers.is_empty());
+        let args = parse_args(&["--apc", "10", "--pgo-type", "none"]);
+        assert!(args.pgo_block_numbers.is_empty());
+        assert_eq!(args.apc_cache_path(), Path::new("apc-cache/setup.bin"));
+
+        let args = parse_args(&["--apc", "10", "--pgo-type", "cell"]);
+        assert_eq!(args.pgo_block_numbers, DEFAULT_PGO_BLOCK_NUMBERS);
+
+        let one_block = parse_args(&["--apc", "10", "--pgo-type", "cell", "--pgo-blocks", "1"]);
+        let two_blocks = parse_args(&["--apc", "10", "--pgo-type", "cell", "--pgo-blocks", "1,2"]);
+        assert_eq!(two_blocks.pgo_block_numbers, vec![1, 2]);
+        assert_eq!(one_block.apc_cache_path(), Path::new("apc-cache/setup_1.bin"));
+        assert_eq!(two_blocks.apc_cache_path(), Path::new("apc-cache/setup_1_2.bin"));
+    }
+
+    #[test]
+    fn test_report_proof_size() {
+        let dir = std::env::temp_dir().join("openvm-reth-benchmark-proof-size");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let output_dir = Some(&dir);
+
+        report_proof_size(output_dir, &BenchMode::ProveStark, None, 100).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("proof_size")).unwrap(), "100");
+
+        // the blocks of a range do not overwrite each other's sizes
+        report_proof_size(output_dir, &BenchMode::ProveStark, Some(1), 200).unwrap();
+        report_proof_size(output_dir, &BenchMode::ProveStark, Some(2), 300).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("proof_size_1")).unwrap(), "200");
+        assert_eq!(fs::read_to_string(dir.join("proof_size_2")).unwrap(), "300");
+        assert_eq!(fs::read_to_string(dir.join("proof_size")).unwrap(), "100");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_block_range() {
+        assert_eq!(parse_block_range("23992138..23992140"), Ok(23992138..23992140));
+        assert!(parse_block_range("23992140..23992138").is_err());
+        assert!(parse_block_range("23992138..23992138").is_err());
+        assert!(parse_block_range("23992138").is_err());
+        assert!(parse_block_range("a..b").is_err());
+    }
+
+    #[test]
+    fn test_input_json_round_trip() {
+        let mut current_block = reth_primitives::Block::default();
+        current_block.header.number = 23992138;
+        let client_input = ClientExecutorInput {
+            current_block,
+            ancestor_headers: vec![reth_primitives::Header::default()],
+            parent_state_bytes: openvm_mpt::EthereumState::new().encode_to_state_bytes(),
+            bytecodes: vec![],
+        };
+
+        // An input written in make_input mode is read back for the other modes
+        let path = std::env::temp_dir().join("openvm-reth-benchmark-input.json");
+        fs::write(&path, encode_input_json(&client_input)).unwrap();
+        let decoded = read_input_json(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(decoded.current_block.header.number, 23992138);
+        assert_eq!(encode_input_json(&decoded), encode_input_json(&client_input));
+        assert!(decode_input_json(r#"{"input": ["0x02"]}"#).is_err());
+
+        // An input written before the bytecodes were deduplicated is deduplicated when read
+        let duplicated = ClientExecutorInput {
+            bytecodes: vec![Default::default(), Default::default()],
+            ..client_input
+        };
+        let decoded = decode_input_json(&encode_input_json(&duplicated)).unwrap();
+        assert_eq!(decoded.bytecodes.len(), 1);
+    }
+
+    #[test]
+    fn test_argument_errors() {
+        assert!(check_app_log_blowup(APP_LOG_BLOWUP).is_ok());
+        let err = check_app_log_blowup(APP_LOG_BLOWUP + 1).unwrap_err().to_string();
+        assert!(err.contains(&format!("--app-log-blowup {}", APP_LOG_BLOWUP + 1)), "{err}");
+        assert!(err.contains(&format!("must be {APP_LOG_BLOWUP}")), "{err}");
+
+        let message = unknown_chain_id_message(10);
+        assert!(message.starts_with("unknown chain ID: 10."), "{message}");
+        assert!(message.contains("1, 11155111, 17000"), "{message}");
+        assert!(message.contains("--chain-spec"), "{message}");
+    }
+
+    #[test]
+    fn test_top_executed_pcs() {
+        let profile = HashMap::from([(0x200800, 5), (0x200804, 10), (0x200808, 5), (0x20080c, 1)]);
+        let (top, total) = top_executed_pcs(&profile, 3);
+        assert_eq!(total, 21);
+        assert_eq!(top, vec![(0x200804, 10), (0x200800, 5), (0x200808, 5)]);
+
+        let (all, _) = top_executed_pcs(&profile, 10);
+        assert_eq!(all.iter().map(|&(_, count)| count as u64).sum::<u64>(), total);
+    }
+}
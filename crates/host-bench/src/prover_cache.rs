@@ -0,0 +1,209 @@
+use alloy_primitives::{keccak256, B256};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Version of the prover data cache format, written as the first 4 bytes of the file. Bump it
+/// whenever the layout of the cached data changes, e.g. when updating openvm or powdr.
+pub const CACHE_FORMAT_VERSION: u32 = 3;
+
+/// Compression applied to the cache payload, recorded in the byte following the version.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CacheCompression {
+    #[default]
+    None,
+    Zstd,
+}
+
+impl CacheCompression {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zstd => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::None),
+            1 => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a cache file written by [`write_cache`], with any compression. Returns `None` if the file
+/// does not exist, or if it was written with another format version or cannot be decoded, in
+/// which case the caller should recompute the data.
+pub fn read_cache<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let mut file = BufReader::new(File::open(path).ok()?);
+
+    let mut header = [0u8; 5];
+    let compression = file
+        .read_exact(&mut header)
+        .ok()
+        .filter(|_| u32::from_le_bytes(header[..4].try_into().unwrap()) == CACHE_FORMAT_VERSION)
+        .and_then(|_| CacheCompression::from_byte(header[4]));
+    let Some(compression) = compression else {
+        tracing::warn!("Cache format changed for {}, recomputing", path.display());
+        return None;
+    };
+
+    let config = bincode::config::standard();
+    let decoded = match compression {
+        CacheCompression::None => bincode::serde::decode_from_std_read(&mut file, config),
+        CacheCompression::Zstd => match zstd::Decoder::new(file) {
+            Ok(mut decoder) => bincode::serde::decode_from_std_read(&mut decoder, config),
+            Err(err) => {
+                tracing::warn!("Failed to open cache {}: {err}, recomputing", path.display());
+                return None;
+            }
+        },
+    };
+    match decoded {
+        Ok(data) => Some(data),
+        Err(err) => {
+            tracing::warn!("Failed to decode cache {}: {err}, recomputing", path.display());
+            None
+        }
+    }
+}
+
+/// Same as [`read_cache`], but also returns how long reading and decoding the cache took.
+pub fn read_cache_timed<T: DeserializeOwned>(path: &Path) -> Option<(T, Duration)> {
+    let start = Instant::now();
+    let data = read_cache(path)?;
+    Some((data, start.elapsed()))
+}
+
+/// Fails if `cached_elf_hash`, the keccak hash of the ELF that cached prover data was computed
+/// from, is not the hash of `elf`. The cached program is specialized with APCs, so it cannot be
+/// compared to the ELF directly: a stale cache would otherwise silently run another program.
+pub fn check_elf_hash(cached_elf_hash: B256, elf: &[u8]) -> eyre::Result<()> {
+    let elf_hash = keccak256(elf);
+    if elf_hash != cached_elf_hash {
+        eyre::bail!(
+            "the cached prover data was computed from the ELF with hash {cached_elf_hash}, but the \
+             embedded ELF has hash {elf_hash}: remove the stale cache or use another \
+             --apc-setup-name"
+        );
+    }
+    Ok(())
+}
+
+/// Writes `data` to a cache file, prefixed with the [`CACHE_FORMAT_VERSION`] and the compression.
+pub fn write_cache<T: Serialize>(
+    path: &Path,
+    data: &T,
+    compression: CacheCompression,
+) -> eyre::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&CACHE_FORMAT_VERSION.to_le_bytes())?;
+    file.write_all(&[compression.to_byte()])?;
+
+    let config = bincode::config::standard();
+    match compression {
+        CacheCompression::None => {
+            bincode::serde::encode_into_std_write(data, &mut file, config)?;
+            file.flush()?;
+        }
+        CacheCompression::Zstd => {
+            let mut encoder = zstd::Encoder::new(file, 0)?;
+            bincode::serde::encode_into_std_write(data, &mut encoder, config)?;
+            encoder.finish()?.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_format_version() {
+        let dir = std::env::temp_dir().join("openvm-reth-benchmark-prover-cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("setup.bin");
+        let data = (vec![1u64, 2, 3], "program".to_string());
+
+        assert_eq!(read_cache::<(Vec<u64>, String)>(&path), None);
+
+        write_cache(&path, &data, CacheCompression::None).unwrap();
+        assert_eq!(read_cache(&path), Some(data.clone()));
+
+        // A cache written with another format version is recomputed instead of panicking
+        let mut stale = (CACHE_FORMAT_VERSION - 1).to_le_bytes().to_vec();
+        stale.extend(bincode::serde::encode_to_vec(&data, bincode::config::standard()).unwrap());
+        std::fs::write(&path, stale).unwrap();
+        assert_eq!(read_cache::<(Vec<u64>, String)>(&path), None);
+
+        // So is a cache from before the version tag was added
+        std::fs::write(
+            &path,
+            bincode::serde::encode_to_vec(&data, bincode::config::standard()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(read_cache::<(Vec<u64>, String)>(&path), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_cache_timed() {
+        let dir = std::env::temp_dir().join("openvm-reth-benchmark-prover-cache-timed");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("setup.bin");
+        let data = vec![3u64; 1 << 16];
+        write_cache(&path, &data, CacheCompression::Zstd).unwrap();
+
+        let start = Instant::now();
+        let (loaded, elapsed) = read_cache_timed::<Vec<u64>>(&path).unwrap();
+        // measured around the load itself, not anything after it
+        std::thread::sleep(Duration::from_millis(50));
+        let total = start.elapsed();
+        assert_eq!(loaded, data);
+        assert!(elapsed > Duration::ZERO);
+        assert!(elapsed < total - Duration::from_millis(50), "{elapsed:?} of {total:?}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(read_cache_timed::<Vec<u64>>(&path).is_none());
+    }
+
+    #[test]
+    fn test_check_elf_hash() {
+        let elf = b"\x7fELF client".as_slice();
+        check_elf_hash(keccak256(elf), elf).unwrap();
+
+        // a cache computed from an older build of the client
+        let err = check_elf_hash(keccak256(b"\x7fELF old client"), elf).unwrap_err();
+        assert!(err.to_string().contains("stale cache"), "{err}");
+    }
+
+    #[test]
+    fn test_cache_compression_round_trip() {
+        let dir = std::env::temp_dir().join("openvm-reth-benchmark-prover-cache-zstd");
+        std::fs::create_dir_all(&dir).unwrap();
+        let data = (vec![7u64; 4096], "program".repeat(64));
+
+        let plain_path = dir.join("plain.bin");
+        write_cache(&plain_path, &data, CacheCompression::None).unwrap();
+        let zstd_path = dir.join("zstd.bin");
+        write_cache(&zstd_path, &data, CacheCompression::Zstd).unwrap();
+
+        let plain: (Vec<u64>, String) = read_cache(&plain_path).unwrap();
+        let compressed: (Vec<u64>, String) = read_cache(&zstd_path).unwrap();
+        assert_eq!(plain, data);
+        assert_eq!(compressed, plain);
+        assert!(
+            std::fs::metadata(&zstd_path).unwrap().len() <
+                std::fs::metadata(&plain_path).unwrap().len()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,51 @@
+/// Peak resident set size of the current process in kB, or `None` if it cannot be read on this
+/// platform.
+pub fn peak_rss_kb() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmHWM:"))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Peak RSS samples taken at phase boundaries.
+#[derive(Debug, Default)]
+pub struct PeakRssReport {
+    samples: Vec<(String, Option<u64>)>,
+}
+
+impl PeakRssReport {
+    /// Records the peak RSS at the end of `phase`.
+    pub fn sample(&mut self, phase: impl Into<String>) {
+        self.samples.push((phase.into(), peak_rss_kb()));
+    }
+
+    /// Prints the recorded samples as a table.
+    pub fn print_summary(&self) {
+        println!("{:<40} {:>16}", "phase", "peak RSS (MiB)");
+        for (phase, kb) in &self.samples {
+            match kb {
+                Some(kb) => println!("{phase:<40} {:>16}", kb / 1024),
+                None => println!("{phase:<40} {:>16}", "n/a"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_peak_rss_kb() {
+        assert!(peak_rss_kb().unwrap() > 0);
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Wall-clock durations of the benchmark phases, in seconds.
+#[derive(Debug, Default)]
+pub struct PhaseTimings {
+    phases: BTreeMap<String, f64>,
+}
+
+impl PhaseTimings {
+    /// Records `elapsed` for `phase`, adding to any time already recorded for it.
+    pub fn record(&mut self, phase: impl Into<String>, elapsed: Duration) {
+        let phase = phase.into();
+        println!(">>> Time to {phase}: {elapsed:?}");
+        *self.phases.entry(phase).or_default() += elapsed.as_secs_f64();
+    }
+
+    /// Runs `f` and records its duration for `phase`.
+    pub fn time<R>(&mut self, phase: impl Into<String>, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    /// Writes the timings as a `{phase_name: seconds}` JSON object.
+    pub fn write_json(&self, path: &Path) -> eyre::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(&self.phases)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timings_json() {
+        let mut timings = PhaseTimings::default();
+        timings.record("get_client_input", Duration::from_millis(1500));
+        let sum = timings.time("elf_decode", || 1 + 1);
+        assert_eq!(sum, 2);
+        timings.record("create_sdk", Duration::from_secs(2));
+        timings.record("create_sdk", Duration::from_secs(1));
+
+        let path = std::env::temp_dir().join("openvm-reth-benchmark-timings.json");
+        timings.write_json(&path).unwrap();
+        let json: BTreeMap<String, f64> =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            json.keys().map(String::as_str).collect::<Vec<_>>(),
+            ["create_sdk", "elf_decode", "get_client_input"]
+        );
+        assert_eq!(json["get_client_input"], 1.5);
+        assert_eq!(json["create_sdk"], 3.0);
+    }
+}
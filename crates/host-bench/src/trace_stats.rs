@@ -0,0 +1,199 @@
+use openvm_circuit::arch::execution_mode::Segment;
+use openvm_sdk::SC;
+use openvm_stark_sdk::openvm_stark_backend::keygen::types::MultiStarkProvingKey;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// Trace dimensions of one AIR in one segment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AirTraceStats {
+    pub air_name: String,
+    pub height: usize,
+    pub width: usize,
+    pub cells: usize,
+}
+
+/// Where a segment starts and how much work it holds, as found by metered execution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentSummary {
+    pub instret_start: u64,
+    pub num_insns: u64,
+    /// Trace height of each AIR, indexed like the AIRs of the proving key.
+    pub trace_heights: Vec<u32>,
+}
+
+impl From<&Segment> for SegmentSummary {
+    fn from(segment: &Segment) -> Self {
+        Self {
+            instret_start: segment.instret_start,
+            num_insns: segment.num_insns,
+            trace_heights: segment.trace_heights.clone(),
+        }
+    }
+}
+
+/// Writes the start, instruction count and trace heights of each segment to `path` as JSON.
+pub fn write_segments_json(path: &Path, segments: &[Segment]) -> eyre::Result<()> {
+    let summaries = segments.iter().map(SegmentSummary::from).collect::<Vec<_>>();
+    fs::write(path, serde_json::to_vec_pretty(&summaries)?)?;
+    println!("wrote {} segments to {}", summaries.len(), path.display());
+    Ok(())
+}
+
+/// Total number of instructions executed over all the segments.
+pub fn total_instructions(segments: &[Segment]) -> u64 {
+    segments.iter().map(|segment| segment.num_insns).sum()
+}
+
+/// Computes the per-AIR trace stats of each segment. `airs` holds the name and main trace width
+/// of each AIR, indexed like `Segment::trace_heights`. AIRs with an empty trace are skipped.
+pub fn segment_trace_stats(
+    airs: &[(String, usize)],
+    segments: &[Segment],
+) -> Vec<Vec<AirTraceStats>> {
+    segments
+        .iter()
+        .map(|segment| {
+            airs.iter()
+                .zip(&segment.trace_heights)
+                .filter(|(_, &height)| height > 0)
+                .map(|((air_name, width), &height)| AirTraceStats {
+                    air_name: air_name.clone(),
+                    height: height as usize,
+                    width: *width,
+                    cells: height as usize * width,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Writes the per-AIR trace stats of each segment to `path` as JSON.
+pub fn write_trace_stats(
+    path: &Path,
+    pk: &MultiStarkProvingKey<SC>,
+    segments: &[Segment],
+) -> eyre::Result<()> {
+    let airs = pk
+        .per_air
+        .iter()
+        .map(|air| (air.air_name.clone(), air.vk.params.width.main_width()))
+        .collect::<Vec<_>>();
+    let stats = segment_trace_stats(&airs, segments);
+    fs::write(path, serde_json::to_vec_pretty(&stats)?)?;
+    println!("wrote trace stats to {}", path.display());
+    Ok(())
+}
+
+/// Fails on the first AIR of a segment whose trace is taller than `max_height`, so that a run gives
+/// up before proving instead of failing deep inside the prover. `air_names` is indexed like
+/// `Segment::trace_heights`.
+pub fn check_trace_heights(
+    air_names: &[String],
+    segments: &[Segment],
+    max_height: u32,
+) -> eyre::Result<()> {
+    for (seg_idx, segment) in segments.iter().enumerate() {
+        for (air_idx, &height) in segment.trace_heights.iter().enumerate() {
+            if height > max_height {
+                let air_name = air_names.get(air_idx).map_or("unknown", String::as_str);
+                eyre::bail!(
+                    "segment {seg_idx}: trace height {height} of AIR {air_idx} ({air_name}) \
+                     exceeds --max-trace-height {max_height}"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Same as [`check_trace_heights`], with the AIR names of `pk`.
+pub fn check_max_trace_height(
+    pk: &MultiStarkProvingKey<SC>,
+    segments: &[Segment],
+    max_height: u32,
+) -> eyre::Result<()> {
+    let air_names = pk.per_air.iter().map(|air| air.air_name.clone()).collect::<Vec<_>>();
+    check_trace_heights(&air_names, segments, max_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_trace_stats() {
+        let airs = vec![("ProgramAir".to_string(), 10), ("Rv32BaseAluAir".to_string(), 36)];
+        let segments = vec![
+            Segment { instret_start: 0, num_insns: 100, trace_heights: vec![64, 128] },
+            Segment { instret_start: 100, num_insns: 50, trace_heights: vec![64, 0] },
+        ];
+
+        let stats = segment_trace_stats(&airs, &segments);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].len(), 2);
+        assert_eq!(stats[1].len(), 1);
+        for (segment, segment_stats) in segments.iter().zip(&stats) {
+            let cells: usize = segment_stats.iter().map(|air| air.cells).sum();
+            let expected: usize = airs
+                .iter()
+                .zip(&segment.trace_heights)
+                .map(|((_, width), &height)| width * height as usize)
+                .sum();
+            assert_eq!(cells, expected);
+        }
+
+        let json = serde_json::to_string(&stats).unwrap();
+        let decoded: Vec<Vec<AirTraceStats>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, stats);
+    }
+
+    #[test]
+    fn test_total_instructions() {
+        let segments = vec![
+            Segment { instret_start: 0, num_insns: 100, trace_heights: vec![64, 128] },
+            Segment { instret_start: 100, num_insns: 50, trace_heights: vec![64, 0] },
+        ];
+        let total = total_instructions(&segments);
+        assert_eq!(total, 150);
+        // the segments split the execution without gaps
+        let last = segments.last().unwrap();
+        assert_eq!(total, last.instret_start + last.num_insns);
+        assert_eq!(total_instructions(&[]), 0);
+    }
+
+    #[test]
+    fn test_check_trace_heights() {
+        let air_names = vec!["ProgramAir".to_string(), "Rv32BaseAluAir".to_string()];
+        let segments = vec![
+            Segment { instret_start: 0, num_insns: 100, trace_heights: vec![64, 128] },
+            Segment { instret_start: 100, num_insns: 50, trace_heights: vec![64, 1 << 23] },
+        ];
+
+        check_trace_heights(&air_names, &segments, 1 << 23).unwrap();
+        let err = check_trace_heights(&air_names, &segments, 1 << 22).unwrap_err().to_string();
+        assert!(err.contains("segment 1"), "{err}");
+        assert!(err.contains("Rv32BaseAluAir"), "{err}");
+        assert!(err.contains(&(1 << 23).to_string()), "{err}");
+    }
+
+    #[test]
+    fn test_segments_json() {
+        let segments = vec![
+            Segment { instret_start: 0, num_insns: 100, trace_heights: vec![64, 128] },
+            Segment { instret_start: 100, num_insns: 50, trace_heights: vec![64, 0] },
+            Segment { instret_start: 150, num_insns: 7, trace_heights: vec![8, 8] },
+        ];
+
+        let path = std::env::temp_dir().join("openvm-reth-benchmark-segments.json");
+        write_segments_json(&path, &segments).unwrap();
+        let decoded: Vec<SegmentSummary> =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // the same count as the printed `Number of segments`
+        assert_eq!(decoded.len(), segments.len());
+        assert_eq!(decoded[1], SegmentSummary::from(&segments[1]));
+        assert_eq!(decoded[2].instret_start, 150);
+    }
+}
@@ -1,15 +1,59 @@
+use alloy_primitives::{keccak256, B256, U256};
 use bincode::config::standard;
+use bumpalo::Bump;
 use criterion::{criterion_group, criterion_main, Criterion};
-use openvm_client_executor::io::{ClientExecutorInput, ClientExecutorInputWithState};
-use openvm_primitives::chain_spec::mainnet;
-use reth_evm::execute::{BasicBlockExecutor, Executor};
-use reth_evm_ethereum::EthEvmConfig;
+use openvm_client_executor::{
+    io::{ClientExecutorInput, ClientExecutorInputWithState},
+    ChainVariant, ClientExecutor,
+};
 use reth_execution_types::ExecutionOutcome;
-use reth_primitives_traits::Block;
-use reth_revm::db::CacheDB;
-use std::{fs, hint::black_box, sync::Arc};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs,
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// Number of allocations made since the start of the process, read by [`count_allocations`].
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting every allocation and reallocation.
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
 
-fn benchmark_mpt_operations(c: &mut Criterion) {
+/// Runs `f` and returns its result along with the number of allocations it made.
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    let result = f();
+    (result, ALLOCATIONS.load(Ordering::Relaxed) - start)
+}
+
+/// Loads the benchmark data file and pre-computes the post-state of the block.
+fn load_block(
+) -> (Vec<u8>, ClientExecutorInput, ClientExecutorInputWithState<'static>, ExecutionOutcome) {
     // Load the benchmark data file (this is not counted in benchmark timing)
     // Check for BLOCK environment variable, default to 23992138
     let block_number = std::env::var("BLOCK").unwrap_or_else(|_| "23992138".to_string());
@@ -24,21 +68,30 @@ fn benchmark_mpt_operations(c: &mut Criterion) {
     let bincode_config = standard();
 
     // Pre-compute the post-state once for the MPT benchmarks (not timed)
-    let (pre_input, _): (ClientExecutorInput, _) =
+    let (mut pre_input, _): (ClientExecutorInput, _) =
         bincode::serde::decode_from_slice(&buffer, bincode_config).unwrap();
+    // Data generated before the bytecodes were deduplicated would fail to build the witness
+    // database, so it is deduplicated and encoded again for the deserializing benchmarks
+    pre_input.dedup_bytecodes();
+    let buffer = bincode::serde::encode_to_vec(&pre_input, bincode_config).unwrap();
+    let (_, executor_outcome) =
+        ClientExecutor.execute_with_outcome(ChainVariant::Mainnet, pre_input.clone()).unwrap();
     let client_input = ClientExecutorInputWithState::build(pre_input.clone()).unwrap();
-    let witness_db = client_input.witness_db().unwrap();
-    let cache_db = CacheDB::new(&witness_db);
-    let spec = Arc::new(mainnet());
-    let current_block = client_input.input.current_block.clone().try_into_recovered().unwrap();
-    let block_executor = BasicBlockExecutor::new(EthEvmConfig::new(spec), cache_db);
-    let executor_output = block_executor.execute(&current_block).unwrap();
-    let executor_outcome = ExecutionOutcome::new(
-        executor_output.state,
-        vec![executor_output.result.receipts],
-        client_input.input.current_block.header.number,
-        vec![executor_output.result.requests],
-    );
+
+    (buffer, pre_input, client_input, executor_outcome)
+}
+
+/// Arenas for `par_update_from_bundle_state`, one per core. The states of the benchmarks live in a
+/// leaked arena, so these are leaked too, once.
+fn par_update_arenas() -> &'static [Bump] {
+    let num_arenas = std::thread::available_parallelism().map_or(1, |n| n.get());
+    Vec::leak((0..num_arenas).map(|_| Bump::new()).collect())
+}
+
+fn benchmark_mpt_operations(c: &mut Criterion) {
+    let (buffer, pre_input, client_input, executor_outcome) = load_block();
+    let arenas = par_update_arenas();
+    let bincode_config = standard();
 
     // Benchmark the realistic end-to-end workflow (deserialize -> witness_db -> mpt_update)
     // This excludes block execution since that's not what you want to measure
@@ -84,6 +137,49 @@ fn benchmark_mpt_operations(c: &mut Criterion) {
         })
     });
 
+    // The hashed storage slots read by the block, as `WitnessDb::storage_ref` reads them
+    let storage_reads = executor_outcome
+        .bundle
+        .state
+        .iter()
+        .flat_map(|(address, account)| {
+            let hashed_address = keccak256(address);
+            account
+                .storage
+                .keys()
+                .map(move |slot| (hashed_address, keccak256(slot.to_be_bytes::<32>())))
+        })
+        .collect::<Vec<(B256, B256)>>();
+
+    c.bench_function("witness db storage reads (get_rlp)", |b| {
+        b.iter(|| {
+            for (hashed_address, hashed_slot) in &storage_reads {
+                let Some(storage_trie) = client_input.state.storage_trie(hashed_address).unwrap()
+                else {
+                    continue;
+                };
+                let value = storage_trie.get_rlp::<U256>(hashed_slot.as_slice()).unwrap();
+                black_box(value.unwrap_or_default());
+            }
+        })
+    });
+
+    c.bench_function("witness db storage reads (get_rlp_into)", |b| {
+        b.iter(|| {
+            let mut value = U256::ZERO;
+            for (hashed_address, hashed_slot) in &storage_reads {
+                let Some(storage_trie) = client_input.state.storage_trie(hashed_address).unwrap()
+                else {
+                    continue;
+                };
+                value = U256::ZERO;
+                storage_trie.get_rlp_into(hashed_slot.as_slice(), &mut value).unwrap();
+                black_box(&value);
+            }
+            value
+        })
+    });
+
     c.bench_function("update only", |b| {
         b.iter_with_setup(
             || {
@@ -97,6 +193,19 @@ fn benchmark_mpt_operations(c: &mut Criterion) {
         )
     });
 
+    c.bench_function("update only (parallel)", |b| {
+        b.iter_with_setup(
+            || {
+                // Setup: This part is NOT timed
+                client_input.state.clone()
+            },
+            |mut parent_state| {
+                // Routine: This part IS timed
+                parent_state.par_update_from_bundle_state(&executor_outcome.bundle, arenas)
+            },
+        )
+    });
+
     c.bench_function("state root only", |b| {
         b.iter_with_setup(
             || {
@@ -114,5 +223,28 @@ fn benchmark_mpt_operations(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, benchmark_mpt_operations);
+/// Prints the number of heap allocations of the update and state root paths, which drive the
+/// zkVM cycle cost more than wall time does.
+fn benchmark_mpt_allocations(_c: &mut Criterion) {
+    let (_, _, client_input, executor_outcome) = load_block();
+
+    let mut parent_state = client_input.state.clone();
+    let (_, update) = count_allocations(|| {
+        parent_state.update_from_bundle_state(&executor_outcome.bundle).unwrap()
+    });
+    let (_, state_root) = count_allocations(|| black_box(parent_state.state_trie.hash()));
+
+    let arenas = par_update_arenas();
+    let mut parent_state = client_input.state.clone();
+    let (_, par_update) = count_allocations(|| {
+        parent_state.par_update_from_bundle_state(&executor_outcome.bundle, arenas).unwrap()
+    });
+
+    println!("Allocations per operation:");
+    println!("  update only:            {}", update);
+    println!("  update only (parallel): {}", par_update);
+    println!("  state root only:        {}", state_root);
+}
+
+criterion_group!(benches, benchmark_mpt_operations, benchmark_mpt_allocations);
 criterion_main!(benches);
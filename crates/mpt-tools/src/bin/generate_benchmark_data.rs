@@ -1,7 +1,11 @@
 use alloy_provider::RootProvider;
 use bincode::config::standard;
 use openvm_host_executor::HostExecutor;
-use std::env;
+use std::{
+    env,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
 use tracing_subscriber::{
     filter::EnvFilter, fmt, prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt,
 };
@@ -10,9 +14,11 @@ use url::Url;
 fn print_usage() {
     println!("Usage: cargo run --bin generate_benchmark_data");
     println!("       BLOCK=18884864 cargo run --bin generate_benchmark_data");
+    println!("       BLOCKS=18884864-18884870 cargo run --bin generate_benchmark_data");
     println!();
     println!("Environment:");
     println!("  BLOCK    Block number to fetch (default: 23992138)");
+    println!("  BLOCKS   Inclusive range of blocks to fetch, overrides BLOCK");
     println!("  RPC_1    Ethereum RPC endpoint (required)");
     println!();
     println!("Output: <block_number>.bin");
@@ -28,17 +34,20 @@ async fn main() -> eyre::Result<()> {
         return Ok(());
     }
 
-    // Get block number from environment
-    let block_number = env::var("BLOCK")
-        .unwrap_or_else(|_| "23992138".to_string())
-        .parse::<u64>()
-        .unwrap_or_else(|_| panic!("Invalid BLOCK number"));
-
-    let output_file = format!("{}.bin", block_number);
+    // Get block numbers from environment
+    let block_numbers = match env::var("BLOCKS") {
+        Ok(blocks) => parse_block_range(&blocks),
+        Err(_) => {
+            let block_number = env::var("BLOCK")
+                .unwrap_or_else(|_| "23992138".to_string())
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("Invalid BLOCK number"));
+            block_number..=block_number
+        }
+    };
 
     println!("Benchmark Data Generator");
-    println!("Block number: {}", block_number);
-    println!("Output file: {}", output_file);
+    println!("Blocks: {}-{}", block_numbers.start(), block_numbers.end());
     println!();
 
     // Initialize the environment variables.
@@ -57,9 +66,47 @@ async fn main() -> eyre::Result<()> {
     )?;
     let provider = RootProvider::new_http(rpc_url);
 
-    // Setup the host executor.
+    // Setup the host executor, shared by all blocks.
     let host_executor = HostExecutor::new(provider);
 
+    for block_number in block_numbers.clone() {
+        let output_file = generate_block(&host_executor, block_number, Path::new(".")).await?;
+        println!("Successfully generated benchmark data:");
+        println!("  File: {}", output_file.display());
+        println!("  Size: {} bytes", std::fs::metadata(&output_file)?.len());
+        println!();
+    }
+
+    println!("Next steps:");
+    println!("  BLOCK={} cargo run --bin mpt_profiler", block_numbers.start());
+    println!("  BLOCK={} cargo bench", block_numbers.start());
+
+    Ok(())
+}
+
+/// Parses an inclusive `start-end` block range, or a single block number.
+fn parse_block_range(blocks: &str) -> RangeInclusive<u64> {
+    let parse = |block: &str| {
+        block.trim().parse::<u64>().unwrap_or_else(|_| panic!("Invalid block number {}", block))
+    };
+    let range = match blocks.split_once('-') {
+        Some((start, end)) => parse(start)..=parse(end),
+        None => parse(blocks)..=parse(blocks),
+    };
+    assert!(!range.is_empty(), "Invalid BLOCKS range {}", blocks);
+    range
+}
+
+/// Fetches the client input of `block_number` and writes it to `<block_number>.bin` in `dir`.
+async fn generate_block(
+    host_executor: &HostExecutor<RootProvider>,
+    block_number: u64,
+    dir: &Path,
+) -> eyre::Result<PathBuf> {
+    let output_file = dir.join(format!("{}.bin", block_number));
+    println!("Block number: {}", block_number);
+    println!("Output file: {}", output_file.display());
+
     println!("Fetching block data from RPC...");
     // Execute the host.
     let client_input = host_executor.execute(block_number).await?;
@@ -71,13 +118,40 @@ async fn main() -> eyre::Result<()> {
 
     // Save the buffer to a file for benchmarking
     std::fs::write(&output_file, &buffer)?;
-    println!("Successfully generated benchmark data:");
-    println!("  File: {}", output_file);
-    println!("  Size: {} bytes", buffer.len());
-    println!();
-    println!("Next steps:");
-    println!("  BLOCK={} cargo run --bin mpt_profiler", block_number);
-    println!("  BLOCK={} cargo bench", block_number);
+    Ok(output_file)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_block_range() {
+        assert_eq!(parse_block_range("10-12"), 10..=12);
+        assert_eq!(parse_block_range("10"), 10..=10);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_generate_block_range() {
+        // Only runs when an RPC endpoint is configured.
+        dotenv::dotenv().ok();
+        let Ok(rpc_url) = std::env::var("RPC_1") else {
+            return;
+        };
+        let host_executor = HostExecutor::new(RootProvider::new_http(rpc_url.parse().unwrap()));
+
+        let dir = env::temp_dir().join("openvm-reth-benchmark-generate-data");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut files = Vec::new();
+        for block_number in parse_block_range("23992138-23992139") {
+            files.push(generate_block(&host_executor, block_number, &dir).await.unwrap());
+        }
+
+        let contents: Vec<_> = files.iter().map(|file| std::fs::read(file).unwrap()).collect();
+        assert_eq!(contents.len(), 2);
+        assert!(contents.iter().all(|content| !content.is_empty()));
+        assert_ne!(contents[0], contents[1]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -2,16 +2,50 @@
 static ALLOC: dhat::Alloc = dhat::Alloc;
 
 use bincode::config::standard;
-use dhat::Profiler;
-use openvm_client_executor::io::{ClientExecutorInput, ClientExecutorInputWithState};
+use dhat::{HeapStats, Profiler};
+use openvm_client_executor::{
+    io::{ClientExecutorInput, ClientExecutorInputWithState},
+    ChainVariant, ClientExecutor,
+};
 use openvm_mpt::EthereumState;
-use openvm_primitives::chain_spec::mainnet;
-use reth_evm::execute::{BasicBlockExecutor, Executor};
-use reth_evm_ethereum::EthEvmConfig;
 use reth_execution_types::ExecutionOutcome;
-use reth_primitives_traits::Block;
-use reth_revm::db::CacheDB;
-use std::{env, fs, sync::Arc};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Operations profiled one after another by the `csv` mode.
+const CSV_OPERATIONS: [&str; 5] = ["end-to-end", "deserialize", "witness", "update", "state-root"];
+
+/// Inputs of the profiled operations, computed before any profiler is started.
+struct BlockData {
+    buffer: Vec<u8>,
+    pre_input: ClientExecutorInput,
+    parent_state: EthereumState<'static>,
+    executor_outcome: ExecutionOutcome,
+}
+
+/// Heap stats of one profiled operation, i.e. one row of the CSV export.
+#[derive(Debug)]
+struct ProfileRow {
+    operation: String,
+    total_bytes: u64,
+    max_bytes: usize,
+    total_blocks: u64,
+    max_blocks: usize,
+}
+
+impl ProfileRow {
+    fn new(operation: &str, stats: &HeapStats) -> Self {
+        Self {
+            operation: operation.to_string(),
+            total_bytes: stats.total_bytes,
+            max_bytes: stats.max_bytes,
+            total_blocks: stats.total_blocks,
+            max_blocks: stats.max_blocks,
+        }
+    }
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -24,81 +58,207 @@ fn main() {
 
     // Get operation from args
     let operation = if args.len() > 1 { args[1].as_str() } else { "all" };
+    if !matches!(operation, "all" | "csv") && !CSV_OPERATIONS.contains(&operation) {
+        println!("Unknown operation: {}", operation);
+        print_usage();
+        return;
+    }
 
-    // Get block number from environment
-    let block_number = env::var("BLOCK")
-        .unwrap_or_else(|_| "23992138".to_string())
-        .parse::<u64>()
-        .unwrap_or_else(|_| panic!("Invalid BLOCK number"));
-
-    let input_file = format!("{}.bin", block_number);
+    // Get block numbers from environment
+    let block_numbers = match env::var("BLOCKS") {
+        Ok(blocks) => parse_block_numbers(&blocks),
+        Err(_) => vec![env::var("BLOCK")
+            .unwrap_or_else(|_| "23992138".to_string())
+            .parse::<u64>()
+            .unwrap_or_else(|_| panic!("Invalid BLOCK number"))],
+    };
 
     println!("MPT Memory Profiler");
     println!("Operation: {}", operation);
-    println!("Block: {}", block_number);
-    println!("Input file: {}", input_file);
+    println!("Blocks: {:?}", block_numbers);
     println!();
 
-    // Load the benchmark data file
-    let buffer = fs::read(&input_file)
-        .unwrap_or_else(|_| panic!("Failed to read benchmark data from '{}'. Run 'BLOCK={} cargo run --bin generate_benchmark_data' first to generate it.", input_file, block_number));
+    let batch = block_numbers.len() > 1;
+    let profiles = profile_blocks(&block_numbers, Path::new("."), |block_number, buffer| {
+        profile_block(operation, block_number, buffer, batch)
+    });
+
+    if batch {
+        print_summary(&profiles);
+    }
+
+    println!("Profiling complete! Check the generated .dhat files.");
+}
+
+/// Parses a comma-separated list of block numbers.
+fn parse_block_numbers(blocks: &str) -> Vec<u64> {
+    blocks
+        .split(',')
+        .map(|block| {
+            block.trim().parse::<u64>().unwrap_or_else(|_| panic!("Invalid block number {}", block))
+        })
+        .collect()
+}
+
+/// Loads the `<block>.bin` file of each block from `dir` and profiles it with `profile`.
+fn profile_blocks(
+    block_numbers: &[u64],
+    dir: &Path,
+    mut profile: impl FnMut(u64, Vec<u8>) -> Vec<ProfileRow>,
+) -> Vec<(u64, Vec<ProfileRow>)> {
+    block_numbers
+        .iter()
+        .map(|&block_number| {
+            let input_file = dir.join(format!("{}.bin", block_number));
+            println!("Block: {}", block_number);
+            println!("Input file: {}", input_file.display());
+
+            // Load the benchmark data file
+            let buffer = fs::read(&input_file)
+                .unwrap_or_else(|_| panic!("Failed to read benchmark data from '{}'. Run 'BLOCK={} cargo run --bin generate_benchmark_data' first to generate it.", input_file.display(), block_number));
 
-    println!("Loaded benchmark data: {} bytes", buffer.len());
+            println!("Loaded benchmark data: {} bytes", buffer.len());
+            (block_number, profile(block_number, buffer))
+        })
+        .collect()
+}
 
+/// Profiles `operation`, or every operation in `csv` mode, on one block. In `batch` mode the
+/// `.dhat` file is named after the block, so that the next block does not overwrite it.
+fn profile_block(
+    operation: &str,
+    block_number: u64,
+    buffer: Vec<u8>,
+    batch: bool,
+) -> Vec<ProfileRow> {
     let bincode_config = standard();
 
     // Pre-compute the post-state once
-    let (pre_input, _): (ClientExecutorInput, _) =
+    let (mut pre_input, _): (ClientExecutorInput, _) =
         bincode::serde::decode_from_slice(&buffer, bincode_config).unwrap();
+    // Data generated before the bytecodes were deduplicated would fail to build the witness
+    // database, so it is deduplicated and encoded again for the deserializing profiles
+    pre_input.dedup_bytecodes();
+    let buffer = bincode::serde::encode_to_vec(&pre_input, bincode_config).unwrap();
+    let (_, executor_outcome) =
+        ClientExecutor.execute_with_outcome(ChainVariant::Mainnet, pre_input.clone()).unwrap();
     let client_input = ClientExecutorInputWithState::build(pre_input.clone()).unwrap();
-    let witness_db = client_input.witness_db().unwrap();
-    let cache_db = CacheDB::new(&witness_db);
-    let spec = Arc::new(mainnet());
-    let current_block = client_input.input.current_block.clone().try_into_recovered().unwrap();
-    let block_executor = BasicBlockExecutor::new(EthEvmConfig::new(spec), cache_db);
-    let executor_output = block_executor.execute(&current_block).unwrap();
-    let executor_outcome = ExecutionOutcome::new(
-        executor_output.state,
-        vec![executor_output.result.receipts],
-        client_input.input.current_block.header.number,
-        vec![executor_output.result.requests],
-    );
+
+    let data = BlockData { buffer, pre_input, parent_state: client_input.state, executor_outcome };
 
     println!("Starting profiling...");
 
-    match operation {
+    if operation == "csv" {
+        let csv_path = PathBuf::from(format!("{}-profile.csv", block_number));
+        let rows = profile_operations_csv(
+            &CSV_OPERATIONS,
+            Path::new("."),
+            block_number,
+            |operation, file| profile_operation(operation, &data, file).unwrap(),
+        );
+        write_csv(&csv_path, &rows).expect("Failed to write the CSV file");
+        println!("Wrote {}", csv_path.display());
+        return rows;
+    }
+
+    let dhat_file = if batch {
+        PathBuf::from(format!("dhat-heap-{}.json", block_number))
+    } else {
+        PathBuf::from("dhat-heap.json")
+    };
+    let stats = profile_operation(operation, &data, &dhat_file).unwrap();
+    vec![ProfileRow::new(operation, &stats)]
+}
+
+/// Prints the heap stats of every profiled operation of every block, to compare blocks.
+fn print_summary(profiles: &[(u64, Vec<ProfileRow>)]) {
+    println!();
+    println!(
+        "{:<12} {:<12} {:>16} {:>16} {:>14} {:>14}",
+        "block", "operation", "total_bytes", "max_bytes", "total_blocks", "max_blocks"
+    );
+    for (block_number, rows) in profiles {
+        for row in rows {
+            println!(
+                "{:<12} {:<12} {:>16} {:>16} {:>14} {:>14}",
+                block_number,
+                row.operation,
+                row.total_bytes,
+                row.max_bytes,
+                row.total_blocks,
+                row.max_blocks
+            );
+        }
+    }
+    println!();
+}
+
+/// Profiles `operation` into `dhat_file`, or returns `None` if the operation is unknown.
+fn profile_operation(operation: &str, data: &BlockData, dhat_file: &Path) -> Option<HeapStats> {
+    let stats = match operation {
         "all" | "end-to-end" => {
             println!("Profiling: End-to-end workflow (without execution)");
-            profile_end_to_end(&buffer, &executor_outcome);
+            profile_end_to_end(dhat_file, &data.buffer, &data.executor_outcome)
         }
         "deserialize" => {
             println!("Profiling: Deserialization only");
-            profile_deserialize(&buffer);
+            profile_deserialize(dhat_file, &data.buffer)
         }
         "witness" => {
             println!("Profiling: Witness DB creation only");
-            profile_witness_db(pre_input);
+            profile_witness_db(dhat_file, data.pre_input.clone())
         }
         "update" => {
             println!("Profiling: MPT update only");
-            profile_update(client_input.state, &executor_outcome);
+            profile_update(dhat_file, data.parent_state.clone(), &data.executor_outcome)
         }
         "state-root" => {
             println!("Profiling: Update and state root computation only");
-            profile_state_root(client_input.state, &executor_outcome);
-        }
-        _ => {
-            println!("Unknown operation: {}", operation);
-            print_usage();
-            return;
+            profile_state_root(dhat_file, data.parent_state.clone(), &data.executor_outcome)
         }
+        _ => return None,
+    };
+    Some(stats)
+}
+
+/// Profiles each of `operations` of a block with `profile`, which gets the operation and the
+/// `.dhat` file to write in `out_dir`.
+fn profile_operations_csv(
+    operations: &[&str],
+    out_dir: &Path,
+    block_number: u64,
+    mut profile: impl FnMut(&str, &Path) -> HeapStats,
+) -> Vec<ProfileRow> {
+    operations
+        .iter()
+        .map(|&operation| {
+            let dhat_file = out_dir.join(format!("dhat-heap-{}-{}.json", block_number, operation));
+            ProfileRow::new(operation, &profile(operation, &dhat_file))
+        })
+        .collect()
+}
+
+fn write_csv(path: &Path, rows: &[ProfileRow]) -> std::io::Result<()> {
+    let mut csv = String::from("operation,total_bytes,max_bytes,total_blocks,max_blocks\n");
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            row.operation, row.total_bytes, row.max_bytes, row.total_blocks, row.max_blocks
+        ));
     }
+    fs::write(path, csv)
+}
 
-    println!("Profiling complete! Check the generated .dhat file.");
+fn start_profiler(dhat_file: &Path) -> Profiler {
+    Profiler::builder().file_name(dhat_file).build()
 }
 
-fn profile_end_to_end(buffer: &[u8], executor_outcome: &ExecutionOutcome) {
-    let _profiler = Profiler::new_heap();
+fn profile_end_to_end(
+    dhat_file: &Path,
+    buffer: &[u8],
+    executor_outcome: &ExecutionOutcome,
+) -> HeapStats {
+    let _profiler = start_profiler(dhat_file);
     let bincode_config = standard();
 
     // Deserialize
@@ -113,35 +273,48 @@ fn profile_end_to_end(buffer: &[u8], executor_outcome: &ExecutionOutcome) {
     // Update MPT with pre-computed post-state
     client_input.state.update_from_bundle_state(&executor_outcome.bundle).unwrap();
     let _state_root = client_input.state.state_trie.hash();
+    HeapStats::get()
 }
 
-fn profile_deserialize(buffer: &[u8]) {
-    let _profiler = Profiler::new_heap();
+fn profile_deserialize(dhat_file: &Path, buffer: &[u8]) -> HeapStats {
+    let _profiler = start_profiler(dhat_file);
     let bincode_config = standard();
 
     let (_client_input, _): (ClientExecutorInput, _) =
         bincode::serde::decode_from_slice(buffer, bincode_config).unwrap();
+    HeapStats::get()
 }
 
-fn profile_witness_db(client_input: ClientExecutorInput) {
-    let _profiler = Profiler::new_heap();
+fn profile_witness_db(dhat_file: &Path, client_input: ClientExecutorInput) -> HeapStats {
+    let _profiler = start_profiler(dhat_file);
 
     let input = ClientExecutorInputWithState::build(client_input).unwrap();
 
     let _witness_db = input.witness_db().unwrap();
+    HeapStats::get()
 }
 
-fn profile_update(mut parent_state: EthereumState, executor_outcome: &ExecutionOutcome) {
-    let _profiler = Profiler::new_heap();
+fn profile_update(
+    dhat_file: &Path,
+    mut parent_state: EthereumState<'_>,
+    executor_outcome: &ExecutionOutcome,
+) -> HeapStats {
+    let _profiler = start_profiler(dhat_file);
 
     parent_state.update_from_bundle_state(&executor_outcome.bundle).unwrap();
+    HeapStats::get()
 }
 
-fn profile_state_root(mut parent_state: EthereumState, executor_outcome: &ExecutionOutcome) {
-    let _profiler = Profiler::new_heap();
+fn profile_state_root(
+    dhat_file: &Path,
+    mut parent_state: EthereumState<'_>,
+    executor_outcome: &ExecutionOutcome,
+) -> HeapStats {
+    let _profiler = start_profiler(dhat_file);
 
     parent_state.update_from_bundle_state(&executor_outcome.bundle).unwrap();
     let _state_root = parent_state.state_trie.hash();
+    HeapStats::get()
 }
 
 fn print_usage() {
@@ -160,4 +333,76 @@ fn print_usage() {
     println!("  witness      Witness DB creation only");
     println!("  update       MPT update only");
     println!("  state-root   State root computation only");
+    println!("  csv          Profile each operation and write <BLOCK>-profile.csv");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// dhat only allows one running profiler per process.
+    static PROFILER_LOCK: Mutex<()> = Mutex::new(());
+
+    /// A tiny synthetic workload in place of the operations on a real block.
+    fn profile_synthetic(dhat_file: &Path) -> HeapStats {
+        let _profiler = start_profiler(dhat_file);
+        let mut state = EthereumState::new();
+        state.state_trie.insert(&[0x12; 32], b"value").unwrap();
+        std::hint::black_box(&state);
+        HeapStats::get()
+    }
+
+    #[test]
+    fn test_profile_operations_csv() {
+        let _lock = PROFILER_LOCK.lock().unwrap();
+        let dir = env::temp_dir().join("openvm-reth-benchmark-mpt-profiler");
+        fs::create_dir_all(&dir).unwrap();
+
+        let rows = profile_operations_csv(&CSV_OPERATIONS, &dir, 1, |_, dhat_file| {
+            profile_synthetic(dhat_file)
+        });
+        let csv_path = dir.join("profile.csv");
+        write_csv(&csv_path, &rows).unwrap();
+
+        let csv = fs::read_to_string(&csv_path).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("operation,total_bytes,max_bytes,total_blocks,max_blocks"));
+        let operations: Vec<_> = lines.map(|line| line.split(',').next().unwrap()).collect();
+        assert_eq!(operations, CSV_OPERATIONS);
+        for operation in CSV_OPERATIONS {
+            assert!(dir.join(format!("dhat-heap-1-{}.json", operation)).exists());
+        }
+        assert!(rows.iter().all(|row| row.total_bytes > 0 && row.max_bytes > 0));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_profile_blocks() {
+        let _lock = PROFILER_LOCK.lock().unwrap();
+        let dir = env::temp_dir().join("openvm-reth-benchmark-mpt-profiler-blocks");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("1.bin"), [1u8; 4]).unwrap();
+        fs::write(dir.join("2.bin"), [2u8; 8]).unwrap();
+
+        let block_numbers = parse_block_numbers("1, 2");
+        assert_eq!(block_numbers, [1, 2]);
+        let mut loaded = Vec::new();
+        let profiles = profile_blocks(&block_numbers, &dir, |block_number, buffer| {
+            loaded.push((block_number, buffer));
+            profile_operations_csv(&["update"], &dir, block_number, |_, dhat_file| {
+                profile_synthetic(dhat_file)
+            })
+        });
+
+        assert_eq!(loaded, [(1, vec![1u8; 4]), (2, vec![2u8; 8])]);
+        assert_eq!(profiles.iter().map(|(block, _)| *block).collect::<Vec<_>>(), [1, 2]);
+        for (block_number, rows) in &profiles {
+            assert_eq!(rows.len(), 1);
+            assert!(dir.join(format!("dhat-heap-{}-update.json", block_number)).exists());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -6,14 +6,24 @@ use crate::{
     hp::{prefix_to_nibs, to_encoded_path},
     node::{NodeData, NodeId},
     owned::MptOwned,
-    Error, EthereumState,
+    Error, EthereumState, Mpt,
 };
 
-/// Parses proof bytes into a vector of tries.
+/// Parses proof bytes into a vector of tries. Decoding failures are reported with the index of
+/// the offending node.
 fn parse_proof(proof: &[impl AsRef<[u8]>]) -> Result<Vec<MptOwned>, Error> {
     proof
         .iter()
-        .map(|bytes| MptOwned::decode_from_proof_rlp(&mut bytes.as_ref()))
+        .enumerate()
+        .map(|(index, bytes)| {
+            let bytes = bytes.as_ref();
+            MptOwned::decode_from_proof_rlp(&mut &bytes[..]).map_err(|err| Error::ProofNodeDecode {
+                index,
+                count: proof.len(),
+                len: bytes.len(),
+                source: Box::new(err),
+            })
+        })
         .collect::<Result<Vec<_>, _>>()
 }
 
@@ -181,37 +191,181 @@ fn build_storage_trie(proof: &AccountProof, fini_proofs: &AccountProof) -> Resul
     Ok(resolve_nodes(&storage_root_node, &storage_nodes))
 }
 
+/// The proofs of each account at the parent block and at the block, paired by address.
+type TransitionProofs<'p> = Vec<(&'p Address, &'p AccountProof, &'p AccountProof)>;
+
+fn pair_transition_proofs<'p>(
+    parent_proofs: &'p HashMap<Address, AccountProof>,
+    proofs: &'p HashMap<Address, AccountProof>,
+) -> TransitionProofs<'p> {
+    parent_proofs
+        .iter()
+        .map(|(address, proof)| (address, proof, proofs.get(address).unwrap()))
+        .collect()
+}
+
+/// Resolves the state trie from the state proofs of `accounts`, and returns the state with it
+/// and the already built `storage_tries`.
+fn resolve_state(
+    accounts: &TransitionProofs<'_>,
+    storage_tries: impl IntoIterator<Item = (B256, Mpt<'static>)>,
+) -> Result<EthereumState<'static>, Error> {
+    let bump = Box::leak(Box::new(Bump::new()));
+    let mut state_nodes = HashMap::default();
+    let mut state_root_node = MptOwned::default();
+
+    for (address, proof, fini_proofs) in accounts {
+        if let Some(root) = process_proof(&proof.proof, &mut state_nodes)? {
+            state_root_node = root;
+        }
+        add_orphaned_leafs(address, &fini_proofs.proof, &mut state_nodes)?;
+    }
+
+    let state_trie = resolve_nodes(&state_root_node, &state_nodes);
+    Ok(EthereumState::from_tries_in(state_trie.into_inner(), storage_tries, bump))
+}
+
+/// The state of an account-less transition, which only knows the state root.
+fn empty_state(state_root: B256) -> EthereumState<'static> {
+    let bump = Box::leak(Box::new(Bump::new()));
+    EthereumState::from_tries_in(node_from_digest(state_root).into_inner(), [], bump)
+}
+
 pub fn transition_proofs_to_tries(
     state_root: B256,
     parent_proofs: &HashMap<Address, AccountProof>,
     proofs: &HashMap<Address, AccountProof>,
-) -> Result<EthereumState, Error> {
-    let bump = Box::leak(Box::new(Bump::new()));
+) -> Result<EthereumState<'static>, Error> {
+    if parent_proofs.is_empty() {
+        return Ok(empty_state(state_root));
+    }
+
+    let accounts = pair_transition_proofs(parent_proofs, proofs);
+    let storage_tries = accounts
+        .iter()
+        .map(|(address, proof, fini_proofs)| {
+            let storage_trie = build_storage_trie(proof, fini_proofs)?;
+            Ok((B256::from(keccak256(address)), storage_trie.into_inner()))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    resolve_state(&accounts, storage_tries)
+}
+
+/// Same as [`transition_proofs_to_tries`], but builds the storage tries of all accounts
+/// concurrently before resolving the state trie on the current thread.
+///
+/// Every storage trie is resolved into an arena of its own, so the tries can be moved back from
+/// the rayon workers.
+#[cfg(feature = "parallel")]
+pub fn par_transition_proofs_to_tries(
+    state_root: B256,
+    parent_proofs: &HashMap<Address, AccountProof>,
+    proofs: &HashMap<Address, AccountProof>,
+) -> Result<EthereumState<'static>, Error> {
+    use crate::state::SendMpt;
+    use rayon::prelude::*;
 
     if parent_proofs.is_empty() {
-        return Ok(EthereumState {
-            state_trie: node_from_digest(state_root).into_inner(),
-            storage_tries: HashMap::default(),
-            bump,
-        });
+        return Ok(empty_state(state_root));
     }
 
-    let mut storage_tries = HashMap::default();
-    let mut state_nodes = HashMap::default();
-    let mut state_root_node = MptOwned::default();
+    let accounts = pair_transition_proofs(parent_proofs, proofs);
+    let storage_tries = accounts
+        .par_iter()
+        .map(|(address, proof, fini_proofs)| {
+            let storage_trie = build_storage_trie(proof, fini_proofs)?;
+            Ok((B256::from(keccak256(address)), SendMpt(storage_trie.into_inner())))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
 
-    for (address, proof) in parent_proofs {
-        if let Some(root) = process_proof(&proof.proof, &mut state_nodes)? {
-            state_root_node = root;
+    resolve_state(
+        &accounts,
+        storage_tries.into_iter().map(|(address, SendMpt(trie))| (address, trie)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm_primitives::U256;
+
+    #[test]
+    fn test_parse_proof_error_index() {
+        let bump = Bump::new();
+        let mut trie = Mpt::new(&bump);
+        for i in 0..16u64 {
+            trie.insert_rlp(keccak256(i.to_be_bytes()).as_slice(), U256::from(i)).unwrap();
         }
+        let mut proof = trie.payloads();
+        assert!(proof.len() > 2);
+        assert!(parse_proof(&proof).is_ok());
 
-        let fini_proofs = proofs.get(address).unwrap();
-        add_orphaned_leafs(address, &fini_proofs.proof, &mut state_nodes)?;
+        // a list header claiming more bytes than there are
+        let mut corrupted = proof[1].to_vec();
+        corrupted.truncate(corrupted.len() / 2);
+        proof[1] = corrupted.into();
 
-        let storage_trie = build_storage_trie(proof, fini_proofs)?;
-        storage_tries.insert(B256::from(keccak256(address)), storage_trie.into_inner());
+        let err = parse_proof(&proof).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::ProofNodeDecode { index: 1, count, len, .. }
+                if count == proof.len() && len == proof[1].len()
+        ));
+        assert!(err.to_string().starts_with("failed to decode proof node 1 of"));
     }
 
-    let state_trie = resolve_nodes(&state_root_node, &state_nodes);
-    Ok(EthereumState { state_trie: state_trie.into_inner(), storage_tries, bump })
+    #[test]
+    fn test_node_from_digest_root_kind() {
+        use crate::NodeKind;
+
+        assert_eq!(
+            node_from_digest(reth_trie::EMPTY_ROOT_HASH).inner().root_kind(),
+            NodeKind::Null
+        );
+        assert_eq!(node_from_digest(B256::ZERO).inner().root_kind(), NodeKind::Null);
+        let digest = keccak256(b"root");
+        let trie = node_from_digest(digest);
+        assert_eq!(trie.inner().root_kind(), NodeKind::Digest);
+        assert_eq!(trie.hash(), digest);
+
+        let bump = Bump::new();
+        let mut trie = Mpt::new(&bump);
+        trie.insert(b"a", b"a").unwrap();
+        assert_eq!(trie.root_kind(), NodeKind::Leaf);
+        trie.insert(b"q", b"q").unwrap();
+        assert_eq!(trie.root_kind(), NodeKind::Branch);
+        trie.insert(b"b", b"b").unwrap();
+        trie.delete(b"q").unwrap();
+        assert_eq!(trie.root_kind(), NodeKind::Extension);
+    }
+
+    #[test]
+    fn test_digest_borrows_encoding() {
+        // a branch node referencing 16 hashed children
+        let mut encoded = vec![0xf9, 0x02, 0x11];
+        for i in 0..16u8 {
+            encoded.push(alloy_rlp::EMPTY_STRING_CODE + 32);
+            encoded.extend([i; 32]);
+        }
+        encoded.push(alloy_rlp::EMPTY_STRING_CODE);
+
+        let trie = MptOwned::decode_from_proof_rlp(&mut &encoded[..]).unwrap();
+        let Some(NodeData::Branch(children)) = trie.get_node(trie.root_id()) else {
+            panic!("expected a branch node");
+        };
+        let digests: Vec<&[u8]> = children
+            .iter()
+            .map(|child_id| match trie.get_node(child_id.unwrap()) {
+                Some(NodeData::Digest(digest)) => *digest,
+                node => panic!("expected a digest node, got {node:?}"),
+            })
+            .collect();
+
+        // The digests are slices of the copied encoding rather than separately allocated
+        for (i, digest) in digests.iter().enumerate() {
+            assert_eq!(*digest, [i as u8; 32]);
+            assert_eq!(digest.as_ptr() as usize - digests[0].as_ptr() as usize, 33 * i);
+        }
+    }
 }
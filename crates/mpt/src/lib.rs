@@ -7,6 +7,7 @@ pub use state::*;
 mod bump_bufmut;
 mod hp;
 mod node;
+pub use node::NodeKind;
 
 #[cfg(feature = "host")]
 pub mod from_proof;
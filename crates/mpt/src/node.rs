@@ -22,6 +22,28 @@ pub(crate) enum NodeData<'a> {
     Digest(&'a [u8]),
 }
 
+/// The kind of a node, without its data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    Null,
+    Branch,
+    Leaf,
+    Extension,
+    Digest,
+}
+
+impl NodeData<'_> {
+    pub(crate) fn kind(&self) -> NodeKind {
+        match self {
+            NodeData::Null => NodeKind::Null,
+            NodeData::Branch(_) => NodeKind::Branch,
+            NodeData::Leaf(..) => NodeKind::Leaf,
+            NodeData::Extension(..) => NodeKind::Extension,
+            NodeData::Digest(_) => NodeKind::Digest,
+        }
+    }
+}
+
 /// Represents the ways in which one node can reference another node inside the sparse Merkle
 /// Patricia Trie (MPT).
 ///
@@ -5,7 +5,11 @@ use crate::{
 };
 use alloy_rlp::PayloadView;
 use bytes::{BufMut, BytesMut};
-use revm_primitives::{Bytes, HashMap, B256};
+use revm_primitives::{Bytes, HashMap, HashSet, B256};
+
+/// Default maximum number of nested nodes [`MptResolver::resolve`] descends through. A trie over
+/// 32-byte keys has at most 64 branches, 64 extensions and a leaf on any path.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
 
 /// [`MptResolver`] resolves an MPT from a from a given mapping of `keccak(payload) -> payload` of
 /// all MPT nodes.
@@ -27,6 +31,36 @@ impl MptResolver {
 
     /// Resolves an MPT from the mapping stored in [`MptResolver`] given its `root` hash.
     pub fn resolve(&self, root: &B256) -> Result<Mpt<'static>, Error> {
+        self.resolve_with_max_depth(root, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Like [`MptResolver::resolve`], but fails with [`Error::DepthExceeded`] if any path nests
+    /// more than `max_depth` nodes, and with [`Error::ResolveCycle`] if the node store contains a
+    /// cycle.
+    pub fn resolve_with_max_depth(
+        &self,
+        root: &B256,
+        max_depth: usize,
+    ) -> Result<Mpt<'static>, Error> {
+        self.resolve_inner(root, max_depth, &mut |_| None)
+    }
+
+    /// Like [`MptResolver::resolve`], but calls `fetch` for every digest that is not in the node
+    /// store, e.g. to load it from a database or an RPC. Digests for which `fetch` returns `None`
+    /// are left as [`NodeData::Digest`] nodes.
+    pub fn resolve_with<F>(&self, root: &B256, mut fetch: F) -> Result<Mpt<'static>, Error>
+    where
+        F: FnMut(&B256) -> Option<Bytes>,
+    {
+        self.resolve_inner(root, DEFAULT_MAX_DEPTH, &mut fetch)
+    }
+
+    fn resolve_inner(
+        &self,
+        root: &B256,
+        max_depth: usize,
+        fetch: &mut dyn FnMut(&B256) -> Option<Bytes>,
+    ) -> Result<Mpt<'static>, Error> {
         let mut mpt = MptOwned::default();
 
         let rlp_root = {
@@ -36,7 +70,9 @@ impl MptResolver {
             out.to_vec()
         };
 
-        let root_id = self.resolve_internal(&mut rlp_root.as_slice(), &mut mpt)?;
+        let mut resolution =
+            Resolution { max_depth, depth: 0, ancestors: HashSet::default(), fetch };
+        let root_id = self.resolve_internal(&mut rlp_root.as_slice(), &mut mpt, &mut resolution)?;
         mpt.set_root_id(root_id);
 
         Ok(mpt.into_inner())
@@ -46,62 +82,128 @@ impl MptResolver {
         &self,
         node_bytes: &mut &[u8],
         mpt: &mut MptOwned,
+        resolution: &mut Resolution<'_>,
     ) -> Result<NodeId, Error> {
         let node_id = match alloy_rlp::Header::decode_raw(node_bytes)? {
             PayloadView::String(item) => match item.len() {
                 0 => NULL_NODE_ID,
-                32 => match self.node_store.get(&B256::from_slice(item)) {
-                    Some(resolved_node_bytes) => {
-                        self.resolve_internal(&mut resolved_node_bytes.as_ref(), mpt)?
+                32 => {
+                    let digest = B256::from_slice(item);
+                    let resolved = match self.node_store.get(&digest) {
+                        Some(resolved_node_bytes) => Some(resolved_node_bytes.clone()),
+                        None => (resolution.fetch)(&digest),
+                    };
+                    match resolved {
+                        Some(resolved_node_bytes) => {
+                            // Identical subtries may legitimately be shared, so only a digest on
+                            // the current path is a cycle.
+                            if !resolution.ancestors.insert(digest) {
+                                return Err(Error::ResolveCycle(digest));
+                            }
+                            let node_id = self.resolve_internal(
+                                &mut resolved_node_bytes.as_ref(),
+                                mpt,
+                                resolution,
+                            )?;
+                            resolution.ancestors.remove(&digest);
+                            node_id
+                        }
+                        None => mpt.add_node(&NodeData::Digest(item)),
                     }
-                    None => mpt.add_node(&NodeData::Digest(item)),
-                },
+                }
                 _ => {
                     return Err(Error::RlpError(alloy_rlp::Error::UnexpectedLength));
                 }
             },
-            PayloadView::List(mut items) => match items.len() {
-                2 => {
-                    let path = alloy_rlp::Header::decode_bytes(&mut items[0], false)?;
-                    let prefix = path[0];
-                    if (prefix & (2 << 4)) == 0 {
-                        let ext_node_id = self.resolve_internal(&mut items[1], mpt)?;
-                        let node_data = NodeData::Extension(path, ext_node_id);
-                        mpt.add_node(&node_data)
-                    } else {
-                        let value = alloy_rlp::Header::decode_bytes(&mut items[1], false)?;
-                        let node_data = NodeData::Leaf(path, value);
-                        mpt.add_node(&node_data)
-                    }
+            PayloadView::List(items) => {
+                resolution.depth += 1;
+                if resolution.depth > resolution.max_depth {
+                    return Err(Error::DepthExceeded(resolution.max_depth));
                 }
-                17 => {
-                    if items[16] != NULL_NODE_REF_SLICE {
-                        return Err(Error::ValueInBranch);
-                    }
+                let node_id = self.resolve_list(items, mpt, resolution)?;
+                resolution.depth -= 1;
+                node_id
+            }
+        };
+        Ok(node_id)
+    }
 
-                    let mut childs: [Option<NodeId>; 16] = Default::default();
-                    for (i, mut item) in items.into_iter().take(16).enumerate() {
-                        let child_id = self.resolve_internal(&mut item, mpt)?;
-                        childs[i] = if child_id == NULL_NODE_ID { None } else { Some(child_id) };
-                    }
-                    let node_data = NodeData::Branch(childs);
+    fn resolve_list(
+        &self,
+        mut items: Vec<&[u8]>,
+        mpt: &mut MptOwned,
+        resolution: &mut Resolution<'_>,
+    ) -> Result<NodeId, Error> {
+        let node_id = match items.len() {
+            2 => {
+                let path = alloy_rlp::Header::decode_bytes(&mut items[0], false)?;
+                let prefix = path[0];
+                if (prefix & (2 << 4)) == 0 {
+                    let ext_node_id = self.resolve_internal(&mut items[1], mpt, resolution)?;
+                    let node_data = NodeData::Extension(path, ext_node_id);
+                    mpt.add_node(&node_data)
+                } else {
+                    let value = alloy_rlp::Header::decode_bytes(&mut items[1], false)?;
+                    let node_data = NodeData::Leaf(path, value);
                     mpt.add_node(&node_data)
                 }
-                _ => {
-                    return Err(Error::RlpError(alloy_rlp::Error::UnexpectedLength));
+            }
+            17 => {
+                if items[16] != NULL_NODE_REF_SLICE {
+                    return Err(Error::ValueInBranch);
                 }
-            },
+
+                let mut childs: [Option<NodeId>; 16] = Default::default();
+                for (i, mut item) in items.into_iter().take(16).enumerate() {
+                    let child_id = self.resolve_internal(&mut item, mpt, resolution)?;
+                    childs[i] = if child_id == NULL_NODE_ID { None } else { Some(child_id) };
+                }
+                let node_data = NodeData::Branch(childs);
+                mpt.add_node(&node_data)
+            }
+            _ => {
+                return Err(Error::RlpError(alloy_rlp::Error::UnexpectedLength));
+            }
         };
         Ok(node_id)
     }
 }
 
+/// Per-call state of a [`MptResolver`] resolution.
+struct Resolution<'f> {
+    max_depth: usize,
+    /// Number of nodes on the current path.
+    depth: usize,
+    /// Digests resolved on the current path.
+    ancestors: HashSet<B256>,
+    /// Fallback for digests missing from the node store.
+    fetch: &'f mut dyn FnMut(&B256) -> Option<Bytes>,
+}
+
 #[cfg(test)]
 mod tests {
-    use revm_primitives::{keccak256, map::DefaultHashBuilder, HashMap};
+    use revm_primitives::{b256, keccak256, map::DefaultHashBuilder, Bytes, HashMap, B256};
 
     use crate::{resolver::MptResolver, Error, Mpt};
 
+    /// RLP of a branch node whose only child is referenced by `child`.
+    fn branch_with_child(child: Option<B256>) -> Bytes {
+        let mut payload = Vec::new();
+        match child {
+            Some(child) => {
+                payload.push(alloy_rlp::EMPTY_STRING_CODE + 32);
+                payload.extend_from_slice(child.as_slice());
+            }
+            None => payload.push(alloy_rlp::EMPTY_STRING_CODE),
+        }
+        payload.extend([alloy_rlp::EMPTY_STRING_CODE; 16]);
+
+        let mut rlp = Vec::new();
+        alloy_rlp::Header { list: true, payload_length: payload.len() }.encode(&mut rlp);
+        rlp.extend(payload);
+        rlp.into()
+    }
+
     #[test]
     fn test_resolve_keccak_trie() -> Result<(), Error> {
         const N: usize = 512;
@@ -127,4 +229,124 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_resolve_with_fetch() -> Result<(), Error> {
+        const N: usize = 512;
+
+        let bump = bumpalo::Bump::new();
+        let mut trie = Mpt::new(&bump);
+        for i in 0..N {
+            assert!(trie.insert_rlp(keccak256(i.to_be_bytes()).as_slice(), i)?);
+        }
+
+        // Split the nodes between the local store and a secondary one served by the callback
+        let mut local = HashMap::default();
+        let mut secondary = HashMap::default();
+        for (i, payload) in trie.payloads().into_iter().enumerate() {
+            let store = if i % 2 == 0 { &mut local } else { &mut secondary };
+            store.insert(keccak256(&payload), payload);
+        }
+        let in_memory = MptResolver::from_iter(
+            local.iter().chain(secondary.iter()).map(|(k, v)| (*k, v.clone())),
+        )
+        .resolve(&trie.hash())?;
+
+        let mpt_resolver = MptResolver::new(local);
+        let mut fetched = 0;
+        let resolved_trie = mpt_resolver.resolve_with(&trie.hash(), |digest| {
+            fetched += 1;
+            secondary.get(digest).cloned()
+        })?;
+        assert!(fetched > 0);
+        assert_eq!(resolved_trie.hash(), in_memory.hash());
+        assert_eq!(resolved_trie.hash(), trie.hash());
+
+        // Without the secondary store the missing nodes stay unresolved digests, which still hash
+        // to the same root but cannot be read
+        let partial = mpt_resolver.resolve(&trie.hash())?;
+        assert_eq!(partial.hash(), trie.hash());
+        assert!((0..N).any(|i| partial.get(keccak256(i.to_be_bytes()).as_slice()).is_err()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_payloads_are_referenced() -> Result<(), Error> {
+        let bump = bumpalo::Bump::new();
+        let mut trie = Mpt::new(&bump);
+        // short keys and values give leaves small enough to be embedded in their parent branch
+        for i in 0..64u8 {
+            trie.insert_rlp(&[i], i)?;
+        }
+
+        let payloads = trie.payloads();
+        assert_eq!(keccak256(&payloads[0]), trie.hash());
+        let contains = |haystack: &[u8], needle: &[u8]| {
+            haystack.windows(needle.len()).any(|window| window == needle)
+        };
+        for (i, payload) in payloads.iter().enumerate().skip(1) {
+            // hashed nodes are referenced by their digest, embedded ones by their encoding
+            let reference =
+                if payload.len() >= 32 { keccak256(payload).to_vec() } else { payload.to_vec() };
+            assert!(
+                payloads
+                    .iter()
+                    .enumerate()
+                    .any(|(j, parent)| j != i && contains(parent, &reference)),
+                "node {i} is not referenced"
+            );
+        }
+        assert!(payloads.iter().any(|payload| payload.len() < 32));
+
+        let resolver = MptResolver::from_iter(
+            payloads.into_iter().map(|payload| (keccak256(&payload), payload)),
+        );
+        assert_eq!(resolver.resolve(&trie.hash())?.hash(), trie.hash());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_cycle() {
+        // The digests do not need to match the payloads for the resolver.
+        let a = b256!("0x000000000000000000000000000000000000000000000000000000000000000a");
+        let b = b256!("0x000000000000000000000000000000000000000000000000000000000000000b");
+        let mpt_resolver = MptResolver::from_iter([
+            (a, branch_with_child(Some(b))),
+            (b, branch_with_child(Some(a))),
+        ]);
+
+        assert!(
+            matches!(mpt_resolver.resolve(&a), Err(Error::ResolveCycle(digest)) if digest == a)
+        );
+    }
+
+    #[test]
+    fn test_resolve_deep_trie() -> Result<(), Error> {
+        const DEPTH: usize = 200;
+
+        // The innermost child is left unresolved, so that every node is referenced by its hash.
+        let mut node_store = HashMap::default();
+        let mut child = Some(B256::repeat_byte(0xff));
+        for _ in 0..DEPTH {
+            let node = branch_with_child(child);
+            let digest = keccak256(&node);
+            node_store.insert(digest, node);
+            child = Some(digest);
+        }
+        let root = child.unwrap();
+        let mpt_resolver = MptResolver::new(node_store);
+
+        let resolved_trie = mpt_resolver.resolve(&root)?;
+        assert_eq!(resolved_trie.hash(), root);
+
+        assert!(matches!(
+            mpt_resolver.resolve_with_max_depth(&root, DEPTH - 1),
+            Err(Error::DepthExceeded(max_depth)) if max_depth == DEPTH - 1
+        ));
+        assert!(mpt_resolver.resolve_with_max_depth(&root, DEPTH).is_ok());
+
+        Ok(())
+    }
 }
@@ -1,7 +1,9 @@
+use std::cell::OnceCell;
+
 use bumpalo::Bump;
 use reth_trie::TrieAccount;
-use revm::database::BundleState;
-use revm_primitives::{keccak256, map::DefaultHashBuilder, HashMap, B256};
+use revm::database::{BundleAccount, BundleState};
+use revm_primitives::{keccak256, map::DefaultHashBuilder, HashMap, B256, U256};
 
 use crate::{Error, Mpt};
 
@@ -12,31 +14,202 @@ pub struct EthereumStateBytes {
     pub storage_tries: Vec<(B256, usize, bytes::Bytes)>,
 }
 
+/// The state and storage tries of the accounts. The tries borrow their data from, and allocate
+/// new nodes in, arenas living for `'a`.
+#[derive(Debug, Clone)]
+pub struct EthereumState<'a> {
+    pub state_trie: Mpt<'a>,
+    pub storage_tries: HashMap<B256, Mpt<'a>>,
+    pub bump: &'a Bump,
+    /// Serialized storage tries, decoded on first access. See [`Self::insert_lazy_storage_trie`].
+    lazy_storage_tries: HashMap<B256, LazyStorageTrie<'a>>,
+    /// Storage tries replaced or removed since [`Self::begin_transaction`].
+    journal: Option<StateJournal<'a>>,
+}
+
+/// A serialized storage trie, and the trie once decoded.
 #[derive(Debug, Clone)]
-pub struct EthereumState {
-    pub state_trie: Mpt<'static>,
-    pub storage_tries: HashMap<B256, Mpt<'static>>,
-    pub bump: &'static Bump,
+struct LazyStorageTrie<'a> {
+    num_nodes: usize,
+    bytes: &'a [u8],
+    /// The storage root of the account, checked when decoding.
+    expected_root: B256,
+    /// The decoded trie, or the error decoding it, so that a failure is not decoded again.
+    trie: OnceCell<Result<Mpt<'a>, Error>>,
+}
+
+impl<'a> LazyStorageTrie<'a> {
+    fn get_or_decode(&self, bump: &'a Bump, hashed_address: &B256) -> Result<&Mpt<'a>, Error> {
+        let decoded = self.trie.get_or_init(|| {
+            let mut bytes = self.bytes;
+            let trie = Mpt::decode_trie(bump, &mut bytes, self.num_nodes)?;
+            if trie.hash() != self.expected_root {
+                return Err(Error::StorageRootMismatch {
+                    hashed_address: *hashed_address,
+                    actual: trie.hash(),
+                    expected: self.expected_root,
+                });
+            }
+            Ok(trie)
+        });
+        decoded.as_ref().map_err(Error::clone)
+    }
+
+    /// Returns the trie if it was already decoded successfully.
+    fn decoded(&self) -> Option<&Mpt<'a>> {
+        self.trie.get().and_then(|decoded| decoded.as_ref().ok())
+    }
 }
 
-impl EthereumState {
+/// The storage tries of the accounts as they were before their first replacement, creation or
+/// removal in a transaction, in order. `None` means the account had no storage trie. Tries that
+/// are only updated in place roll back their own journals instead.
+#[derive(Debug, Clone, Default)]
+struct StateJournal<'a> {
+    storage_tries: Vec<(B256, Option<Mpt<'a>>)>,
+    /// The lazily inserted storage tries of the removed accounts.
+    lazy_storage_tries: Vec<(B256, LazyStorageTrie<'a>)>,
+}
+
+impl EthereumState<'static> {
+    /// Creates an empty state in a newly leaked arena.
     pub fn new() -> Self {
-        let bump = Box::leak(Box::new(Bump::new()));
-        Self {
-            state_trie: Mpt::new(bump),
-            storage_tries: HashMap::with_capacity_and_hasher(1, DefaultHashBuilder::default()),
+        Self::new_in(Box::leak(Box::new(Bump::new())))
+    }
+}
+
+impl<'a> EthereumState<'a> {
+    /// Creates an empty state allocating in `bump`.
+    pub fn new_in(bump: &'a Bump) -> Self {
+        Self::from_tries_in(
+            Mpt::new(bump),
+            HashMap::with_capacity_and_hasher(1, DefaultHashBuilder::default()),
             bump,
-        }
+        )
     }
 
     pub fn from_tries(
-        state_trie: Mpt<'static>,
-        storage_tries: impl IntoIterator<Item = (B256, Mpt<'static>)>,
+        state_trie: Mpt<'a>,
+        storage_tries: impl IntoIterator<Item = (B256, Mpt<'a>)>,
+    ) -> Self {
+        Self::from_tries_in(state_trie, storage_tries, Box::leak(Box::new(Bump::new())))
+    }
+
+    /// Same as [`Self::from_tries`], but allocates new nodes in `bump`.
+    pub fn from_tries_in(
+        state_trie: Mpt<'a>,
+        storage_tries: impl IntoIterator<Item = (B256, Mpt<'a>)>,
+        bump: &'a Bump,
     ) -> Self {
         Self {
             state_trie,
             storage_tries: storage_tries.into_iter().collect(),
-            bump: Box::leak(Box::new(Bump::new())),
+            bump,
+            lazy_storage_tries: HashMap::default(),
+            journal: None,
+        }
+    }
+
+    /// Adds the serialized storage trie of an account without decoding it. It is decoded in the
+    /// arena of the state on first access through [`Self::storage_trie`], or when the account gets
+    /// updated, and then checked against `expected_root`. This keeps the storage tries that are
+    /// never accessed out of memory.
+    pub fn insert_lazy_storage_trie(
+        &mut self,
+        hashed_address: B256,
+        num_nodes: usize,
+        bytes: &'a [u8],
+        expected_root: B256,
+    ) {
+        self.storage_tries.remove(&hashed_address);
+        self.lazy_storage_tries.insert(
+            hashed_address,
+            LazyStorageTrie { num_nodes, bytes, expected_root, trie: OnceCell::new() },
+        );
+    }
+
+    /// Returns the storage trie of the account with the given hashed address, decoding it if it
+    /// was inserted lazily, or `None` if the state has no storage trie for it.
+    pub fn storage_trie(&self, hashed_address: &B256) -> Result<Option<&Mpt<'a>>, Error> {
+        if let Some(storage_trie) = self.storage_tries.get(hashed_address) {
+            return Ok(Some(storage_trie));
+        }
+        match self.lazy_storage_tries.get(hashed_address) {
+            Some(lazy) => lazy.get_or_decode(self.bump, hashed_address).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the number of lazily inserted storage tries that have not been decoded.
+    pub fn num_encoded_storage_tries(&self) -> usize {
+        self.lazy_storage_tries.values().filter(|lazy| lazy.trie.get().is_none()).count()
+    }
+
+    /// Moves the lazily inserted storage trie of the account, if any, to `storage_tries`,
+    /// decoding it if needed.
+    fn resolve_storage_trie(&mut self, hashed_address: B256) -> Result<(), Error> {
+        let Some(lazy) = self.lazy_storage_tries.get(&hashed_address) else {
+            return Ok(());
+        };
+        // kept lazy on failure, so that later accesses fail the same way
+        lazy.get_or_decode(self.bump, &hashed_address)?;
+        let lazy = self.lazy_storage_tries.remove(&hashed_address).expect("found above");
+        let mut storage_trie = lazy.trie.into_inner().expect("decoded above")?;
+        if self.journal.is_some() {
+            storage_trie.begin_journal();
+        }
+        self.storage_tries.insert(hashed_address, storage_trie);
+        Ok(())
+    }
+
+    /// Starts journaling the updates of the state, so that [`Self::rollback`] can revert them,
+    /// e.g. to inspect the root after a speculative bundle. Only the overwritten nodes and the
+    /// replaced storage tries are recorded, which is much cheaper than cloning the state.
+    ///
+    /// Transactions do not nest: this discards the transaction already in progress, if any. Only
+    /// the updates made through the methods of the state and its tries are journaled, not direct
+    /// changes to the `storage_tries` map.
+    pub fn begin_transaction(&mut self) {
+        self.state_trie.begin_journal();
+        for storage_trie in self.storage_tries.values_mut() {
+            storage_trie.begin_journal();
+        }
+        self.journal = Some(StateJournal::default());
+    }
+
+    /// Keeps the updates since [`Self::begin_transaction`].
+    pub fn commit(&mut self) {
+        self.state_trie.commit_journal();
+        for storage_trie in self.storage_tries.values_mut() {
+            storage_trie.commit_journal();
+        }
+        self.journal = None;
+    }
+
+    /// Reverts the updates since [`Self::begin_transaction`]. Does nothing if no transaction is
+    /// in progress.
+    pub fn rollback(&mut self) {
+        let Some(journal) = self.journal.take() else {
+            return;
+        };
+        for (hashed_address, storage_trie) in journal.storage_tries.into_iter().rev() {
+            match storage_trie {
+                Some(storage_trie) => self.storage_tries.insert(hashed_address, storage_trie),
+                None => self.storage_tries.remove(&hashed_address),
+            };
+        }
+        self.lazy_storage_tries.extend(journal.lazy_storage_tries);
+        self.state_trie.rollback_journal();
+        for storage_trie in self.storage_tries.values_mut() {
+            storage_trie.rollback_journal();
+        }
+    }
+
+    /// Records the storage trie of an account before it gets replaced or removed, if a
+    /// transaction is in progress.
+    fn journal_storage_trie(&mut self, hashed_address: B256, previous: Option<Mpt<'a>>) {
+        if let Some(journal) = &mut self.journal {
+            journal.storage_tries.push((hashed_address, previous));
         }
     }
 
@@ -46,45 +219,240 @@ impl EthereumState {
 
             if let Some(info) = &account.info {
                 let storage_trie =
-                    self.storage_tries.entry(hashed_address).or_insert(Mpt::new(self.bump));
+                    self.storage_trie_mut(hashed_address, account.status.was_destroyed())?;
+                update_storage_trie(storage_trie, account)?;
+                let state_account = TrieAccount {
+                    nonce: info.nonce,
+                    balance: info.balance,
+                    storage_root: storage_trie.hash(),
+                    code_hash: info.code_hash,
+                };
+                self.state_trie.insert_rlp(hashed_address.as_slice(), state_account)?;
+            } else {
+                self.remove_account(hashed_address)?;
+            }
+        }
 
-                if account.status.was_destroyed() {
-                    *storage_trie = Mpt::new(self.bump);
-                }
+        Ok(())
+    }
 
-                for (slot, value) in &account.storage {
-                    let hashed_slot = keccak256(slot.to_be_bytes::<32>());
-                    if value.present_value.is_zero() {
-                        storage_trie.delete(hashed_slot.as_slice())?;
-                    } else {
-                        storage_trie.insert_rlp(hashed_slot.as_slice(), value.present_value)?;
-                    }
+    /// Same as [`Self::update_from_bundle_state`], but for callers that already hashed the keys:
+    /// `accounts` holds the new account of each hashed address, or `None` to remove it, and
+    /// `storage` the new values of the hashed slots of each hashed address, where zero removes the
+    /// slot.
+    ///
+    /// The storage is updated first, then the accounts get the storage root of their storage trie
+    /// instead of the one they hold. An account whose storage changes must thus be in `accounts`
+    /// too for its storage root to be updated.
+    pub fn apply_hashed(
+        &mut self,
+        accounts: &[(B256, Option<TrieAccount>)],
+        storage: &[(B256, Vec<(B256, U256)>)],
+    ) -> Result<(), Error> {
+        for (hashed_address, slots) in storage {
+            let storage_trie = self.storage_trie_mut(*hashed_address, false)?;
+            for (hashed_slot, value) in slots {
+                update_storage_slot(storage_trie, hashed_slot, *value)?;
+            }
+        }
+        for (hashed_address, account) in accounts {
+            match account {
+                Some(account) => {
+                    let storage_root = self.storage_trie_mut(*hashed_address, false)?.hash();
+                    let account = TrieAccount { storage_root, ..*account };
+                    self.state_trie.insert_rlp(hashed_address.as_slice(), account)?;
                 }
-                let storage_root = storage_trie.hash();
+                None => self.remove_account(*hashed_address)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the storage trie of the account to update, which is a new empty one if the account
+    /// has none yet or if `wipe` is set.
+    fn storage_trie_mut(
+        &mut self,
+        hashed_address: B256,
+        wipe: bool,
+    ) -> Result<&mut Mpt<'a>, Error> {
+        self.resolve_storage_trie(hashed_address)?;
+        if wipe || !self.storage_tries.contains_key(&hashed_address) {
+            let previous = self.storage_tries.insert(hashed_address, Mpt::new(self.bump));
+            self.journal_storage_trie(hashed_address, previous);
+        }
+        Ok(self.storage_tries.get_mut(&hashed_address).unwrap())
+    }
+
+    /// Removes the account from the state trie, along with its storage trie. A lazily inserted
+    /// storage trie is dropped without decoding it.
+    fn remove_account(&mut self, hashed_address: B256) -> Result<(), Error> {
+        self.state_trie.delete(hashed_address.as_slice())?;
+        if let Some(lazy) = self.lazy_storage_tries.remove(&hashed_address) {
+            if let Some(journal) = &mut self.journal {
+                journal.lazy_storage_tries.push((hashed_address, lazy));
+            }
+        }
+        if let Some(previous) = self.storage_tries.remove(&hashed_address) {
+            self.journal_storage_trie(hashed_address, Some(previous));
+        }
+        Ok(())
+    }
+
+    /// Applies `bundle_state` and returns the new state root.
+    ///
+    /// The tries cache the reference of every node and updates only invalidate the nodes on the
+    /// path to each modified key, so this only re-encodes the subtrees touched by the bundle.
+    /// [`Mpt::dirty_node_count`] tells how many nodes the root computation has to encode.
+    pub fn update_and_root(&mut self, bundle_state: &BundleState) -> Result<B256, Error> {
+        self.update_from_bundle_state(bundle_state)?;
+        Ok(self.state_trie.hash())
+    }
+
+    /// Same as [`Self::update_from_bundle_state`], but updates the storage tries of the accounts
+    /// with storage changes concurrently before inserting the accounts into the state trie on the
+    /// current thread.
+    ///
+    /// A [`Bump`] cannot be shared between threads, so the updated storage tries are split into
+    /// one group per arena of `arenas`, and the tries of a group are updated one after another on
+    /// a single worker, allocating in the arena of the group. The arenas are owned by the caller,
+    /// who can reuse them across calls. Falls back to the serial update if `arenas` is empty.
+    #[cfg(feature = "parallel")]
+    pub fn par_update_from_bundle_state(
+        &mut self,
+        bundle_state: &BundleState,
+        arenas: &'a [Bump],
+    ) -> Result<(), Error> {
+        use rayon::prelude::*;
+
+        if arenas.is_empty() {
+            return self.update_from_bundle_state(bundle_state);
+        }
+
+        let mut groups: Vec<Vec<_>> = arenas.iter().map(|_| Vec::new()).collect();
+        let mut num_updates = 0;
+        for (address, account) in &bundle_state.state {
+            let hashed_address = keccak256(address);
+            let Some(info) = &account.info else {
+                self.remove_account(hashed_address)?;
+                continue;
+            };
+
+            // Without storage changes, the storage root is at most the root of an empty trie.
+            if account.storage.is_empty() {
+                let storage_trie =
+                    self.storage_trie_mut(hashed_address, account.status.was_destroyed())?;
                 let state_account = TrieAccount {
                     nonce: info.nonce,
                     balance: info.balance,
-                    storage_root,
+                    storage_root: storage_trie.hash(),
                     code_hash: info.code_hash,
                 };
                 self.state_trie.insert_rlp(hashed_address.as_slice(), state_account)?;
-            } else {
-                self.state_trie.delete(hashed_address.as_slice()).unwrap();
-                self.storage_tries.remove(&hashed_address);
+                continue;
             }
+
+            self.resolve_storage_trie(hashed_address)?;
+            let group = num_updates % arenas.len();
+            num_updates += 1;
+            let bump = &arenas[group];
+            let storage_trie = match self.storage_tries.remove(&hashed_address) {
+                Some(mut trie) if !account.status.was_destroyed() => {
+                    trie.set_bump(bump);
+                    trie
+                }
+                previous => {
+                    self.journal_storage_trie(hashed_address, previous);
+                    Mpt::new(bump)
+                }
+            };
+            groups[group].push((hashed_address, info, account, SendMpt(storage_trie)));
+        }
+
+        let updated = groups
+            .into_par_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .map(|(hashed_address, info, account, SendMpt(mut storage_trie))| {
+                        update_storage_trie(&mut storage_trie, account)?;
+                        let state_account = TrieAccount {
+                            nonce: info.nonce,
+                            balance: info.balance,
+                            storage_root: storage_trie.hash(),
+                            code_hash: info.code_hash,
+                        };
+                        Ok((hashed_address, state_account, SendMpt(storage_trie)))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        for (hashed_address, state_account, SendMpt(storage_trie)) in updated.into_iter().flatten()
+        {
+            self.state_trie.insert_rlp(hashed_address.as_slice(), state_account)?;
+            self.storage_tries.insert(hashed_address, storage_trie);
         }
 
         Ok(())
     }
 
+    /// Returns the storage root of the account with the given hashed address, or `None` if the
+    /// state has no storage trie for it. A lazily inserted storage trie is not decoded, its root is
+    /// the one it will be checked against.
+    pub fn storage_root(&self, hashed_address: &B256) -> Option<B256> {
+        match self.storage_tries.get(hashed_address) {
+            Some(storage_trie) => Some(storage_trie.hash()),
+            None => self.lazy_storage_tries.get(hashed_address).map(|lazy| lazy.expected_root),
+        }
+    }
+
+    /// Hashes the storage trie of the account, unlike [`Self::storage_root`] which returns the root
+    /// a lazily inserted trie is expected to have. A lazy trie that was not accessed yet is decoded
+    /// without checking its root, so that a [`Error::StorageRootMismatch`] can be diagnosed, in a
+    /// temporary arena so that it is not kept. Returns `None` if the account has no storage trie or
+    /// if it cannot be decoded.
+    pub fn recompute_storage_root(&self, hashed_address: &B256) -> Option<B256> {
+        if let Some(storage_trie) = self.storage_tries.get(hashed_address) {
+            return Some(storage_trie.hash());
+        }
+        let lazy = self.lazy_storage_tries.get(hashed_address)?;
+        if let Some(storage_trie) = lazy.decoded() {
+            return Some(storage_trie.hash());
+        }
+        let bump = Bump::new();
+        let mut bytes = lazy.bytes;
+        Mpt::decode_trie(&bump, &mut bytes, lazy.num_nodes).ok().map(|trie| trie.hash())
+    }
+
+    /// Returns the hashed address of the first account, in key order, whose leaf in the state
+    /// trie differs from the one in `expected`. Returns `None` if the state roots match, or if the
+    /// tries already diverge above the account leaves, e.g. at a node unresolved in either trie.
+    pub fn diff_account(&self, expected: &EthereumState<'_>) -> Option<B256> {
+        let path = self.state_trie.diff(&expected.state_trie)?;
+        if path.len() != 2 * B256::len_bytes() {
+            return None;
+        }
+        let mut hashed_address = B256::ZERO;
+        for (byte, nibbles) in hashed_address.iter_mut().zip(path.chunks_exact(2)) {
+            *byte = (nibbles[0] << 4) | nibbles[1];
+        }
+        Some(hashed_address)
+    }
+
     #[cfg(feature = "host")]
     pub fn encode_to_state_bytes(&self) -> EthereumStateBytes {
         let state_num_nodes = self.state_trie.num_nodes();
         let state_bytes = bytes::Bytes::from(self.state_trie.encode_trie());
+        let lazy_storage_bytes =
+            self.lazy_storage_tries.iter().map(|(addr, lazy)| match lazy.decoded() {
+                Some(trie) => (*addr, trie.num_nodes(), bytes::Bytes::from(trie.encode_trie())),
+                None => (*addr, lazy.num_nodes, bytes::Bytes::copy_from_slice(lazy.bytes)),
+            });
         let mut storage_bytes: Vec<_> = self
             .storage_tries
             .iter()
             .map(|(addr, trie)| (*addr, trie.num_nodes(), bytes::Bytes::from(trie.encode_trie())))
+            .chain(lazy_storage_bytes)
             .collect();
         storage_bytes.sort_by_key(|(addr, _, _)| *addr);
 
@@ -95,7 +463,41 @@ impl EthereumState {
     }
 }
 
-impl Default for EthereumState {
+/// Applies the storage changes of `account` to its storage trie.
+fn update_storage_trie(storage_trie: &mut Mpt<'_>, account: &BundleAccount) -> Result<(), Error> {
+    for (slot, value) in &account.storage {
+        let hashed_slot = keccak256(slot.to_be_bytes::<32>());
+        update_storage_slot(storage_trie, &hashed_slot, value.present_value)?;
+    }
+    Ok(())
+}
+
+/// Sets the value of a hashed slot, removing the slot if the value is zero.
+fn update_storage_slot(
+    storage_trie: &mut Mpt<'_>,
+    hashed_slot: &B256,
+    value: U256,
+) -> Result<(), Error> {
+    if value.is_zero() {
+        storage_trie.delete(hashed_slot.as_slice())?;
+    } else {
+        storage_trie.insert_rlp(hashed_slot.as_slice(), value)?;
+    }
+    Ok(())
+}
+
+/// Wrapper to move a storage trie to a rayon worker and back.
+#[cfg(feature = "parallel")]
+pub(crate) struct SendMpt<'a>(pub(crate) Mpt<'a>);
+
+// SAFETY: `Mpt` is `!Send` only because it holds a `&Bump`. Each wrapped trie was given an arena
+// that no trie on another thread allocates in at the same time, so the arena is only ever accessed
+// from one thread at a time. The nodes themselves only hold shared, immutable slices into other
+// arenas.
+#[cfg(feature = "parallel")]
+unsafe impl Send for SendMpt<'_> {}
+
+impl Default for EthereumState<'static> {
     fn default() -> Self {
         Self::new()
     }
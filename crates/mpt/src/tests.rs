@@ -1,6 +1,8 @@
-use revm_primitives::{b256, keccak256};
+use reth_trie::TrieAccount;
+use revm::{database::BundleState, state::AccountInfo};
+use revm_primitives::{address, b256, keccak256, HashMap, B256, U256};
 
-use crate::{Error, Mpt};
+use crate::{Error, EthereumState, Mpt};
 
 trait RlpBytes {
     /// Returns the RLP-encoding.
@@ -52,6 +54,26 @@ fn test_branch_value() {
     trie.insert(b"dog", b"puppy").unwrap_err();
 }
 
+#[test]
+fn test_longest_prefix() -> Result<(), Error> {
+    let bump = bumpalo::Bump::new();
+    let mut trie = Mpt::new(&bump);
+    trie.insert(b"abc", b"abc")?;
+    trie.insert(b"abx", b"abx")?;
+    trie.insert(b"b", b"b")?;
+    // keys that are prefixes of each other, like "a", "ab" and "abc", cannot be stored
+    assert!(matches!(trie.insert(b"ab", b"ab"), Err(Error::ValueInBranch)));
+
+    assert_eq!(trie.longest_prefix(b"abcd")?, Some((b"abc".to_vec(), b"abc".as_ref())));
+    assert_eq!(trie.longest_prefix(b"abc")?, Some((b"abc".to_vec(), b"abc".as_ref())));
+    assert_eq!(trie.longest_prefix(b"bcd")?, Some((b"b".to_vec(), b"b".as_ref())));
+    assert_eq!(trie.longest_prefix(b"ab")?, None);
+    assert_eq!(trie.longest_prefix(b"abd")?, None);
+    assert_eq!(trie.longest_prefix(b"")?, None);
+
+    Ok(())
+}
+
 #[test]
 fn test_insert() -> Result<(), Error> {
     let bump = bumpalo::Bump::new();
@@ -133,6 +155,52 @@ fn test_keccak_trie() -> Result<(), Error> {
     Ok(())
 }
 
+proptest::proptest! {
+    /// Random interleavings of inserts and deletes must leave the same trie as inserting the
+    /// surviving keys into a fresh one, also in its shape, i.e. collapsing branches and extensions
+    /// when deleting keeps the trie canonical. The keys are two bytes from a small alphabet, so
+    /// that they share long prefixes.
+    #[test]
+    fn test_random_insert_delete(
+        ops in proptest::collection::vec(
+            (proptest::prelude::any::<bool>(), 0..4usize, 0..4usize, 1..=u8::MAX),
+            1..96,
+        )
+    ) {
+        const ALPHABET: [u8; 4] = [0x00, 0x01, 0x10, 0x11];
+
+        let bump = bumpalo::Bump::new();
+        let mut trie = Mpt::new(&bump);
+        let mut expected = std::collections::BTreeMap::new();
+        for (i, (insert, a, b, value)) in ops.into_iter().enumerate() {
+            let key = [ALPHABET[a], ALPHABET[b]];
+            if insert {
+                trie.insert_rlp(&key, value).unwrap();
+                expected.insert(key, value);
+            } else {
+                let deleted = trie.delete(&key).unwrap();
+                proptest::prop_assert_eq!(deleted, expected.remove(&key).is_some());
+            }
+            // hash now and then, so that later updates start from cached references
+            if i % 3 == 0 {
+                trie.hash();
+            }
+        }
+
+        let fresh_bump = bumpalo::Bump::new();
+        let mut fresh = Mpt::new(&fresh_bump);
+        for (key, value) in &expected {
+            fresh.insert_rlp(key, *value).unwrap();
+        }
+        proptest::prop_assert_eq!(trie.hash(), fresh.hash());
+        proptest::prop_assert_eq!(trie.max_depth(), fresh.max_depth());
+        proptest::prop_assert_eq!(trie.is_empty(), expected.is_empty());
+        for (key, value) in &expected {
+            proptest::prop_assert_eq!(trie.get_rlp::<u8>(key).unwrap(), Some(*value));
+        }
+    }
+}
+
 #[test]
 fn test_index_trie() -> Result<(), Error> {
     const N: usize = 512;
@@ -175,6 +243,563 @@ fn test_index_trie() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_hash_deep_trie() -> Result<(), Error> {
+    // The keys `[0; i] ++ [1]` nest, so that each one adds two levels to the trie. Inserting the
+    // longest first keeps every insertion near the root, while hashing has to go all the way down.
+    const N: usize = 4096;
+    fn key(i: usize) -> Vec<u8> {
+        [vec![0u8; i], vec![1]].concat()
+    }
+
+    // small enough that recursing through the 2 * N levels would overflow
+    let deep = std::thread::Builder::new().stack_size(256 * 1024);
+    deep.spawn(|| -> Result<(), Error> {
+        let bump = bumpalo::Bump::new();
+        let mut trie = Mpt::new(&bump);
+        for i in (0..N).rev() {
+            assert!(trie.insert(&key(i), b"value")?);
+        }
+        let hash = trie.hash();
+
+        // only the updated path is rehashed, and matches hashing from scratch
+        trie.insert(&key(0), b"updated")?;
+        let mut expected = Mpt::new(&bump);
+        for i in (1..N).rev() {
+            expected.insert(&key(i), b"value")?;
+        }
+        expected.insert(&key(0), b"updated")?;
+        assert_ne!(trie.hash(), hash);
+        assert_eq!(trie.hash(), expected.hash());
+        assert_eq!(trie.get(&key(0))?, Some(b"updated".as_ref()));
+
+        Ok(())
+    })
+    .unwrap()
+    .join()
+    .unwrap()
+}
+
+#[test]
+fn test_get_rlp_into() -> Result<(), Error> {
+    let bump = bumpalo::Bump::new();
+    let mut trie = Mpt::new(&bump);
+    for i in 0..256u64 {
+        trie.insert_rlp(keccak256(i.to_be_bytes()).as_slice(), U256::from(i) << 128)?;
+    }
+
+    let mut value = U256::MAX;
+    for i in 0..300u64 {
+        let key = keccak256(i.to_be_bytes());
+        let found = trie.get_rlp_into(key.as_slice(), &mut value)?;
+        let expected = trie.get_rlp::<U256>(key.as_slice())?;
+        assert_eq!(found, expected.is_some());
+        if let Some(expected) = expected {
+            assert_eq!(value, expected);
+        }
+    }
+    // a missing key leaves the value of the last found one
+    assert_eq!(value, U256::from(255) << 128);
+
+    Ok(())
+}
+
+#[test]
+fn test_debug_summary() -> Result<(), Error> {
+    let bump = bumpalo::Bump::new();
+    let mut trie = Mpt::new(&bump);
+    for i in 0..1024u32 {
+        trie.insert_rlp(keccak256(i.to_be_bytes()).as_slice(), i)?;
+    }
+
+    // formatting neither hashes the trie nor caches its references
+    let debug = format!("{trie:?}");
+    assert!(debug.contains("root_hash: <unhashed>"), "{debug}");
+    assert_eq!(format!("{trie:?}"), debug);
+
+    let root_hash = trie.hash();
+    let debug = format!("{trie:?}");
+    assert!(debug.len() < 200, "{debug}");
+    assert!(debug.contains(&format!("{root_hash:?}")), "{debug}");
+    assert!(debug.contains(&format!("num_nodes: {}", trie.num_nodes())), "{debug}");
+    assert!(debug.contains("Branch"), "{debug}");
+
+    Ok(())
+}
+
+#[test]
+fn test_subtree_hash() -> Result<(), Error> {
+    let bump = bumpalo::Bump::new();
+    let mut trie = Mpt::new(&bump);
+    // an extension [1, 2, 0] to a branch with two leaves, whose paths are one byte each
+    trie.insert(&[0x12, 0x01, 0xaa], b"left")?;
+    trie.insert(&[0x12, 0x02, 0xbb], b"right")?;
+
+    let mut leaf = Mpt::new(&bump);
+    leaf.insert(&[0xaa], b"left")?;
+    assert_eq!(trie.subtree_hash(&[1, 2, 0, 1])?, Some(leaf.hash()));
+    assert_eq!(trie.subtree_hash(&[])?, Some(trie.hash()));
+    assert!(trie.subtree_hash(&[1, 2, 0])?.is_some());
+
+    // inside the extension, past the branch and inside the leaf
+    assert_eq!(trie.subtree_hash(&[1, 2])?, None);
+    assert_eq!(trie.subtree_hash(&[1, 2, 0, 3])?, None);
+    assert_eq!(trie.subtree_hash(&[1, 2, 0, 1, 0xa])?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_max_depth() -> Result<(), Error> {
+    let bump = bumpalo::Bump::new();
+    let mut trie = Mpt::new(&bump);
+    assert_eq!(trie.max_depth(), 0);
+
+    trie.insert(b"a", b"a")?;
+    assert_eq!(trie.max_depth(), 1);
+
+    // 0x61 and 0x71 diverge at the first nibble: a branch with two leaves
+    trie.insert(b"q", b"q")?;
+    assert_eq!(trie.max_depth(), 2);
+
+    // 0x61 and 0x62 share the first nibble: a second branch below the first one
+    trie.insert(b"b", b"b")?;
+    assert_eq!(trie.max_depth(), 3);
+
+    // 0x7172 and 0x7173 share three nibbles: an extension to a branch below the root
+    trie.delete(b"q")?;
+    trie.insert(b"qr", b"qr")?;
+    trie.insert(b"qs", b"qs")?;
+    assert_eq!(trie.max_depth(), 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_get_many() -> Result<(), Error> {
+    const N: usize = 512;
+
+    let bump = bumpalo::Bump::new();
+    let mut keccak_trie = Mpt::new(&bump);
+    let mut index_trie = Mpt::new(&bump);
+    for i in 0..N {
+        keccak_trie.insert_rlp(keccak256(i.to_be_bytes()).as_slice(), i)?;
+        index_trie.insert_rlp(&i.to_rlp(), i)?;
+    }
+
+    // interleave present and missing keys, including duplicates
+    let keccak_keys: Vec<_> =
+        (0..2 * N).chain(0..N / 2).map(|i| keccak256(i.to_be_bytes())).collect();
+    let keys: Vec<&[u8]> = keccak_keys.iter().map(|k| k.as_slice()).collect();
+    let expected: Vec<_> = keys.iter().map(|k| keccak_trie.get(k)).collect::<Result<_, _>>()?;
+    assert_eq!(keccak_trie.get_many(&keys)?, expected);
+
+    let index_keys: Vec<_> = (0..2 * N).rev().chain(0..N / 2).map(|i| i.to_rlp()).collect();
+    let keys: Vec<&[u8]> = index_keys.iter().map(Vec::as_slice).collect();
+    let expected: Vec<_> = keys.iter().map(|k| index_trie.get(k)).collect::<Result<_, _>>()?;
+    assert_eq!(index_trie.get_many(&keys)?, expected);
+
+    assert!(Mpt::new(&bump).get_many(&keys)?.iter().all(Option::is_none));
+    assert!(index_trie.get_many(&[])?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_update_from_bundle_state() -> Result<(), Error> {
+    let alice = address!("0x00000000000000000000000000000000000a11ce");
+    let bob = address!("0x0000000000000000000000000000000000000b0b");
+    let alice_info = AccountInfo { balance: U256::from(100), nonce: 1, ..Default::default() };
+    let bob_info = AccountInfo { balance: U256::from(7), ..Default::default() };
+    let storage: HashMap<U256, (U256, U256)> = HashMap::from_iter([
+        (U256::from(1), (U256::ZERO, U256::from(10))),
+        (U256::from(2), (U256::ZERO, U256::from(20))),
+    ]);
+
+    let bundle_state = BundleState::builder(0..=0)
+        .state_present_account_info(alice, alice_info.clone())
+        .state_storage(alice, storage.clone())
+        .state_present_account_info(bob, bob_info.clone())
+        .build();
+
+    let mut state = EthereumState::new();
+    state.update_from_bundle_state(&bundle_state)?;
+    let root = state.state_trie.hash();
+
+    // build the expected tries by hand
+    let bump = bumpalo::Bump::new();
+    let mut storage_trie = Mpt::new(&bump);
+    for (slot, (_, value)) in &storage {
+        storage_trie.insert_rlp(keccak256(slot.to_be_bytes::<32>()).as_slice(), *value)?;
+    }
+    let mut state_trie = Mpt::new(&bump);
+    for (address, info, storage_root) in
+        [(alice, &alice_info, storage_trie.hash()), (bob, &bob_info, Mpt::new(&bump).hash())]
+    {
+        let account = TrieAccount {
+            nonce: info.nonce,
+            balance: info.balance,
+            storage_root,
+            code_hash: info.code_hash,
+        };
+        state_trie.insert_rlp(keccak256(address).as_slice(), account)?;
+    }
+    assert_eq!(root, state_trie.hash());
+    assert_eq!(state.storage_tries[&keccak256(alice)].hash(), storage_trie.hash());
+
+    // applying the same bundle again does not change the root
+    state.update_from_bundle_state(&bundle_state)?;
+    assert_eq!(state.state_trie.hash(), root);
+
+    Ok(())
+}
+
+#[test]
+fn test_apply_hashed() -> Result<(), Error> {
+    let alice = address!("0x00000000000000000000000000000000000a11ce");
+    let bob = address!("0x0000000000000000000000000000000000000b0b");
+    let alice_info = AccountInfo { balance: U256::from(100), nonce: 1, ..Default::default() };
+    let bob_info = AccountInfo { balance: U256::from(7), ..Default::default() };
+    let trie_account = |info: &AccountInfo| TrieAccount {
+        nonce: info.nonce,
+        balance: info.balance,
+        storage_root: reth_trie::EMPTY_ROOT_HASH,
+        code_hash: info.code_hash,
+    };
+    let hashed_slot = |slot: u64| keccak256(U256::from(slot).to_be_bytes::<32>());
+
+    // create both accounts, with storage for alice
+    let created = BundleState::builder(0..=0)
+        .state_present_account_info(alice, alice_info.clone())
+        .state_storage(
+            alice,
+            HashMap::from_iter([
+                (U256::from(1), (U256::ZERO, U256::from(10))),
+                (U256::from(2), (U256::ZERO, U256::from(20))),
+            ]),
+        )
+        .state_present_account_info(bob, bob_info.clone())
+        .build();
+    let mut bundle = EthereumState::new();
+    bundle.update_from_bundle_state(&created)?;
+    let mut hashed = EthereumState::new();
+    hashed.apply_hashed(
+        &[
+            (keccak256(alice), Some(trie_account(&alice_info))),
+            (keccak256(bob), Some(trie_account(&bob_info))),
+        ],
+        &[(
+            keccak256(alice),
+            vec![(hashed_slot(1), U256::from(10)), (hashed_slot(2), U256::from(20))],
+        )],
+    )?;
+    assert_eq!(hashed.state_trie.hash(), bundle.state_trie.hash());
+    assert_eq!(hashed.storage_root(&keccak256(alice)), bundle.storage_root(&keccak256(alice)));
+
+    // clear a slot of alice and remove bob
+    let updated = BundleState::builder(1..=1)
+        .state_present_account_info(alice, alice_info.clone())
+        .state_storage(alice, HashMap::from_iter([(U256::from(1), (U256::from(10), U256::ZERO))]))
+        .state_original_account_info(bob, bob_info)
+        .build();
+    bundle.update_from_bundle_state(&updated)?;
+    hashed.apply_hashed(
+        &[(keccak256(alice), Some(trie_account(&alice_info))), (keccak256(bob), None)],
+        &[(keccak256(alice), vec![(hashed_slot(1), U256::ZERO)])],
+    )?;
+    assert_eq!(hashed.state_trie.hash(), bundle.state_trie.hash());
+    assert!(hashed.storage_root(&keccak256(bob)).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_storage_root() -> Result<(), Error> {
+    let alice = address!("0x00000000000000000000000000000000000a11ce");
+    let bob = address!("0x0000000000000000000000000000000000000b0b");
+    let carol = address!("0x00000000000000000000000000000000000ca401");
+    let info = AccountInfo { balance: U256::from(1), ..Default::default() };
+
+    let bundle_state = BundleState::builder(0..=0)
+        .state_present_account_info(alice, info.clone())
+        .state_storage(alice, HashMap::from_iter([(U256::from(1), (U256::ZERO, U256::from(2)))]))
+        .state_present_account_info(bob, info.clone())
+        .state_storage(bob, HashMap::from_iter([(U256::from(3), (U256::ZERO, U256::from(4)))]))
+        .build();
+
+    let mut state = EthereumState::new();
+    state.update_from_bundle_state(&bundle_state)?;
+
+    for address in [alice, bob] {
+        let hashed_address = keccak256(address);
+        let account: TrieAccount = state.state_trie.get_rlp(hashed_address.as_slice())?.unwrap();
+        assert_eq!(state.storage_root(&hashed_address), Some(account.storage_root));
+    }
+    assert_ne!(state.storage_root(&keccak256(alice)), state.storage_root(&keccak256(bob)));
+    assert_eq!(state.storage_root(&keccak256(carol)), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_recompute_storage_root() -> Result<(), Error> {
+    let alice = address!("0x00000000000000000000000000000000000a11ce");
+    let bob = address!("0x0000000000000000000000000000000000000b0b");
+    let info = AccountInfo { balance: U256::from(1), ..Default::default() };
+    let bundle_state = BundleState::builder(0..=0)
+        .state_present_account_info(alice, info.clone())
+        .state_storage(alice, HashMap::from_iter([(U256::from(1), (U256::ZERO, U256::from(2)))]))
+        .state_present_account_info(bob, info)
+        .build();
+    let mut state = EthereumState::new();
+    state.update_from_bundle_state(&bundle_state)?;
+
+    let hashed_alice = keccak256(alice);
+    let account: TrieAccount = state.state_trie.get_rlp(hashed_alice.as_slice())?.unwrap();
+    assert_eq!(state.recompute_storage_root(&hashed_alice), Some(account.storage_root));
+    assert_eq!(state.recompute_storage_root(&keccak256(bob)), Some(reth_trie::EMPTY_ROOT_HASH));
+    assert_eq!(state.recompute_storage_root(&B256::ZERO), None);
+
+    // a lazy trie whose expected root is wrong still gives its actual root
+    let storage_trie = &state.storage_tries[&hashed_alice];
+    let bytes = storage_trie.encode_trie();
+    let bump = bumpalo::Bump::new();
+    let mut lazy = EthereumState::new_in(&bump);
+    lazy.insert_lazy_storage_trie(hashed_alice, storage_trie.num_nodes(), &bytes, B256::ZERO);
+    assert_eq!(lazy.storage_root(&hashed_alice), Some(B256::ZERO));
+    let allocated = bump.allocated_bytes();
+    assert_eq!(lazy.recompute_storage_root(&hashed_alice), Some(account.storage_root));
+    // the trie is decoded in a temporary arena
+    assert_eq!(bump.allocated_bytes(), allocated);
+    assert!(matches!(lazy.storage_trie(&hashed_alice), Err(Error::StorageRootMismatch { .. })));
+
+    // the failure is cached instead of decoding into the arena again on every access
+    let allocated = bump.allocated_bytes();
+    let err = lazy.storage_trie(&hashed_alice).unwrap_err();
+    assert!(
+        matches!(err, Error::StorageRootMismatch { actual, .. } if actual == account.storage_root)
+    );
+    assert_eq!(bump.allocated_bytes(), allocated);
+    assert_eq!(lazy.num_encoded_storage_tries(), 0);
+    // and updating the account fails the same way, without dropping its storage trie
+    let bundle_state = BundleState::builder(1..=1)
+        .state_present_account_info(alice, AccountInfo::default())
+        .build();
+    assert!(matches!(
+        lazy.update_from_bundle_state(&bundle_state),
+        Err(Error::StorageRootMismatch { .. })
+    ));
+    assert_eq!(lazy.storage_root(&hashed_alice), Some(B256::ZERO));
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_lazy_account() -> Result<(), Error> {
+    let alice = address!("0x00000000000000000000000000000000000a11ce");
+    let hashed_alice = keccak256(alice);
+    let bump = bumpalo::Bump::new();
+    let storage_trie = Mpt::new(&bump);
+    let bytes = storage_trie.encode_trie();
+    let mut state = EthereumState::new_in(&bump);
+    // a lazy trie that fails its root check when decoded
+    state.insert_lazy_storage_trie(hashed_alice, storage_trie.num_nodes(), &bytes, B256::ZERO);
+    let bundle_state = BundleState::builder(0..=0)
+        .state_original_account_info(alice, AccountInfo::default())
+        .build();
+
+    // removing the account does not decode its storage trie, and can be rolled back
+    state.begin_transaction();
+    state.update_from_bundle_state(&bundle_state)?;
+    assert_eq!(state.storage_root(&hashed_alice), None);
+    state.rollback();
+    assert_eq!(state.storage_root(&hashed_alice), Some(B256::ZERO));
+    assert_eq!(state.num_encoded_storage_tries(), 1);
+
+    state.update_from_bundle_state(&bundle_state)?;
+    assert_eq!(state.storage_root(&hashed_alice), None);
+    assert!(state.storage_trie(&hashed_alice)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_update_and_root_invalidates_path() -> Result<(), Error> {
+    let mut builder = BundleState::builder(0..=0);
+    for i in 1..=256u64 {
+        let address = revm_primitives::Address::left_padding_from(&i.to_be_bytes());
+        let info = AccountInfo { balance: U256::from(i), nonce: i, ..Default::default() };
+        builder = builder.state_present_account_info(address, info);
+    }
+    let mut state = EthereumState::new();
+    let created_root = state.update_and_root(&builder.build())?;
+    assert_eq!(created_root, state.state_trie.hash());
+    assert_eq!(state.state_trie.dirty_node_count(), 0);
+
+    // updating a single account only invalidates the nodes on its path
+    let address = revm_primitives::Address::left_padding_from(&7u64.to_be_bytes());
+    let info = AccountInfo { balance: U256::from(1000), nonce: 8, ..Default::default() };
+    let bundle_state =
+        BundleState::builder(1..=1).state_present_account_info(address, info).build();
+    state.update_from_bundle_state(&bundle_state)?;
+    let dirty = state.state_trie.dirty_node_count();
+    // the path to a leaf of 256 random keys is about 3 nodes long, out of more than 256 nodes
+    assert!(dirty > 0 && dirty <= 8, "{dirty} dirty nodes");
+    assert!(state.state_trie.num_nodes() > 256);
+
+    assert_ne!(state.state_trie.hash(), created_root);
+    assert_eq!(state.state_trie.dirty_node_count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_transaction_rollback() -> Result<(), Error> {
+    let address = |i: u64| revm_primitives::Address::left_padding_from(&i.to_be_bytes());
+    let info = |i: u64| AccountInfo { balance: U256::from(i), nonce: i, ..Default::default() };
+    let storage =
+        |value: u64| HashMap::from_iter([(U256::from(1), (U256::ZERO, U256::from(value)))]);
+
+    let mut builder = BundleState::builder(0..=0);
+    for i in 1..=64 {
+        builder = builder
+            .state_present_account_info(address(i), info(i))
+            .state_storage(address(i), storage(i));
+    }
+    let mut state = EthereumState::new();
+    let root = state.update_and_root(&builder.build())?;
+    let num_nodes = state.state_trie.num_nodes();
+    let storage_root = state.storage_root(&keccak256(address(1)));
+
+    // update an account and its storage, create one and remove another
+    let bundle_state = BundleState::builder(1..=1)
+        .state_present_account_info(address(1), info(100))
+        .state_storage(address(1), storage(100))
+        .state_present_account_info(address(65), info(65))
+        .state_storage(address(65), storage(65))
+        .state_original_account_info(address(2), info(2))
+        .build();
+
+    state.begin_transaction();
+    let speculative_root = state.update_and_root(&bundle_state)?;
+    assert_ne!(speculative_root, root);
+    assert!(state.storage_root(&keccak256(address(2))).is_none());
+    state.rollback();
+
+    assert_eq!(state.state_trie.hash(), root);
+    assert_eq!(state.state_trie.num_nodes(), num_nodes);
+    assert_eq!(state.storage_root(&keccak256(address(1))), storage_root);
+    assert!(state.storage_root(&keccak256(address(2))).is_some());
+    assert!(state.storage_root(&keccak256(address(65))).is_none());
+    assert_eq!(state.state_trie.dirty_node_count(), 0);
+
+    // after a commit, there is nothing left to roll back
+    state.begin_transaction();
+    assert_eq!(state.update_and_root(&bundle_state)?, speculative_root);
+    state.commit();
+    state.rollback();
+    assert_eq!(state.state_trie.hash(), speculative_root);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_account_names_tampered_account() -> Result<(), Error> {
+    let accounts: Vec<_> = (1..=64u64)
+        .map(|i| {
+            let address = revm_primitives::Address::left_padding_from(&i.to_be_bytes());
+            let info = AccountInfo { balance: U256::from(i), nonce: i, ..Default::default() };
+            (address, info)
+        })
+        .collect();
+    let storage = |value: u64| -> HashMap<U256, (U256, U256)> {
+        HashMap::from_iter([(U256::from(1), (U256::ZERO, U256::from(value)))])
+    };
+
+    let mut builder = BundleState::builder(0..=0);
+    for (address, info) in &accounts {
+        builder = builder
+            .state_present_account_info(*address, info.clone())
+            .state_storage(*address, storage(1));
+    }
+    let mut expected = EthereumState::new();
+    expected.update_from_bundle_state(&builder.build())?;
+    assert_eq!(expected.diff_account(&expected.clone()), None);
+
+    // write a wrong value to a single storage slot
+    let (tampered, info) = &accounts[41];
+    let bundle_state = BundleState::builder(1..=1)
+        .state_present_account_info(*tampered, info.clone())
+        .state_storage(*tampered, storage(2))
+        .build();
+    let mut actual = expected.clone();
+    actual.update_from_bundle_state(&bundle_state)?;
+
+    assert_ne!(actual.state_trie.hash(), expected.state_trie.hash());
+    assert_eq!(actual.diff_account(&expected), Some(keccak256(tampered)));
+    assert_eq!(expected.diff_account(&actual), Some(keccak256(tampered)));
+
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_par_update_from_bundle_state() -> Result<(), Error> {
+    let accounts: Vec<_> = (1..=64u64)
+        .map(|i| {
+            let address = revm_primitives::Address::left_padding_from(&i.to_be_bytes());
+            let info = AccountInfo { balance: U256::from(i), nonce: i, ..Default::default() };
+            let storage: HashMap<U256, (U256, U256)> = HashMap::from_iter(
+                (0..i).map(|slot| (U256::from(slot), (U256::ZERO, U256::from(slot + 1)))),
+            );
+            (address, info, storage)
+        })
+        .collect();
+
+    let mut builder = BundleState::builder(0..=0);
+    for (address, info, storage) in &accounts {
+        builder = builder
+            .state_present_account_info(*address, info.clone())
+            .state_storage(*address, storage.clone());
+    }
+    let created = builder.build();
+
+    // clear the storage of every other account, delete every third one and only change the
+    // balance of some of the others
+    let mut builder = BundleState::builder(1..=1);
+    for (i, (address, info, storage)) in accounts.iter().enumerate() {
+        if i % 3 == 0 {
+            builder = builder.state_original_account_info(*address, info.clone());
+        } else if i % 2 == 0 {
+            let cleared = storage.iter().map(|(slot, (_, value))| (*slot, (*value, U256::ZERO)));
+            builder = builder
+                .state_present_account_info(*address, info.clone())
+                .state_storage(*address, HashMap::from_iter(cleared));
+        } else if i % 5 == 0 {
+            let info = AccountInfo { balance: info.balance + U256::from(1), ..info.clone() };
+            builder = builder.state_present_account_info(*address, info);
+        }
+    }
+    let updated = builder.build();
+
+    // fewer arenas than updated accounts, reused across the updates
+    let arenas = [bumpalo::Bump::new(), bumpalo::Bump::new(), bumpalo::Bump::new()];
+    let bump = bumpalo::Bump::new();
+    let mut serial = EthereumState::new();
+    let mut parallel = EthereumState::new_in(&bump);
+    for bundle_state in [&created, &updated] {
+        serial.update_from_bundle_state(bundle_state)?;
+        parallel.par_update_from_bundle_state(bundle_state, &arenas)?;
+        assert_eq!(parallel.state_trie.hash(), serial.state_trie.hash());
+        assert_eq!(parallel.storage_tries.len(), serial.storage_tries.len());
+        for (hashed_address, trie) in &serial.storage_tries {
+            assert_eq!(parallel.storage_tries[hashed_address].hash(), trie.hash());
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "host")]
 #[test]
 fn test_serde_index_trie() -> Result<(), Error> {
@@ -202,6 +827,26 @@ fn test_serde_index_trie() -> Result<(), Error> {
     Ok(())
 }
 
+#[cfg(feature = "host")]
+#[test]
+fn test_decode_trie_caps_num_nodes() -> Result<(), Error> {
+    let bump = bumpalo::Bump::new();
+    let mut trie = Mpt::new(&bump);
+    for i in 0..16usize {
+        trie.insert_rlp(&i.to_rlp(), i)?;
+    }
+    let encoded = trie.encode_trie();
+
+    // preallocating for the claimed number of nodes would abort
+    for num_nodes in [usize::MAX, 1 << 40] {
+        let recovered_trie = Mpt::decode_trie(&bump, &mut encoded.as_slice(), num_nodes)?;
+        assert_eq!(recovered_trie.hash(), trie.hash());
+        assert_eq!(recovered_trie.get_rlp(&7usize.to_rlp())?, Some(7usize));
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "host")]
 #[test]
 fn test_serde_keccak_trie() -> Result<(), Error> {
@@ -228,3 +873,95 @@ fn test_serde_keccak_trie() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(feature = "host")]
+#[test]
+fn test_serde_unaligned_trie() -> Result<(), Error> {
+    use crate::EncodeOptions;
+
+    let bump = bumpalo::Bump::new();
+    let mut trie = Mpt::new(&bump);
+    for i in 0..64usize {
+        trie.insert_rlp(keccak256(i.to_be_bytes()).as_slice(), i)?;
+    }
+    let unaligned = EncodeOptions { align: 1 };
+    let encoded = trie.encode_trie_with(unaligned);
+    assert!(encoded.len() < trie.encode_trie().len());
+
+    // the nodes are plain RLP lists one after another, starting with the root
+    let mut buf = encoded.as_slice();
+    let mut nodes = Vec::new();
+    while !buf.is_empty() {
+        let start = buf;
+        alloy_rlp::Header::decode_bytes(&mut buf, true)?;
+        nodes.push(&start[..start.len() - buf.len()]);
+    }
+    assert!(nodes.len() > 64);
+    assert_eq!(keccak256(nodes[0]), trie.hash());
+
+    let recovered_trie =
+        Mpt::decode_trie_with(&bump, &mut encoded.as_slice(), trie.num_nodes(), unaligned)?;
+    assert_eq!(recovered_trie.hash(), trie.hash());
+    assert_eq!(recovered_trie.get_rlp(keccak256(7usize.to_be_bytes()).as_slice())?, Some(7usize));
+
+    let empty = Mpt::new(&bump).encode_trie_with(unaligned);
+    assert_eq!(empty, [alloy_rlp::EMPTY_STRING_CODE]);
+    let recovered_empty = Mpt::decode_trie_with(&bump, &mut empty.as_slice(), 1, unaligned)?;
+    assert_eq!(recovered_empty.hash(), reth_trie::EMPTY_ROOT_HASH);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "host", feature = "parallel"))]
+#[test]
+fn test_par_transition_proofs_to_tries() -> Result<(), Error> {
+    use crate::from_proof::{par_transition_proofs_to_tries, transition_proofs_to_tries};
+    use reth_trie::{AccountProof, StorageProof};
+
+    let bump = bumpalo::Bump::new();
+    let mut state_trie = Mpt::new(&bump);
+    let mut accounts = Vec::new();
+    for i in 1..=32u64 {
+        let address = revm_primitives::Address::left_padding_from(&i.to_be_bytes());
+        let mut storage_trie = Mpt::new(&bump);
+        let mut storage_proofs = Vec::new();
+        for slot in 0..i {
+            let key = revm_primitives::B256::from(U256::from(slot));
+            storage_trie.insert_rlp(keccak256(key).as_slice(), U256::from(slot + 1))?;
+            storage_proofs.push(StorageProof::new(key));
+        }
+        // all the nodes of the trie are a superset of the proof of each of its keys
+        for storage_proof in &mut storage_proofs {
+            storage_proof.proof = storage_trie.payloads();
+        }
+        let account = TrieAccount {
+            nonce: i,
+            balance: U256::from(i),
+            storage_root: storage_trie.hash(),
+            code_hash: revm_primitives::KECCAK_EMPTY,
+        };
+        state_trie.insert_rlp(keccak256(address).as_slice(), account)?;
+        accounts.push(AccountProof {
+            storage_root: storage_trie.hash(),
+            storage_proofs,
+            ..AccountProof::new(address)
+        });
+    }
+
+    let state_proof = state_trie.payloads();
+    let proofs: HashMap<_, _> = accounts
+        .into_iter()
+        .map(|account| (account.address, AccountProof { proof: state_proof.clone(), ..account }))
+        .collect();
+
+    let serial = transition_proofs_to_tries(state_trie.hash(), &proofs, &proofs)?;
+    let parallel = par_transition_proofs_to_tries(state_trie.hash(), &proofs, &proofs)?;
+    assert_eq!(serial.state_trie.hash(), state_trie.hash());
+    assert_eq!(parallel.state_trie.hash(), serial.state_trie.hash());
+    assert_eq!(parallel.storage_tries.len(), serial.storage_tries.len());
+    for (hashed_address, trie) in &serial.storage_tries {
+        assert_eq!(parallel.storage_tries[hashed_address].hash(), trie.hash());
+    }
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, mem::MaybeUninit};
+use std::{cell::RefCell, fmt, mem::MaybeUninit};
 
 use alloy_rlp::Encodable;
 use bumpalo::Bump;
@@ -10,14 +10,37 @@ use crate::{
     bump_bufmut::BumpBytesMut,
     hp::{
         encoded_path_eq_nibs, encoded_path_strip_prefix, lcp, prefix_to_nibs,
-        to_encoded_path_with_bump, to_nibs,
+        to_encoded_path_with_bump, to_nibs, Nibbles,
     },
-    node::{NodeData, NodeId, NodeRef},
+    node::{NodeData, NodeId, NodeKind, NodeRef},
 };
 
 /// OpenVM memory alignment word size.
 const MIN_ALIGN: usize = 4;
 
+/// Layout of the tries serialized by [`Mpt::encode_trie_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// The RLP encoding of each node is padded with zeros to a multiple of `align` bytes, which
+    /// lets the guest hash the nodes in place. An `align` of 1 gives the plain RLP encodings of
+    /// the nodes one after another.
+    pub align: usize,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self { align: MIN_ALIGN }
+    }
+}
+
+impl EncodeOptions {
+    /// Number of padding bytes following a node encoding of `len` bytes.
+    fn padding_len(self, len: usize) -> usize {
+        let align = self.align.max(1);
+        (align - (len % align)) % align
+    }
+}
+
 /// Initial capacity of [`MptTrie`]'s `rlp_scratch`.
 const RLP_SCRATCH_INIT_CAPACITY: usize = 600;
 
@@ -29,7 +52,7 @@ const VALUE_RLP_BUFFER_CAPACITY: usize = 200;
 /// as the canonical "no node" identifier.
 pub(crate) const NULL_NODE_ID: NodeId = 0;
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, thiserror::Error)]
 pub enum Error {
     /// Node reference does not match the expected node reference in the parent.
     #[error("node reference mismatch")]
@@ -44,12 +67,34 @@ pub enum Error {
     /// Occurs when a value is unexpectedly found in a branch node.
     #[error("branch node with value")]
     ValueInBranch,
+    /// Occurs when resolving a node reaches one of its own ancestors again.
+    #[error("cycle detected while resolving node {0:#}")]
+    ResolveCycle(B256),
+    /// Occurs when resolving a trie nests nodes deeper than the allowed maximum.
+    #[error("maximum resolve depth of {0} nodes exceeded")]
+    DepthExceeded(usize),
+    /// Occurs when a lazily decoded storage trie does not have the storage root of its account.
+    #[error("storage root mismatch on hashed account {hashed_address}: got {actual}, expected {expected}")]
+    StorageRootMismatch { hashed_address: B256, actual: B256, expected: B256 },
+    /// Occurs when a node of a proof cannot be decoded. `index` is the position of the node in
+    /// the proof of `count` nodes, and `len` the length of its encoding.
+    #[error("failed to decode proof node {index} of {count} (len {len})")]
+    ProofNodeDecode {
+        index: usize,
+        count: usize,
+        len: usize,
+        #[source]
+        source: Box<Error>,
+    },
 }
 
 /// Arena-based implementation that stores all nodes in a flat vector and uses indices for better
 /// memory layout and performance. The lifetime parameter `'a` allows zero-copy deserialization by
 /// borrowing from the input buffer.
-#[derive(Debug, Clone)]
+///
+/// Its [`Debug`](fmt::Debug) output only summarizes the trie, see [`Self::print_trie`] for all its
+/// nodes.
+#[derive(Clone)]
 pub struct Mpt<'a> {
     root_id: NodeId,
 
@@ -65,6 +110,21 @@ pub struct Mpt<'a> {
 
     /// Bump allocation area.
     bump: &'a Bump,
+
+    /// Mutations since [`Self::begin_journal`], if it was called since the last commit or
+    /// rollback.
+    journal: Option<MptJournal<'a>>,
+}
+
+/// Records what is needed to undo the mutations of a trie: nodes are only ever appended or
+/// overwritten in place, so it is enough to truncate the arena and restore the overwritten slots.
+#[derive(Debug, Clone)]
+struct MptJournal<'a> {
+    root_id: NodeId,
+    num_nodes: usize,
+    /// Previous data and cached reference of the overwritten nodes, in order of mutation. Nodes
+    /// added since the journal began are not recorded, they are truncated on rollback.
+    overwritten: Vec<(NodeId, NodeData<'a>, Option<NodeRef<'a>>)>,
 }
 
 impl<'a> Mpt<'a> {
@@ -88,6 +148,7 @@ impl<'a> Mpt<'a> {
             cached_references,
             bump,
             root_id: 0,
+            journal: None,
         }
     }
 }
@@ -105,21 +166,33 @@ impl<'a> Mpt<'a> {
     /// performance-critical.
     #[cfg(feature = "host")]
     pub fn encode_trie(&self) -> Vec<u8> {
+        self.encode_trie_with(EncodeOptions::default())
+    }
+
+    /// Same as [`Self::encode_trie`], with the given layout. The tries must be decoded with the
+    /// same options, see [`Self::decode_trie_with`].
+    #[cfg(feature = "host")]
+    pub fn encode_trie_with(&self, options: EncodeOptions) -> Vec<u8> {
         let mut encoded = Vec::new();
-        self.encode_trie_internal(self.root_id, &mut encoded);
+        self.encode_trie_internal(self.root_id, options, &mut encoded);
         encoded
     }
 
     #[cfg(feature = "host")]
-    fn encode_trie_internal(&self, node_id: NodeId, out: &mut dyn alloy_rlp::BufMut) {
+    fn encode_trie_internal(
+        &self,
+        node_id: NodeId,
+        options: EncodeOptions,
+        out: &mut dyn alloy_rlp::BufMut,
+    ) {
         let payload_length = self.payload_length(node_id);
         self.encode_with_payload_len(node_id, payload_length, out);
 
-        // Pad the RLP encoding so its total length is divisible by `MIN_ALIGN`.
-        // This let us to avoid memcpy operations when calculating keccak(rlp_encoded) during
-        // decoding.
+        // Pad the RLP encoding so its total length is divisible by the alignment, `MIN_ALIGN` by
+        // default. This let us to avoid memcpy operations when calculating keccak(rlp_encoded)
+        // during decoding.
         let rlp_length = payload_length + alloy_rlp::length_of_length(payload_length);
-        let padding_len = (MIN_ALIGN - (rlp_length % MIN_ALIGN)) % MIN_ALIGN;
+        let padding_len = options.padding_len(rlp_length);
         for _ in 0..padding_len {
             out.put_u8(0);
         }
@@ -128,12 +201,12 @@ impl<'a> Mpt<'a> {
             NodeData::Branch(childs) => {
                 childs.iter().for_each(|c| {
                     if let Some(child_id) = c {
-                        self.encode_trie_internal(*child_id, out)
+                        self.encode_trie_internal(*child_id, options, out)
                     }
                 });
             }
             NodeData::Extension(_, ext_id) => {
-                self.encode_trie_internal(ext_id, out);
+                self.encode_trie_internal(ext_id, options, out);
             }
             _ => {}
         }
@@ -145,7 +218,21 @@ impl<'a> Mpt<'a> {
         bytes: &mut &'a [u8],
         num_nodes: usize,
     ) -> Result<Self, Error> {
-        if bytes == &[alloy_rlp::EMPTY_STRING_CODE, 0, 0, 0] {
+        Self::decode_trie_with(bump, bytes, num_nodes, EncodeOptions::default())
+    }
+
+    /// Same as [`Self::decode_trie`], for bytes encoded with the given options, see
+    /// [`Self::encode_trie_with`].
+    pub fn decode_trie_with(
+        bump: &'a Bump,
+        bytes: &mut &'a [u8],
+        num_nodes: usize,
+        options: EncodeOptions,
+    ) -> Result<Self, Error> {
+        if bytes.len() == 1 + options.padding_len(1) &&
+            bytes[0] == alloy_rlp::EMPTY_STRING_CODE &&
+            bytes[1..].iter().all(|&b| b == 0)
+        {
             return Ok(Self::new(bump));
         }
 
@@ -162,6 +249,11 @@ impl<'a> Mpt<'a> {
         //
         // More advanced improvement: either pre-execute block at guest to know exact allocations in
         // advance, or allocate a separate arena specifically for updates.
+        //
+        // `num_nodes` is untrusted, so it is capped to the number of nodes that `bytes` can hold:
+        // every node takes at least the alignment in bytes with its padding. Beyond that, the
+        // vector grows as needed.
+        let num_nodes = num_nodes.min(bytes.len() / options.align.max(1) + 1);
         let capacity = num_nodes + (num_nodes / 2);
         let mut trie = Self::with_capacity(bump, capacity);
 
@@ -186,7 +278,7 @@ impl<'a> Mpt<'a> {
             }
         };
 
-        let root_id = trie.decode_trie_internal(bytes, root_ref)?;
+        let root_id = trie.decode_trie_internal(bytes, root_ref, options)?;
         trie.root_id = root_id;
 
         Ok(trie)
@@ -196,6 +288,7 @@ impl<'a> Mpt<'a> {
         &mut self,
         bytes: &mut &'a [u8],
         expected_node_ref: NodeRef<'a>,
+        options: EncodeOptions,
     ) -> Result<NodeId, Error> {
         let rlp_node_header_start = *bytes;
         let alloy_rlp::Header { list, payload_length } = alloy_rlp::Header::decode(bytes)?;
@@ -206,7 +299,7 @@ impl<'a> Mpt<'a> {
 
         let rlp_node = &rlp_node_header_start[..rlp_node_length];
 
-        let padding_len = (MIN_ALIGN - (rlp_node_length % MIN_ALIGN)) % MIN_ALIGN;
+        let padding_len = options.padding_len(rlp_node_length);
         // SAFETY: we expect the padding. See the `encode_trie_internal` function.
         unsafe { advance_unchecked(bytes, padding_len) };
 
@@ -266,7 +359,8 @@ impl<'a> Mpt<'a> {
                 // extension node
                 let ext_node_expected_ref =
                     NodeRef::from_rlp_slice(&item1_header_start[..item1_length]);
-                let ext_node_id = self.decode_trie_internal(bytes, ext_node_expected_ref)?;
+                let ext_node_id =
+                    self.decode_trie_internal(bytes, ext_node_expected_ref, options)?;
                 let node_data = NodeData::Extension(path, ext_node_id);
                 return Ok(self.add_node(node_data, Some(node_ref)));
             } else {
@@ -283,7 +377,7 @@ impl<'a> Mpt<'a> {
             if child0_expected_node_ref.as_slice() == NULL_NODE_REF_SLICE {
                 None
             } else {
-                Some(self.decode_trie_internal(bytes, child0_expected_node_ref)?)
+                Some(self.decode_trie_internal(bytes, child0_expected_node_ref, options)?)
             }
         };
 
@@ -292,7 +386,7 @@ impl<'a> Mpt<'a> {
             if child1_expected_node_ref.as_slice() == NULL_NODE_REF_SLICE {
                 None
             } else {
-                Some(self.decode_trie_internal(bytes, child1_expected_node_ref)?)
+                Some(self.decode_trie_internal(bytes, child1_expected_node_ref, options)?)
             }
         };
 
@@ -321,7 +415,7 @@ impl<'a> Mpt<'a> {
                 if child_expected_node_ref.as_slice() == NULL_NODE_REF_SLICE {
                     None
                 } else {
-                    Some(self.decode_trie_internal(bytes, child_expected_node_ref)?)
+                    Some(self.decode_trie_internal(bytes, child_expected_node_ref, options)?)
                 }
             });
         }
@@ -452,6 +546,49 @@ impl<'a> Mpt<'a> {
         }
     }
 
+    /// Fills the reference cache of the subtrie at `node_id` bottom-up. This uses an explicit stack
+    /// instead of recursion, so that deep tries cannot overflow the call stack when computing
+    /// references: once all the children are cached, [`Self::calc_reference`] does not recurse.
+    /// Subtries that are already cached are skipped.
+    fn fill_references(&self, node_id: NodeId) {
+        // checked before allocating the stack, as the root is usually cached when hashing again
+        if self.cached_references[node_id as usize].borrow().is_some() {
+            return;
+        }
+        let mut stack = vec![(node_id, false)];
+        while let Some((node_id, children_cached)) = stack.pop() {
+            let cached_reference = &self.cached_references[node_id as usize];
+            if cached_reference.borrow().is_some() {
+                continue;
+            }
+            if children_cached {
+                let node_ref = self.calc_reference(node_id);
+                *cached_reference.borrow_mut() = Some(node_ref);
+                continue;
+            }
+
+            stack.push((node_id, true));
+            match &self.nodes[node_id as usize] {
+                NodeData::Branch(children) => {
+                    stack.extend(children.iter().flatten().map(|&child_id| (child_id, false)))
+                }
+                NodeData::Extension(_, child_id) => stack.push((*child_id, false)),
+                NodeData::Null | NodeData::Leaf(..) | NodeData::Digest(_) => {}
+            }
+        }
+    }
+
+    /// Returns the root hash if the reference of the root is cached, without computing anything.
+    fn cached_root_hash(&self) -> Option<B256> {
+        if let NodeData::Null = self.nodes[self.root_id as usize] {
+            return Some(reth_trie::EMPTY_ROOT_HASH);
+        }
+        match self.cached_references[self.root_id as usize].borrow().as_ref()? {
+            NodeRef::Digest(digest) => Some(B256::from_slice(digest)),
+            NodeRef::Bytes(bytes) => Some(keccak256(bytes)),
+        }
+    }
+
     /// Returns the length of the encoded [NodeRef] of this node.
     #[inline]
     fn reference_length(&self, node_id: NodeId) -> usize {
@@ -470,16 +607,14 @@ impl<'a> Mpt<'a> {
     /// Root hash of the MPT.
     #[inline]
     pub fn hash(&self) -> B256 {
-        match self.nodes[self.root_id as usize] {
-            NodeData::Null => reth_trie::EMPTY_ROOT_HASH,
-            _ => match self.cached_references[self.root_id as usize]
-                .borrow_mut()
-                .get_or_insert_with(|| self.calc_reference(self.root_id))
-            {
-                NodeRef::Digest(digest) => B256::from_slice(digest),
-                NodeRef::Bytes(bytes) => keccak256(bytes),
-            },
-        }
+        self.node_hash(self.root_id)
+    }
+
+    /// Hash of the subtrie whose root is at `prefix_nibs`, one nibble per byte, i.e. the root hash
+    /// of a trie holding the keys of this subtrie with the prefix stripped. Returns `None` if no
+    /// node starts at the prefix, e.g. if it ends inside the path of an extension or a leaf.
+    pub fn subtree_hash(&self, prefix_nibs: &[u8]) -> Result<Option<B256>, Error> {
+        Ok(self.node_at(prefix_nibs)?.map(|node_id| self.node_hash(node_id)))
     }
 
     /// Retrieves the value associated with a given key in the trie.
@@ -488,6 +623,16 @@ impl<'a> Mpt<'a> {
         self.get_internal(self.root_id, &to_nibs(key))
     }
 
+    /// Retrieves the longest key in the trie that is a prefix of `key`, and its value. Unlike
+    /// [`Self::get`], `key` may continue past the stored key. Since no key can be a prefix of
+    /// another one, see [`Error::ValueInBranch`], there is at most one such key.
+    pub fn longest_prefix<'s>(&'s self, key: &[u8]) -> Result<Option<(Vec<u8>, &'a [u8])>, Error> {
+        let key_nibs = to_nibs(key);
+        let found = self.longest_prefix_internal(self.root_id, &key_nibs)?;
+        // stored keys are whole bytes, so the unmatched nibbles are too
+        Ok(found.map(|(rest, value)| (key[..(key_nibs.len() - rest) / 2].to_vec(), value)))
+    }
+
     /// Retrieves the RLP-decoded value corresponding to the key.
     #[inline]
     pub fn get_rlp<T: alloy_rlp::Decodable>(&self, key: &[u8]) -> Result<Option<T>, Error> {
@@ -500,6 +645,83 @@ impl<'a> Mpt<'a> {
         }
     }
 
+    /// Same as [`Self::get_rlp`], but decodes the value into `out`, which is left as is if the key
+    /// is not in the trie. Returns whether the key was found. This lets hot loops reuse one value
+    /// instead of building an `Option` per lookup.
+    #[inline]
+    pub fn get_rlp_into<T: alloy_rlp::Decodable>(
+        &self,
+        key: &[u8],
+        out: &mut T,
+    ) -> Result<bool, Error> {
+        match self.get(key)? {
+            Some(mut bytes) => {
+                *out = T::decode(&mut bytes)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Retrieves the values associated with multiple keys in the trie.
+    ///
+    /// Keys are visited in sorted order so that the descent from the root is shared between keys
+    /// with common prefixes: for each key, traversal resumes from the deepest node on the previous
+    /// key's path that is still on the current key's path. Results are returned in the original
+    /// order of `keys`.
+    pub fn get_many<'s>(&'s self, keys: &[&[u8]]) -> Result<Vec<Option<&'a [u8]>>, Error> {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_unstable_by(|&a, &b| keys[a].cmp(keys[b]));
+
+        let mut results = vec![None; keys.len()];
+        // Nodes on the current descent path along with the number of key nibbles consumed to
+        // reach them. The root is always at the bottom of the stack.
+        let mut path: SmallVec<[(NodeId, usize); 64]> = SmallVec::new();
+        path.push((self.root_id, 0));
+        let mut prev_nibs = Nibbles::new();
+
+        for idx in order {
+            let key_nibs = to_nibs(keys[idx]);
+
+            // Only nodes reached by consuming at most the shared prefix remain on the path.
+            let common_len = lcp(&prev_nibs, &key_nibs);
+            while path.last().is_some_and(|&(_, depth)| depth > common_len) {
+                path.pop();
+            }
+
+            results[idx] = loop {
+                let (node_id, depth) = *path.last().unwrap();
+                let tail = &key_nibs[depth..];
+                match &self.nodes[node_id as usize] {
+                    NodeData::Null => break None,
+                    NodeData::Branch(children) => match tail.first() {
+                        Some(i) => match children[*i as usize] {
+                            Some(child_id) => path.push((child_id, depth + 1)),
+                            None => break None,
+                        },
+                        None => break None,
+                    },
+                    NodeData::Leaf(path_bytes, value) => {
+                        break encoded_path_eq_nibs(path_bytes, tail).then_some(*value);
+                    }
+                    NodeData::Extension(path_bytes, child_id) => {
+                        match encoded_path_strip_prefix(path_bytes, tail) {
+                            Some(rest) => path.push((*child_id, key_nibs.len() - rest.len())),
+                            None => break None,
+                        }
+                    }
+                    NodeData::Digest(digest) => {
+                        return Err(Error::NodeNotResolved(B256::from_slice(digest)));
+                    }
+                }
+            };
+
+            prev_nibs = key_nibs;
+        }
+
+        Ok(results)
+    }
+
     /// Inserts a key-value pair into the trie.
     #[inline]
     pub fn insert(&mut self, key: &[u8], value: &'a [u8]) -> Result<bool, Error> {
@@ -529,17 +751,69 @@ impl<'a> Mpt<'a> {
         self.delete_internal(self.root_id, key_nibs)
     }
 
+    /// Returns the kind of the root node, e.g. [`NodeKind::Digest`] for a trie that only knows
+    /// its root hash.
+    #[inline]
+    pub fn root_kind(&self) -> NodeKind {
+        self.nodes[self.root_id as usize].kind()
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool {
         matches!(&self.nodes[self.root_id as usize], NodeData::Null)
     }
 
+    /// Returns the number of nodes reachable from the root whose reference is not cached, i.e.
+    /// the nodes that the next [`Self::hash`] has to encode again. Updates only invalidate the
+    /// nodes on the path to the modified key, so this stays in the order of the trie depth per
+    /// key updated since the last hash.
+    pub fn dirty_node_count(&self) -> usize {
+        self.dirty_node_count_internal(self.root_id)
+    }
+
+    /// Returns the number of nodes on the longest path from the root to a leaf or unresolved
+    /// node, or 0 for an empty trie. This bounds the cost of descending to any key.
+    pub fn max_depth(&self) -> usize {
+        // explicit stack, so that measuring a deep trie cannot overflow
+        let mut stack = vec![(self.root_id, 1)];
+        let mut max_depth = 0;
+        while let Some((node_id, depth)) = stack.pop() {
+            match &self.nodes[node_id as usize] {
+                NodeData::Null => {}
+                NodeData::Branch(children) => {
+                    stack.extend(children.iter().flatten().map(|&child_id| (child_id, depth + 1)))
+                }
+                NodeData::Extension(_, child_id) => stack.push((*child_id, depth + 1)),
+                NodeData::Leaf(..) | NodeData::Digest(_) => max_depth = max_depth.max(depth),
+            }
+        }
+        max_depth
+    }
+
+    /// Returns the nibble path to the first place, in key order, where `self` and `other` differ,
+    /// or `None` if both tries have the same hash. Subtrees with the same reference are skipped,
+    /// so either trie may be partial. When the difference is a leaf of `self`, e.g. a value
+    /// mismatch, the path is the full key of that leaf; otherwise it leads to the first node whose
+    /// kind or path differs, or that is unresolved in one of the tries.
+    pub fn diff(&self, other: &Mpt<'_>) -> Option<Vec<u8>> {
+        let mut path = Vec::new();
+        self.diff_internal(self.root_id, other, other.root_id, &mut path).then_some(path)
+    }
+
     /// Reserves additional capacity for the trie.
     #[inline]
     pub fn reserve(&mut self, additional: usize) {
         self.nodes.reserve(additional);
         self.cached_references.reserve(additional);
     }
+
+    /// Replaces the arena used for new allocations. Existing nodes keep borrowing from the
+    /// previous arena.
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub(crate) fn set_bump(&mut self, bump: &'a Bump) {
+        self.bump = bump;
+    }
 }
 
 // Internal Implementation
@@ -552,11 +826,138 @@ impl<'a> Mpt<'a> {
         id
     }
 
+    #[inline]
+    fn set_node_data(&mut self, node_id: NodeId, data: NodeData<'a>) {
+        self.journal_node(node_id);
+        self.nodes[node_id as usize] = data;
+    }
+
     #[inline]
     fn invalidate_ref_cache(&mut self, node_id: NodeId) {
+        self.journal_node(node_id);
         self.cached_references[node_id as usize].borrow_mut().take();
     }
 
+    /// Records the current state of the node before it gets mutated, if a journal is active.
+    #[inline]
+    fn journal_node(&mut self, node_id: NodeId) {
+        if let Some(journal) = &mut self.journal {
+            if (node_id as usize) < journal.num_nodes {
+                journal.overwritten.push((
+                    node_id,
+                    self.nodes[node_id as usize].clone(),
+                    self.cached_references[node_id as usize].borrow().clone(),
+                ));
+            }
+        }
+    }
+
+    /// Starts journaling the mutations of the trie, so that [`Self::rollback_journal`] can undo
+    /// them. Journals do not nest: this discards the journal already in progress, if any.
+    pub(crate) fn begin_journal(&mut self) {
+        self.journal = Some(MptJournal {
+            root_id: self.root_id,
+            num_nodes: self.nodes.len(),
+            overwritten: Vec::new(),
+        });
+    }
+
+    /// Keeps the mutations since [`Self::begin_journal`] and stops journaling.
+    pub(crate) fn commit_journal(&mut self) {
+        self.journal = None;
+    }
+
+    /// Undoes the mutations since [`Self::begin_journal`] and stops journaling. Does nothing if
+    /// no journal is active.
+    pub(crate) fn rollback_journal(&mut self) {
+        let Some(journal) = self.journal.take() else {
+            return;
+        };
+        // restore in reverse, so that the state before the first mutation of a node wins
+        for (node_id, data, node_ref) in journal.overwritten.into_iter().rev() {
+            self.nodes[node_id as usize] = data;
+            *self.cached_references[node_id as usize].get_mut() = node_ref;
+        }
+        self.nodes.truncate(journal.num_nodes);
+        self.cached_references.truncate(journal.num_nodes);
+        self.root_id = journal.root_id;
+    }
+
+    fn dirty_node_count_internal(&self, node_id: NodeId) -> usize {
+        let children = match &self.nodes[node_id as usize] {
+            NodeData::Null => return 0,
+            NodeData::Branch(children) => children
+                .iter()
+                .flatten()
+                .map(|&child_id| self.dirty_node_count_internal(child_id))
+                .sum(),
+            NodeData::Extension(_, child_id) => self.dirty_node_count_internal(*child_id),
+            NodeData::Leaf(..) | NodeData::Digest(_) => 0,
+        };
+        children + self.cached_references[node_id as usize].borrow().is_none() as usize
+    }
+
+    #[inline]
+    fn reference(&self, node_id: NodeId) -> NodeRef<'a> {
+        self.cached_references[node_id as usize]
+            .borrow_mut()
+            .get_or_insert_with(|| self.calc_reference(node_id))
+            .clone()
+    }
+
+    /// Returns whether the subtries differ, leaving the path to the first difference in `path`.
+    fn diff_internal(
+        &self,
+        node_id: NodeId,
+        other: &Mpt<'_>,
+        other_id: NodeId,
+        path: &mut Vec<u8>,
+    ) -> bool {
+        if self.reference(node_id) == other.reference(other_id) {
+            return false;
+        }
+        match (&self.nodes[node_id as usize], &other.nodes[other_id as usize]) {
+            (NodeData::Branch(children), NodeData::Branch(other_children)) => {
+                for (i, (child, other_child)) in children.iter().zip(other_children).enumerate() {
+                    path.push(i as u8);
+                    let differs = match (child, other_child) {
+                        (Some(child_id), Some(other_child_id)) => {
+                            self.diff_internal(*child_id, other, *other_child_id, path)
+                        }
+                        (Some(child_id), None) => {
+                            self.extend_leaf_path(*child_id, path);
+                            true
+                        }
+                        (None, Some(_)) => true,
+                        (None, None) => false,
+                    };
+                    if differs {
+                        return true;
+                    }
+                    path.pop();
+                }
+            }
+            (
+                NodeData::Extension(prefix, child_id),
+                NodeData::Extension(other_prefix, other_child_id),
+            ) if prefix == other_prefix => {
+                path.extend(prefix_to_nibs(prefix));
+                if self.diff_internal(*child_id, other, *other_child_id, path) {
+                    return true;
+                }
+            }
+            _ => self.extend_leaf_path(node_id, path),
+        }
+        true
+    }
+
+    /// Appends the path of the node to `path` if it is a leaf.
+    fn extend_leaf_path(&self, node_id: NodeId, path: &mut Vec<u8>) {
+        if let NodeData::Leaf(prefix, _) = &self.nodes[node_id as usize] {
+            path.extend(prefix_to_nibs(prefix));
+        }
+    }
+
     #[inline]
     fn get_internal(&self, node_id: NodeId, key_nibs: &[u8]) -> Result<Option<&'a [u8]>, Error> {
         match &self.nodes[node_id as usize] {
@@ -591,6 +992,77 @@ impl<'a> Mpt<'a> {
         }
     }
 
+    /// Returns the node starting at `prefix_nibs`, if any.
+    fn node_at(&self, mut prefix_nibs: &[u8]) -> Result<Option<NodeId>, Error> {
+        let mut node_id = self.root_id;
+        while let Some((&nib, tail)) = prefix_nibs.split_first() {
+            match &self.nodes[node_id as usize] {
+                NodeData::Branch(children) => match children.get(nib as usize).copied().flatten() {
+                    Some(child_id) => (node_id, prefix_nibs) = (child_id, tail),
+                    None => return Ok(None),
+                },
+                NodeData::Extension(path_bytes, child_id) => {
+                    match encoded_path_strip_prefix(path_bytes, prefix_nibs) {
+                        Some(tail) => (node_id, prefix_nibs) = (*child_id, tail),
+                        None => return Ok(None),
+                    }
+                }
+                NodeData::Null | NodeData::Leaf(..) => return Ok(None),
+                NodeData::Digest(digest) => {
+                    return Err(Error::NodeNotResolved(B256::from_slice(digest)))
+                }
+            }
+        }
+        Ok(Some(node_id))
+    }
+
+    /// Hash of the subtrie rooted at `node_id`, hashing the encoding of the node even if it is
+    /// shorter than 32 bytes, like for the root.
+    fn node_hash(&self, node_id: NodeId) -> B256 {
+        match self.nodes[node_id as usize] {
+            NodeData::Null => reth_trie::EMPTY_ROOT_HASH,
+            _ => {
+                self.fill_references(node_id);
+                match self.cached_references[node_id as usize]
+                    .borrow_mut()
+                    .get_or_insert_with(|| self.calc_reference(node_id))
+                {
+                    NodeRef::Digest(digest) => B256::from_slice(digest),
+                    NodeRef::Bytes(bytes) => keccak256(bytes),
+                }
+            }
+        }
+    }
+
+    /// Returns the number of nibbles of `key_nibs` left after the leaf whose path is a prefix of
+    /// them, and the value of that leaf.
+    fn longest_prefix_internal(
+        &self,
+        node_id: NodeId,
+        key_nibs: &[u8],
+    ) -> Result<Option<(usize, &'a [u8])>, Error> {
+        match &self.nodes[node_id as usize] {
+            NodeData::Null => Ok(None),
+            NodeData::Branch(nodes) => match key_nibs.split_first() {
+                Some((i, tail)) => match nodes[*i as usize] {
+                    Some(id) => self.longest_prefix_internal(id, tail),
+                    None => Ok(None),
+                },
+                None => Ok(None),
+            },
+            NodeData::Leaf(path_bytes, value) => {
+                Ok(encoded_path_strip_prefix(path_bytes, key_nibs).map(|tail| (tail.len(), *value)))
+            }
+            NodeData::Extension(path_bytes, child_id) => {
+                match encoded_path_strip_prefix(path_bytes, key_nibs) {
+                    Some(tail) => self.longest_prefix_internal(*child_id, tail),
+                    None => Ok(None),
+                }
+            }
+            NodeData::Digest(digest) => Err(Error::NodeNotResolved(B256::from_slice(digest))),
+        }
+    }
+
     #[inline]
     fn insert_internal(
         &mut self,
@@ -601,7 +1073,7 @@ impl<'a> Mpt<'a> {
         let updated = match self.nodes[node_id as usize] {
             NodeData::Null => {
                 let path = to_encoded_path_with_bump(self.bump, key_nibs, true);
-                self.nodes[node_id as usize] = NodeData::Leaf(path, value);
+                self.set_node_data(node_id, NodeData::Leaf(path, value));
                 true
             }
             NodeData::Branch(mut children) => {
@@ -612,7 +1084,7 @@ impl<'a> Mpt<'a> {
                             let path = to_encoded_path_with_bump(self.bump, tail, true);
                             let new_leaf_id = self.add_node(NodeData::Leaf(path, value), None);
                             children[*i as usize] = Some(new_leaf_id);
-                            self.nodes[node_id as usize] = NodeData::Branch(children);
+                            self.set_node_data(node_id, NodeData::Branch(children));
                             true
                         }
                     }
@@ -629,7 +1101,7 @@ impl<'a> Mpt<'a> {
                     if old_value == value {
                         return Ok(false);
                     }
-                    self.nodes[node_id as usize] = NodeData::Leaf(prefix, value);
+                    self.set_node_data(node_id, NodeData::Leaf(prefix, value));
                     true
                 } else if common_len == self_nibs.len() || common_len == key_nibs.len() {
                     return Err(Error::ValueInBranch);
@@ -657,7 +1129,7 @@ impl<'a> Mpt<'a> {
                     } else {
                         NodeData::Branch(children)
                     };
-                    self.nodes[node_id as usize] = new_node_data;
+                    self.set_node_data(node_id, new_node_data);
                     true
                 }
             }
@@ -695,7 +1167,7 @@ impl<'a> Mpt<'a> {
                     } else {
                         NodeData::Branch(children)
                     };
-                    self.nodes[node_id as usize] = new_node_data;
+                    self.set_node_data(node_id, new_node_data);
                     true
                 }
             }
@@ -771,9 +1243,9 @@ impl<'a> Mpt<'a> {
                         }
                         NodeData::Null => unreachable!(),
                     };
-                    self.nodes[node_id as usize] = new_node_data;
+                    self.set_node_data(node_id, new_node_data);
                 } else {
-                    self.nodes[node_id as usize] = NodeData::Branch(children);
+                    self.set_node_data(node_id, NodeData::Branch(children));
                 }
 
                 true
@@ -783,7 +1255,7 @@ impl<'a> Mpt<'a> {
                 if leaf_nibs.as_slice() != key_nibs {
                     return Ok(false);
                 }
-                self.nodes[node_id as usize] = NodeData::Null;
+                self.set_node_data(node_id, NodeData::Null);
                 true
             }
             NodeData::Extension(prefix, child_id) => {
@@ -827,7 +1299,7 @@ impl<'a> Mpt<'a> {
                         NodeData::Extension(prefix, child_id)
                     }
                 };
-                self.nodes[node_id as usize] = new_node_data;
+                self.set_node_data(node_id, new_node_data);
                 true
             }
             NodeData::Digest(digest) => {
@@ -897,7 +1369,10 @@ impl<'a> Mpt<'a> {
         Ok(node_id)
     }
 
-    /// Returns list of every node's payload in the trie.
+    /// Returns the RLP encoding of every node reachable from the root, the root first. Mapping
+    /// them by their keccak hash gives the node store of a [`MptResolver`].
+    ///
+    /// [`MptResolver`]: crate::resolver::MptResolver
     #[cfg(feature = "host")]
     pub fn payloads(&self) -> Vec<revm_primitives::Bytes> {
         let mut res = Vec::new();
@@ -929,7 +1404,20 @@ impl<'a> Mpt<'a> {
     }
 }
 
+impl fmt::Debug for Mpt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Mpt");
+        // Only the cached root is printed, as hashing would fill the cache of the whole trie
+        match self.cached_root_hash() {
+            Some(root_hash) => debug.field("root_hash", &root_hash),
+            None => debug.field("root_hash", &format_args!("<unhashed>")),
+        };
+        debug.field("num_nodes", &self.num_nodes()).field("root_kind", &self.root_kind()).finish()
+    }
+}
+
 impl Mpt<'_> {
+    /// Prints all the nodes reachable from the root, one per line and indented by depth.
     pub fn print_trie(&self) {
         self.print_trie_internal(self.root_id, 0);
     }
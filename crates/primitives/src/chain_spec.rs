@@ -1,5 +1,10 @@
 use alloy_eips::{eip7840::BlobParams, eip7892::BlobScheduleBlobParams};
-use alloy_hardforks::mainnet::{MAINNET_BPO1_TIMESTAMP, MAINNET_BPO2_TIMESTAMP};
+use alloy_genesis::Genesis;
+use alloy_hardforks::{
+    holesky::{HOLESKY_BPO1_TIMESTAMP, HOLESKY_BPO2_TIMESTAMP},
+    mainnet::{MAINNET_BPO1_TIMESTAMP, MAINNET_BPO2_TIMESTAMP},
+    sepolia::{SEPOLIA_BPO1_TIMESTAMP, SEPOLIA_BPO2_TIMESTAMP},
+};
 use reth_chainspec::{
     BaseFeeParams, BaseFeeParamsKind, Chain, ChainHardforks, ChainSpec, DepositContract,
     DEV_HARDFORKS,
@@ -35,6 +40,62 @@ pub fn mainnet() -> ChainSpec {
     spec
 }
 
+/// Returns the [ChainSpec] for the Sepolia testnet.
+pub fn sepolia() -> ChainSpec {
+    // Spec extracted from:
+    //
+    // https://github.com/paradigmxyz/reth/blob/v1.9.3/crates/chainspec/src/spec.rs (SEPOLIA)
+    let mut spec = ChainSpec {
+        chain: Chain::sepolia(),
+        // We don't need the genesis state. Using default to save cycles.
+        genesis: Default::default(),
+        paris_block_and_final_difficulty: Some((1450409, U256::from(17_000_018_015_853_232u128))),
+        hardforks: ChainHardforks::from(alloy_hardforks::EthereumHardfork::sepolia()),
+        deposit_contract: Some(DepositContract::new(
+            address!("7f02c3e3c98b133055b8b348b2ac625669ed295d"),
+            1273020,
+            b256!("649bbc62d0e31342afea4e5cd82d4049e7e1ee912fc0889aa790803be39038c5"),
+        )),
+        base_fee_params: BaseFeeParamsKind::Constant(BaseFeeParams::ethereum()),
+        prune_delete_limit: 10000,
+        genesis_header: Default::default(),
+        blob_params: BlobScheduleBlobParams::default().with_scheduled([
+            (SEPOLIA_BPO1_TIMESTAMP, BlobParams::bpo1()),
+            (SEPOLIA_BPO2_TIMESTAMP, BlobParams::bpo2()),
+        ]),
+    };
+    spec.genesis.config.dao_fork_support = true;
+    spec
+}
+
+/// Returns the [ChainSpec] for the Holesky testnet.
+pub fn holesky() -> ChainSpec {
+    // Spec extracted from:
+    //
+    // https://github.com/paradigmxyz/reth/blob/v1.9.3/crates/chainspec/src/spec.rs (HOLESKY)
+    let mut spec = ChainSpec {
+        chain: Chain::holesky(),
+        // We don't need the genesis state. Using default to save cycles.
+        genesis: Default::default(),
+        paris_block_and_final_difficulty: Some((0, U256::from(1))),
+        hardforks: ChainHardforks::from(alloy_hardforks::EthereumHardfork::holesky()),
+        deposit_contract: Some(DepositContract::new(
+            address!("4242424242424242424242424242424242424242"),
+            0,
+            b256!("649bbc62d0e31342afea4e5cd82d4049e7e1ee912fc0889aa790803be39038c5"),
+        )),
+        base_fee_params: BaseFeeParamsKind::Constant(BaseFeeParams::ethereum()),
+        prune_delete_limit: 10000,
+        genesis_header: Default::default(),
+        blob_params: BlobScheduleBlobParams::default().with_scheduled([
+            (HOLESKY_BPO1_TIMESTAMP, BlobParams::bpo1()),
+            (HOLESKY_BPO2_TIMESTAMP, BlobParams::bpo2()),
+        ]),
+    };
+    spec.genesis.config.dao_fork_support = true;
+    spec
+}
+
 /// Returns the [ChainSpec] for Reth's dev testnet.
 pub fn dev() -> ChainSpec {
     // taken from:
@@ -49,3 +110,51 @@ pub fn dev() -> ChainSpec {
         ..Default::default()
     }
 }
+
+/// Builds a [ChainSpec] from a genesis JSON file, as used by private and dev networks.
+pub fn from_genesis_json(json: &str) -> eyre::Result<ChainSpec> {
+    let genesis: Genesis = serde_json::from_str(json)?;
+    Ok(ChainSpec::from(genesis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reth_chainspec::EthereumHardforks;
+
+    #[test]
+    fn test_from_genesis_json() {
+        let json = r#"{
+            "config": {
+                "chainId": 1337,
+                "homesteadBlock": 0,
+                "eip150Block": 0,
+                "eip155Block": 0,
+                "eip158Block": 0,
+                "byzantiumBlock": 0,
+                "constantinopleBlock": 0,
+                "petersburgBlock": 0,
+                "istanbulBlock": 0,
+                "berlinBlock": 0,
+                "londonBlock": 0,
+                "terminalTotalDifficulty": 0,
+                "terminalTotalDifficultyPassed": true,
+                "shanghaiTime": 0,
+                "cancunTime": 0
+            },
+            "nonce": "0x0",
+            "timestamp": "0x0",
+            "extraData": "0x",
+            "gasLimit": "0x1c9c380",
+            "difficulty": "0x0",
+            "alloc": {}
+        }"#;
+
+        let spec = from_genesis_json(json).unwrap();
+        assert_eq!(spec.chain.id(), 1337);
+        assert!(spec.is_cancun_active_at_timestamp(0));
+        assert!(!spec.is_prague_active_at_timestamp(0));
+
+        assert!(from_genesis_json("{}").is_err());
+    }
+}
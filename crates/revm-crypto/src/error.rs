@@ -0,0 +1,50 @@
+use revm::precompile::PrecompileError;
+
+/// Errors returned by the OpenVM precompile implementations.
+///
+/// These are converted into [`PrecompileError::Other`] with the same message when returned to
+/// REVM.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenVmCryptoError {
+    #[error("Invalid signature format")]
+    InvalidSignature,
+
+    #[error("Invalid recovery ID")]
+    InvalidRecoveryId,
+
+    #[error("Key recovery failed")]
+    KeyRecoveryFailed,
+
+    #[error("openvm kzg proof verification failed")]
+    KzgVerificationFailed,
+
+    #[error("invalid BLS12-381 fp length")]
+    InvalidBlsFpLength,
+
+    #[error("invalid BLS12-381 scalar length")]
+    InvalidBlsScalarLength,
+
+    #[error("element not in BLS12-381 base field")]
+    BlsFpNotInField,
+
+    #[error("failed to create BLS12-381 G1 point")]
+    BlsG1NotOnCurve,
+
+    #[error("failed to create BLS12-381 G2 point")]
+    BlsG2NotOnCurve,
+
+    #[error("BLS12-381 G1 point not in subgroup")]
+    BlsG1NotInSubgroup,
+
+    #[error("BLS12-381 G2 point not in subgroup")]
+    BlsG2NotInSubgroup,
+
+    #[error("invalid BN254 scalar length")]
+    InvalidBnScalarLength,
+}
+
+impl From<OpenVmCryptoError> for PrecompileError {
+    fn from(err: OpenVmCryptoError) -> Self {
+        PrecompileError::other(err.to_string())
+    }
+}
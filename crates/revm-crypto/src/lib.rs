@@ -3,6 +3,8 @@
 //! This module provides OpenVM-optimized implementations of cryptographic operations
 //! for both transaction validation (via Alloy crypto provider) and precompile execution.
 
+pub mod error;
+
 use alloy_consensus::crypto::{
     backend::{install_default_provider, CryptoProvider},
     RecoveryError,
@@ -15,9 +17,11 @@ use openvm_ecc_guest::{
 };
 use openvm_k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
 use openvm_keccak256::keccak256;
-use openvm_kzg::{Bytes32, Bytes48, KzgProof};
-#[allow(unused_imports, clippy::single_component_path_imports)]
-use openvm_p256; // ensure this is linked in for the standard OpenVM config
+use openvm_kzg::{Bytes32, Bytes48, EnvKzgSettings, KzgError, KzgProof, KzgSettings};
+use openvm_p256::ecdsa::{
+    signature::hazmat::PrehashVerifier, Signature as P256Signature,
+    VerifyingKey as P256VerifyingKey,
+};
 use openvm_pairing::{
     bls12_381::{self as bls, Bls12_381},
     bn254::{self as bn, Bn254},
@@ -34,10 +38,15 @@ use revm::{
             FP_LENGTH as BLS_FP_LEN, G1_LENGTH as BLS_G1_LEN, G2_LENGTH as BLS_G2_LEN,
             SCALAR_LENGTH as BLS_SCALAR_LEN,
         },
-        Crypto, PrecompileError,
+        Crypto, DefaultCrypto, PrecompileError,
     },
 };
-use std::{sync::Arc, vec::Vec};
+use std::{
+    sync::{Arc, OnceLock},
+    vec::Vec,
+};
+
+use crate::error::OpenVmCryptoError;
 
 // BN254 constants
 const BN_FQ_LEN: usize = 32;
@@ -47,9 +56,30 @@ const BN_G2_LEN: usize = 128;
 /// This is an element in the scalar field of BN254.
 const BN_SCALAR_LEN: usize = 32;
 
+/// Largest valid secp256k1 recovery id. Ids 2 and 3 are for the rare `r >= n` x-coordinates.
+const MAX_RECOVERY_ID: u8 = 3;
+
+/// How secp256k1 signatures with a high `s` value, i.e. `s > n / 2`, are handled.
+///
+/// The `ecrecover` precompile accepts any `0 < s < n`, so it always normalizes. Transaction
+/// signatures must have a low `s` since EIP-2, which Alloy checks before calling
+/// [`CryptoProvider::recover_signer_unchecked`]; rejecting them in the provider as well guards
+/// call sites that skip that check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HighSPolicy {
+    /// Recover from the equivalent low-s signature, flipping the recovery id
+    #[default]
+    Normalize,
+    /// Fail the recovery
+    Reject,
+}
+
 /// OpenVM k256 backend for Alloy crypto operations (transaction validation)
 #[derive(Debug, Default)]
-struct OpenVmK256Provider;
+struct OpenVmK256Provider {
+    /// Handling of high-s transaction signatures
+    high_s: HighSPolicy,
+}
 
 impl CryptoProvider for OpenVmK256Provider {
     fn recover_signer_unchecked(
@@ -58,12 +88,19 @@ impl CryptoProvider for OpenVmK256Provider {
         msg: &[u8; 32],
     ) -> Result<Address, RecoveryError> {
         // Extract components: sig[0..32]=r, sig[32..64]=s, sig[64]=recovery_id
+        if sig[64] > MAX_RECOVERY_ID {
+            return Err(RecoveryError::new());
+        }
+
         // Parse signature using OpenVM k256
         let mut signature = Signature::from_slice(&sig[..64]).map_err(|_| RecoveryError::new())?;
 
         // Normalize signature if needed
         let mut recid = sig[64];
         if let Some(sig_normalized) = signature.normalize_s() {
+            if self.high_s == HighSPolicy::Reject {
+                return Err(RecoveryError::new());
+            }
             signature = sig_normalized;
             recid ^= 1;
         }
@@ -90,18 +127,137 @@ impl CryptoProvider for OpenVmK256Provider {
     }
 }
 
+/// Recovers the signers of many `(signature, message hash)` pairs with the OpenVM k256 backend.
+///
+/// Signatures use the `r || s || v` layout of [`CryptoProvider::recover_signer_unchecked`], and
+/// as there, high-s signatures are not rejected. Fails on the first signature that cannot be
+/// recovered.
+pub fn recover_signers_unchecked<'a>(
+    items: impl IntoIterator<Item = (&'a [u8; 65], &'a [u8; 32])>,
+) -> Result<Vec<Address>, RecoveryError> {
+    let provider = OpenVmK256Provider::default();
+    items.into_iter().map(|(sig, msg)| provider.recover_signer_unchecked(sig, msg)).collect()
+}
+
+/// Selects which crypto operations use the OpenVM implementations. Disabled operations fall
+/// back to the default REVM backend.
+///
+/// The [`Default`] value enables every override.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CryptoOverrides {
+    /// SHA-256 precompile
+    pub sha256: bool,
+    /// RIPEMD-160 precompile
+    pub ripemd160: bool,
+    /// Modular exponentiation precompile
+    pub modexp: bool,
+    /// BN254 addition, multiplication and pairing precompiles
+    pub bn254: bool,
+    /// EIP-2537 BLS12-381 precompiles
+    pub bls12_381: bool,
+    /// ecrecover precompile and transaction sender recovery
+    pub secp256k1: bool,
+    /// RIP-7212 P-256 verification precompile
+    pub secp256r1: bool,
+    /// KZG point evaluation precompile
+    pub kzg: bool,
+}
+
+impl CryptoOverrides {
+    /// Enables every override.
+    pub const ALL: Self = Self {
+        sha256: true,
+        ripemd160: true,
+        modexp: true,
+        bn254: true,
+        bls12_381: true,
+        secp256k1: true,
+        secp256r1: true,
+        kzg: true,
+    };
+
+    /// Disables every override, so only the default backend is used.
+    pub const NONE: Self = Self {
+        sha256: false,
+        ripemd160: false,
+        modexp: false,
+        bn254: false,
+        bls12_381: false,
+        secp256k1: false,
+        secp256r1: false,
+        kzg: false,
+    };
+}
+
+impl Default for CryptoOverrides {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
 /// OpenVM custom crypto implementation for faster precompiles
 #[derive(Debug, Default)]
-struct OpenVmCrypto;
+struct OpenVmCrypto {
+    /// Operations that use the OpenVM implementation
+    overrides: CryptoOverrides,
+    /// Trusted setup used by the KZG point evaluation precompile
+    kzg_settings: EnvKzgSettings,
+}
 
 impl Crypto for OpenVmCrypto {
     /// Custom SHA-256 implementation with openvm optimization
     fn sha256(&self, input: &[u8]) -> [u8; 32] {
+        if !self.overrides.sha256 {
+            return DefaultCrypto.sha256(input);
+        }
         openvm_sha2::sha256(input)
     }
 
+    /// RIPEMD-160 hash, left-padded to 32 bytes as returned by the precompile
+    fn ripemd160(&self, input: &[u8]) -> [u8; 32] {
+        if !self.overrides.ripemd160 {
+            return DefaultCrypto.ripemd160(input);
+        }
+        let mut output = [0u8; 32];
+        output[12..].copy_from_slice(&ripemd160_digest(input));
+        output
+    }
+
+    /// Modular exponentiation
+    ///
+    /// Length headers and the EIP-7823 size caps are handled by revm before this is called. The
+    /// openvm bigint extension has no modular reduction, so apart from the trivial cases below the
+    /// computation is done by the default backend.
+    fn modexp(&self, base: &[u8], exp: &[u8], modulus: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+        if !self.overrides.modexp {
+            return DefaultCrypto.modexp(base, exp, modulus);
+        }
+        let is_zero = |bytes: &[u8]| bytes.iter().all(|&b| b == 0);
+
+        // x mod 0 = 0 by convention, and x mod 1 = 0
+        let modulus_is_one = modulus.last() == Some(&1) && is_zero(&modulus[..modulus.len() - 1]);
+        if is_zero(modulus) || modulus_is_one {
+            return Ok(vec![0u8; modulus.len()]);
+        }
+        // x^0 = 1, including 0^0
+        if is_zero(exp) {
+            let mut output = vec![0u8; modulus.len()];
+            output[modulus.len() - 1] = 1;
+            return Ok(output);
+        }
+        // 0^y = 0 for y > 0
+        if is_zero(base) {
+            return Ok(vec![0u8; modulus.len()]);
+        }
+
+        DefaultCrypto.modexp(base, exp, modulus)
+    }
+
     /// Custom BN254 G1 addition with openvm optimization
     fn bn254_g1_add(&self, p1_bytes: &[u8], p2_bytes: &[u8]) -> Result<[u8; 64], PrecompileError> {
+        if !self.overrides.bn254 {
+            return DefaultCrypto.bn254_g1_add(p1_bytes, p2_bytes);
+        }
         let p1 = read_bn_g1_point(p1_bytes)?;
         let p2 = read_bn_g1_point(p2_bytes)?;
         let result = p1 + p2;
@@ -114,14 +270,20 @@ impl Crypto for OpenVmCrypto {
         point_bytes: &[u8],
         scalar_bytes: &[u8],
     ) -> Result<[u8; 64], PrecompileError> {
+        if !self.overrides.bn254 {
+            return DefaultCrypto.bn254_g1_mul(point_bytes, scalar_bytes);
+        }
         let p = read_bn_g1_point(point_bytes)?;
-        let s = read_bn_scalar(scalar_bytes);
+        let s = read_bn_scalar(scalar_bytes)?;
         let result = Bn254::msm(&[s], &[p]);
         Ok(encode_bn_g1_point(result))
     }
 
     /// Custom BN254 pairing check with openvm optimization
     fn bn254_pairing_check(&self, pairs: &[(&[u8], &[u8])]) -> Result<bool, PrecompileError> {
+        if !self.overrides.bn254 {
+            return DefaultCrypto.bn254_pairing_check(pairs);
+        }
         if pairs.is_empty() {
             return Ok(true);
         }
@@ -152,6 +314,9 @@ impl Crypto for OpenVmCrypto {
         a: BlsG1Point,
         b: BlsG1Point,
     ) -> Result<[u8; BLS_G1_LEN], PrecompileError> {
+        if !self.overrides.bls12_381 {
+            return DefaultCrypto.bls12_381_g1_add(a, b);
+        }
         let p1 = read_bls_g1_point(&a)?;
         let p2 = read_bls_g1_point(&b)?;
         let sum = p1 + p2;
@@ -163,13 +328,16 @@ impl Crypto for OpenVmCrypto {
         &self,
         pairs: &mut dyn Iterator<Item = Result<BlsG1PointScalar, PrecompileError>>,
     ) -> Result<[u8; BLS_G1_LEN], PrecompileError> {
+        if !self.overrides.bls12_381 {
+            return DefaultCrypto.bls12_381_g1_msm(pairs);
+        }
         let mut scalars = Vec::new();
         let mut points = Vec::new();
 
         for pair in pairs {
             let (point_bytes, scalar_bytes) = pair?;
-            points.push(read_bls_g1_point(&point_bytes)?);
-            scalars.push(read_bls_scalar(&scalar_bytes));
+            points.push(read_bls_g1_point_in_subgroup(&point_bytes)?);
+            scalars.push(read_bls_scalar(&scalar_bytes)?);
         }
 
         if points.is_empty() {
@@ -186,6 +354,9 @@ impl Crypto for OpenVmCrypto {
         a: BlsG2Point,
         b: BlsG2Point,
     ) -> Result<[u8; BLS_G2_LEN], PrecompileError> {
+        if !self.overrides.bls12_381 {
+            return DefaultCrypto.bls12_381_g2_add(a, b);
+        }
         let p1 = read_bls_g2_point(&a)?;
         let p2 = read_bls_g2_point(&b)?;
         let sum = p1 + p2;
@@ -197,13 +368,16 @@ impl Crypto for OpenVmCrypto {
         &self,
         pairs: &mut dyn Iterator<Item = Result<BlsG2PointScalar, PrecompileError>>,
     ) -> Result<[u8; BLS_G2_LEN], PrecompileError> {
+        if !self.overrides.bls12_381 {
+            return DefaultCrypto.bls12_381_g2_msm(pairs);
+        }
         let mut scalars = Vec::new();
         let mut points = Vec::new();
 
         for pair in pairs {
             let (point_bytes, scalar_bytes) = pair?;
-            points.push(read_bls_g2_point(&point_bytes)?);
-            scalars.push(read_bls_scalar(&scalar_bytes));
+            points.push(read_bls_g2_point_in_subgroup(&point_bytes)?);
+            scalars.push(read_bls_scalar(&scalar_bytes)?);
         }
 
         if points.is_empty() {
@@ -220,6 +394,9 @@ impl Crypto for OpenVmCrypto {
         &self,
         pairs: &[(BlsG1Point, BlsG2Point)],
     ) -> Result<bool, PrecompileError> {
+        if !self.overrides.bls12_381 {
+            return DefaultCrypto.bls12_381_pairing_check(pairs);
+        }
         if pairs.is_empty() {
             return Ok(true);
         }
@@ -228,8 +405,13 @@ impl Crypto for OpenVmCrypto {
         let mut g2_points = Vec::with_capacity(pairs.len());
 
         for (g1_bytes, g2_bytes) in pairs {
-            let g1 = read_bls_g1_point(g1_bytes)?;
-            let g2 = read_bls_g2_point(g2_bytes)?;
+            let g1 = read_bls_g1_point_in_subgroup(g1_bytes)?;
+            let g2 = read_bls_g2_point_in_subgroup(g2_bytes)?;
+
+            // e(O, Q) = e(P, O) = 1, so pairs containing the identity do not affect the result
+            if g1.is_identity() || g2.is_identity() {
+                continue;
+            }
 
             let (g1_x, g1_y) = g1.into_coords();
             let (g2_x, g2_y) = g2.into_coords();
@@ -238,42 +420,50 @@ impl Crypto for OpenVmCrypto {
             g2_points.push(AffinePoint::new(g2_x, g2_y));
         }
 
+        if g1_points.is_empty() {
+            return Ok(true);
+        }
+
         let pairing_result = Bls12_381::pairing_check(&g1_points, &g2_points).is_ok();
         Ok(pairing_result)
     }
 
     /// Custom secp256k1 ECDSA signature recovery with openvm optimization
+    ///
+    /// Signatures with a high `s` value are accepted, as the `ecrecover` precompile only requires
+    /// `0 < r, s < n`, see [`HighSPolicy`].
     fn secp256k1_ecrecover(
         &self,
         sig_bytes: &[u8; 64],
-        mut recid: u8,
+        recid: u8,
         msg_hash: &[u8; 32],
     ) -> Result<[u8; 32], PrecompileError> {
-        let mut sig = Signature::from_slice(sig_bytes)
-            .map_err(|_| PrecompileError::other("Invalid signature format"))?;
-
-        if let Some(sig_normalized) = sig.normalize_s() {
-            sig = sig_normalized;
-            recid ^= 1;
+        if !self.overrides.secp256k1 {
+            return DefaultCrypto.secp256k1_ecrecover(sig_bytes, recid, msg_hash);
         }
+        Ok(ecrecover(sig_bytes, recid, msg_hash)?)
+    }
 
-        let recovery_id = RecoveryId::from_byte(recid)
-            .ok_or_else(|| PrecompileError::other("Invalid recovery ID"))?;
-
-        let recovered_key =
-            VerifyingKey::recover_from_prehash_noverify(msg_hash, &sig.to_bytes(), recovery_id)
-                .map_err(|_| PrecompileError::other("Key recovery failed"))?;
-
-        let public_key = recovered_key.as_affine();
-        let mut encoded_pubkey = [0u8; 64];
-        encoded_pubkey[..32].copy_from_slice(&WeierstrassPoint::x(public_key).to_be_bytes());
-        encoded_pubkey[32..].copy_from_slice(&WeierstrassPoint::y(public_key).to_be_bytes());
-
-        let pubkey_hash = keccak256(&encoded_pubkey);
-        let mut address = [0u8; 32];
-        address[12..].copy_from_slice(&pubkey_hash[12..]);
-
-        Ok(address)
+    /// Custom secp256r1 (P-256) signature verification with openvm optimization
+    ///
+    /// Per RIP-7212, signatures with a high `s` value are accepted; only `0 < r, s < n` is
+    /// enforced.
+    fn secp256r1_verify_signature(&self, msg: &[u8; 32], sig: &[u8; 64], pk: &[u8; 64]) -> bool {
+        if !self.overrides.secp256r1 {
+            return DefaultCrypto.secp256r1_verify_signature(msg, sig, pk);
+        }
+        let Ok(signature) = P256Signature::from_slice(sig) else {
+            return false;
+        };
+
+        let mut encoded_pubkey = [0u8; 65];
+        encoded_pubkey[0] = 0x04; // SEC1 tag for uncompressed points
+        encoded_pubkey[1..].copy_from_slice(pk);
+        let Ok(verifying_key) = P256VerifyingKey::from_sec1_bytes(&encoded_pubkey) else {
+            return false;
+        };
+
+        verifying_key.verify_prehash(msg, &signature).is_ok()
     }
 
     /// Custom KZG point evaluation with configurable backends
@@ -284,39 +474,172 @@ impl Crypto for OpenVmCrypto {
         commitment: &[u8; 48],
         proof: &[u8; 48],
     ) -> Result<(), PrecompileError> {
-        let env = openvm_kzg::EnvKzgSettings::default();
-        let kzg_settings = env.get();
-
-        let commitment_bytes = Bytes48::from_slice(commitment)
-            .map_err(|_| PrecompileError::other("invalid commitment bytes"))?;
-        let z_bytes =
-            Bytes32::from_slice(z).map_err(|_| PrecompileError::other("invalid z bytes"))?;
-        let y_bytes =
-            Bytes32::from_slice(y).map_err(|_| PrecompileError::other("invalid y bytes"))?;
-        let proof_bytes = Bytes48::from_slice(proof)
-            .map_err(|_| PrecompileError::other("invalid proof bytes"))?;
+        if !self.overrides.kzg {
+            return DefaultCrypto.verify_kzg_proof(z, y, commitment, proof);
+        }
+        Ok(verify_kzg_proof(self.kzg_settings.get(), z, y, commitment, proof)?)
+    }
+}
+
+/// Recovers the address that produced `sig_bytes` over `msg_hash`, left-padded to 32 bytes.
+fn ecrecover(
+    sig_bytes: &[u8; 64],
+    mut recid: u8,
+    msg_hash: &[u8; 32],
+) -> Result<[u8; 32], OpenVmCryptoError> {
+    if recid > MAX_RECOVERY_ID {
+        return Err(OpenVmCryptoError::InvalidRecoveryId);
+    }
+
+    let mut sig =
+        Signature::from_slice(sig_bytes).map_err(|_| OpenVmCryptoError::InvalidSignature)?;
 
+    if let Some(sig_normalized) = sig.normalize_s() {
+        sig = sig_normalized;
+        recid ^= 1;
+    }
+
+    let recovery_id = RecoveryId::from_byte(recid).ok_or(OpenVmCryptoError::InvalidRecoveryId)?;
+
+    let recovered_key =
+        VerifyingKey::recover_from_prehash_noverify(msg_hash, &sig.to_bytes(), recovery_id)
+            .map_err(|_| OpenVmCryptoError::KeyRecoveryFailed)?;
+
+    let public_key = recovered_key.as_affine();
+    let mut encoded_pubkey = [0u8; 64];
+    encoded_pubkey[..32].copy_from_slice(&WeierstrassPoint::x(public_key).to_be_bytes());
+    encoded_pubkey[32..].copy_from_slice(&WeierstrassPoint::y(public_key).to_be_bytes());
+
+    let pubkey_hash = keccak256(&encoded_pubkey);
+    let mut address = [0u8; 32];
+    address[12..].copy_from_slice(&pubkey_hash[12..]);
+
+    Ok(address)
+}
+
+/// Verifies the KZG proof that `p(z) = y` for the polynomial committed to by `commitment`.
+///
+/// The byte conversions cannot fail on inputs of these lengths, so every error, including
+/// commitments and proofs that are not valid compressed points, comes from the verification.
+fn verify_kzg_proof(
+    kzg_settings: &KzgSettings,
+    z: &[u8; 32],
+    y: &[u8; 32],
+    commitment: &[u8; 48],
+    proof: &[u8; 48],
+) -> Result<(), OpenVmCryptoError> {
+    let verify = || -> Result<bool, KzgError> {
         KzgProof::verify_kzg_proof(
-            &commitment_bytes,
-            &z_bytes,
-            &y_bytes,
-            &proof_bytes,
+            &Bytes48::from_slice(commitment)?,
+            &Bytes32::from_slice(z)?,
+            &Bytes32::from_slice(y)?,
+            &Bytes48::from_slice(proof)?,
             kzg_settings,
         )
-        .map_err(|_| PrecompileError::other("openvm kzg proof verification failed"))?;
-        Ok(())
+    };
+    let valid = verify().map_err(|_| OpenVmCryptoError::KzgVerificationFailed)?;
+    if !valid {
+        return Err(OpenVmCryptoError::KzgVerificationFailed);
     }
+    Ok(())
+}
+
+/// Outcome of installing the OpenVM crypto implementations.
+///
+/// Each field is `true` if the implementation was installed by this call, and `false` if it was
+/// skipped because another implementation had already been installed or the corresponding
+/// override is disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstallReport {
+    /// The k256 provider used by Alloy for transaction sender recovery
+    pub alloy_provider: bool,
+    /// The precompile implementations used by REVM
+    pub revm_crypto: bool,
 }
 
 /// Install OpenVM crypto implementations globally
-pub fn install_openvm_crypto() -> Result<bool, Box<dyn std::error::Error>> {
+pub fn install_openvm_crypto() -> InstallReport {
+    install(OpenVmCrypto::default())
+}
+
+/// Report of the installation done by [`install_openvm_crypto_once`].
+static INSTALL: OnceLock<InstallReport> = OnceLock::new();
+
+/// Install OpenVM crypto implementations globally, like [`install_openvm_crypto`], on the first
+/// call only. Returns the report of that installation, or `None` for the later calls, which are
+/// no-ops. Concurrent calls block until the installation is done.
+pub fn install_openvm_crypto_once() -> Option<InstallReport> {
+    let mut report = None;
+    INSTALL.get_or_init(|| *report.insert(install_openvm_crypto()));
+    report
+}
+
+/// Makes sure the OpenVM crypto implementations are installed, see
+/// [`install_openvm_crypto_once`]. Safe to call from many threads, e.g. from tests running in
+/// parallel. Returns whether this call performed the installation.
+pub fn ensure_crypto_installed() -> bool {
+    install_openvm_crypto_once().is_some()
+}
+
+/// Install only the selected OpenVM crypto implementations globally
+pub fn install_openvm_crypto_with(overrides: CryptoOverrides) -> InstallReport {
+    install(OpenVmCrypto { overrides, ..Default::default() })
+}
+
+/// Install OpenVM crypto implementations globally, handling high-s transaction signatures in the
+/// Alloy provider with `tx_high_s`. The `ecrecover` precompile always normalizes them.
+pub fn install_openvm_crypto_with_high_s(tx_high_s: HighSPolicy) -> InstallReport {
+    install_with_provider(OpenVmCrypto::default(), OpenVmK256Provider { high_s: tx_high_s })
+}
+
+/// Install OpenVM crypto implementations globally, using a custom KZG trusted setup for the
+/// point evaluation precompile instead of the mainnet one
+pub fn install_openvm_crypto_with_kzg(settings: KzgSettings) -> InstallReport {
+    install(OpenVmCrypto {
+        kzg_settings: EnvKzgSettings::Custom(Arc::new(settings)),
+        ..Default::default()
+    })
+}
+
+fn install(crypto: OpenVmCrypto) -> InstallReport {
+    install_with_provider(crypto, OpenVmK256Provider::default())
+}
+
+fn install_with_provider(crypto: OpenVmCrypto, provider: OpenVmK256Provider) -> InstallReport {
     // Install OpenVM k256 provider for Alloy (transaction validation)
-    install_default_provider(Arc::new(OpenVmK256Provider))?;
+    let alloy_provider =
+        crypto.overrides.secp256k1 && install_default_provider(Arc::new(provider)).is_ok();
 
     // Install OpenVM crypto for REVM precompiles
-    let installed = install_crypto(OpenVmCrypto);
+    let revm_crypto = install_crypto(crypto);
+
+    InstallReport { alloy_provider, revm_crypto }
+}
+
+// Helper functions for hash operations
 
-    Ok(installed)
+/// Computes the 64-byte SHA-512 digest. Not used by any precompile yet.
+///
+/// OpenVM has no SHA-512 intrinsic, so this is the software implementation from the `sha2`
+/// crate. Swap this out once an intrinsic is available.
+#[inline]
+pub fn sha512(input: &[u8]) -> [u8; 64] {
+    use sha2::Digest;
+    let mut output = [0u8; 64];
+    output.copy_from_slice(&sha2::Sha512::digest(input));
+    output
+}
+
+/// Computes the 20-byte RIPEMD-160 digest.
+///
+/// OpenVM has no RIPEMD-160 intrinsic, so this is the software implementation from the `ripemd`
+/// crate. Swap this out once an intrinsic is available.
+#[inline]
+fn ripemd160_digest(input: &[u8]) -> [u8; 20] {
+    use ripemd::Digest;
+    let mut output = [0u8; 20];
+    output.copy_from_slice(&ripemd::Ripemd160::digest(input));
+    output
 }
 
 // Helper functions for BN254 operations
@@ -332,6 +655,9 @@ fn read_bn_fq(input: &[u8]) -> Result<bn::Fp, PrecompileError> {
 
 #[inline]
 fn read_bn_fq2(input: &[u8]) -> Result<bn::Fp2, PrecompileError> {
+    if input.len() < BN_FQ_LEN * 2 {
+        return Err(PrecompileError::Bn254FieldPointNotAMember);
+    }
     let y = read_bn_fq(&input[..BN_FQ_LEN])?;
     let x = read_bn_fq(&input[BN_FQ_LEN..BN_FQ_LEN * 2])?;
     Ok(bn::Fp2::new(x, y))
@@ -344,9 +670,15 @@ fn read_bn_g1_point(input: &[u8]) -> Result<bn::G1Affine, PrecompileError> {
     }
     let px = read_bn_fq(&input[0..BN_FQ_LEN])?;
     let py = read_bn_fq(&input[BN_FQ_LEN..BN_G1_LEN])?;
+    // EIP-196 encodes the point at infinity as (0, 0)
+    if px == bn::Fp::ZERO && py == bn::Fp::ZERO {
+        return Ok(<bn::G1Affine as WeierstrassPoint>::IDENTITY);
+    }
     bn::G1Affine::from_xy(px, py).ok_or(PrecompileError::Bn254AffineGFailedToCreate)
 }
 
+/// Reads a G2 point and checks that it lies in the prime-order subgroup, as required by EIP-197
+/// for pairing inputs. G1 needs no such check, as its cofactor is 1.
 #[inline]
 fn read_bn_g2_point(input: &[u8]) -> Result<bn::G2Affine, PrecompileError> {
     if input.len() != BN_G2_LEN {
@@ -354,11 +686,26 @@ fn read_bn_g2_point(input: &[u8]) -> Result<bn::G2Affine, PrecompileError> {
     }
     let c0 = read_bn_fq2(&input[0..BN_G1_LEN])?;
     let c1 = read_bn_fq2(&input[BN_G1_LEN..BN_G2_LEN])?;
-    bn::G2Affine::from_xy(c0, c1).ok_or(PrecompileError::Bn254AffineGFailedToCreate)
+    let point = bn::G2Affine::from_xy(c0, c1).ok_or(PrecompileError::Bn254AffineGFailedToCreate)?;
+    if point.is_identity() {
+        return Ok(point);
+    }
+    // [r]Q = [r - 1]Q + Q, since the scalar field cannot represent r itself
+    let r_minus_one = -bn::Scalar::ONE;
+    let check =
+        openvm_ecc_guest::msm(&[r_minus_one], core::slice::from_ref(&point)) + point.clone();
+    if !check.is_identity() {
+        return Err(PrecompileError::Bn254AffineGFailedToCreate);
+    }
+    Ok(point)
 }
 
 #[inline]
 fn encode_bn_g1_point(point: bn::G1Affine) -> [u8; BN_G1_LEN] {
+    if point.is_identity() {
+        return [0u8; BN_G1_LEN];
+    }
+
     let mut output = [0u8; BN_G1_LEN];
 
     let x_bytes: &[u8] = point.x().as_le_bytes();
@@ -372,65 +719,90 @@ fn encode_bn_g1_point(point: bn::G1Affine) -> [u8; BN_G1_LEN] {
 
 /// Reads a scalar from the input slice
 ///
-/// Note: The scalar does not need to be canonical.
-///
-/// # Panics
-///
-/// If `input.len()` is not equal to [`BN_SCALAR_LEN`].
+/// Note: The scalar does not need to be canonical. Fails if `input.len()` is not equal to
+/// [`BN_SCALAR_LEN`].
 #[inline]
-fn read_bn_scalar(input: &[u8]) -> bn::Scalar {
-    assert_eq!(
-        input.len(),
-        BN_SCALAR_LEN,
-        "unexpected scalar length. got {}, expected {BN_SCALAR_LEN}",
-        input.len()
-    );
-    bn::Scalar::from_be_bytes_unchecked(input)
+fn read_bn_scalar(input: &[u8]) -> Result<bn::Scalar, OpenVmCryptoError> {
+    if input.len() != BN_SCALAR_LEN {
+        return Err(OpenVmCryptoError::InvalidBnScalarLength);
+    }
+    Ok(bn::Scalar::from_be_bytes_unchecked(input))
 }
 
 // Helper functions for BLS12-381 operations
 
 #[inline]
-fn read_bls_fp(input: &[u8]) -> Result<bls::Fp, PrecompileError> {
+fn read_bls_fp(input: &[u8]) -> Result<bls::Fp, OpenVmCryptoError> {
     if input.len() != BLS_FP_LEN {
-        return Err(PrecompileError::other("invalid BLS12-381 fp length"));
+        return Err(OpenVmCryptoError::InvalidBlsFpLength);
     }
-    bls::Fp::from_be_bytes(input)
-        .ok_or_else(|| PrecompileError::other("element not in BLS12-381 base field"))
+    bls::Fp::from_be_bytes(input).ok_or(OpenVmCryptoError::BlsFpNotInField)
 }
 
 #[inline]
-fn read_bls_fp2(c0: &[u8], c1: &[u8]) -> Result<bls::Fp2, PrecompileError> {
+fn read_bls_fp2(c0: &[u8], c1: &[u8]) -> Result<bls::Fp2, OpenVmCryptoError> {
     let real = read_bls_fp(c0)?;
     let imag = read_bls_fp(c1)?;
     Ok(bls::Fp2::new(real, imag))
 }
 
 #[inline]
-fn read_bls_g1_point(point: &BlsG1Point) -> Result<bls::G1Affine, PrecompileError> {
+fn read_bls_g1_point(point: &BlsG1Point) -> Result<bls::G1Affine, OpenVmCryptoError> {
     let px = read_bls_fp(&point.0)?;
     let py = read_bls_fp(&point.1)?;
-    bls::G1Affine::from_xy(px, py)
-        .ok_or_else(|| PrecompileError::other("failed to create BLS12-381 G1 point"))
+    bls::G1Affine::from_xy(px, py).ok_or(OpenVmCryptoError::BlsG1NotOnCurve)
 }
 
+/// Reads a G1 point and checks that it lies in the prime-order subgroup, as required by
+/// EIP-2537 for MSM and pairing inputs.
 #[inline]
-fn read_bls_g2_point(point: &BlsG2Point) -> Result<bls::G2Affine, PrecompileError> {
+fn read_bls_g1_point_in_subgroup(point: &BlsG1Point) -> Result<bls::G1Affine, OpenVmCryptoError> {
+    let point = read_bls_g1_point(point)?;
+    if point.is_identity() {
+        return Ok(point);
+    }
+    // [r]P = [r - 1]P + P, since the scalar field cannot represent r itself
+    let r_minus_one = -bls::Scalar::ONE;
+    let check = Bls12_381::msm(&[r_minus_one], core::slice::from_ref(&point)) + point.clone();
+    if !check.is_identity() {
+        return Err(OpenVmCryptoError::BlsG1NotInSubgroup);
+    }
+    Ok(point)
+}
+
+#[inline]
+fn read_bls_g2_point(point: &BlsG2Point) -> Result<bls::G2Affine, OpenVmCryptoError> {
     let x = read_bls_fp2(&point.0, &point.1)?;
     let y = read_bls_fp2(&point.2, &point.3)?;
-    bls::G2Affine::from_xy(x, y)
-        .ok_or_else(|| PrecompileError::other("failed to create BLS12-381 G2 point"))
+    bls::G2Affine::from_xy(x, y).ok_or(OpenVmCryptoError::BlsG2NotOnCurve)
 }
 
+/// Reads a G2 point and checks that it lies in the prime-order subgroup, as required by
+/// EIP-2537 for pairing inputs.
 #[inline]
-fn read_bls_scalar(input: &[u8]) -> bls::Scalar {
-    assert_eq!(
-        input.len(),
-        BLS_SCALAR_LEN,
-        "unexpected scalar length. got {}, expected {BLS_SCALAR_LEN}",
-        input.len()
-    );
-    bls::Scalar::from_be_bytes_unchecked(input)
+fn read_bls_g2_point_in_subgroup(point: &BlsG2Point) -> Result<bls::G2Affine, OpenVmCryptoError> {
+    let point = read_bls_g2_point(point)?;
+    if point.is_identity() {
+        return Ok(point);
+    }
+    // [r]Q = [r - 1]Q + Q, since the scalar field cannot represent r itself
+    let r_minus_one = -bls::Scalar::ONE;
+    let check =
+        openvm_ecc_guest::msm(&[r_minus_one], core::slice::from_ref(&point)) + point.clone();
+    if !check.is_identity() {
+        return Err(OpenVmCryptoError::BlsG2NotInSubgroup);
+    }
+    Ok(point)
+}
+
+/// Reads a scalar, which does not need to be canonical. Fails if `input.len()` is not equal to
+/// [`BLS_SCALAR_LEN`].
+#[inline]
+fn read_bls_scalar(input: &[u8]) -> Result<bls::Scalar, OpenVmCryptoError> {
+    if input.len() != BLS_SCALAR_LEN {
+        return Err(OpenVmCryptoError::InvalidBlsScalarLength);
+    }
+    Ok(bls::Scalar::from_be_bytes_unchecked(input))
 }
 
 #[inline]
@@ -470,3 +842,694 @@ fn encode_bls_g2_point(point: &bls::G2Affine) -> [u8; BLS_G2_LEN] {
     }
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, hex};
+
+    /// BLS12-381 G1 generator.
+    const BLS_G1: [u8; BLS_G1_LEN] = hex!(
+        "17f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb"
+        "08b3f481e3aaa0f1a09e30ed741d8ae4fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1"
+    );
+    /// `2 * BLS_G1`
+    const BLS_G1_DOUBLE: [u8; BLS_G1_LEN] = hex!(
+        "0572cbea904d67468808c8eb50a9450c9721db309128012543902d0ac358a62ae28f75bb8f1c7c42c39a8c5529bf0f4e"
+        "166a9d8cabc673a322fda673779d8e3822ba3ecb8670e461f73bb9021d5fd76a4c56d9d4cd16bd1bba86881979749d28"
+    );
+    /// `3 * BLS_G1`
+    const BLS_G1_TRIPLE: [u8; BLS_G1_LEN] = hex!(
+        "09ece308f9d1f0131765212deca99697b112d61f9be9a5f1f3780a51335b3ff981747a0b2ca2179b96d2c0c9024e5224"
+        "032b80d3a6f5b09f8a84623389c5f80ca69a0cddabc3097f9d9c27310fd43be6e745256c634af45ca3473b0590ae30d1"
+    );
+    /// `5 * BLS_G1`
+    const BLS_G1_QUINTUPLE: [u8; BLS_G1_LEN] = hex!(
+        "10e7791fb972fe014159aa33a98622da3cdc98ff707965e536d8636b5fcc5ac7a91a8c46e59a00dca575af0f18fb13dc"
+        "16ba437edcc6551e30c10512367494bfb6b01cc6681e8a4c3cd2501832ab5c4abc40b4578b85cbaffbf0bcd70d67c6e2"
+    );
+    /// A point on the G1 curve outside of the prime-order subgroup.
+    const BLS_G1_NOT_IN_SUBGROUP: [u8; BLS_G1_LEN] = hex!(
+        "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000004"
+        "0a989badd40d6212b33cffc3f3763e9bc760f988c9926b26da9dd85e928483446346b8ed00e1de5d5ea93e354abe706c"
+    );
+    /// BLS12-381 G2 generator.
+    const BLS_G2: [u8; BLS_G2_LEN] = hex!(
+        "024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8"
+        "13e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e"
+        "0ce5d527727d6e118cc9cdc6da2e351aadfd9baa8cbdd3a76d429a695160d12c923ac9cc3baca289e193548608b82801"
+        "0606c4a02ea734cc32acd2b02bc28b99cb3e287e85a763af267492ab572e99ab3f370d275cec1da1aaa9075ff05f79be"
+    );
+    /// A point on the G2 curve outside of the prime-order subgroup.
+    const BLS_G2_NOT_IN_SUBGROUP: [u8; BLS_G2_LEN] = hex!(
+        "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000002"
+        "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000"
+        "013a59858b6809fca4d9a3b6539246a70051a3c88899964a42bc9a69cf9acdd9dd387cfa9086b894185b9a46a402be73"
+        "02d27e0ec3356299a346a09ad7dc4ef68a483c3aed53f9139d2f929a3eecebf72082e5e58c6da24ee32e03040c406d4f"
+    );
+    /// The BLS12-381 base field modulus.
+    const BLS_P: [u8; BLS_FP_LEN] = hex!(
+        "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaab"
+    );
+
+    #[test]
+    fn test_install_twice() {
+        install_openvm_crypto();
+        let report = install_openvm_crypto();
+        assert_eq!(report, InstallReport { alloy_provider: false, revm_crypto: false });
+    }
+
+    #[test]
+    fn test_install_once() {
+        // this is the only test installing through `install_openvm_crypto_once`
+        let threads: Vec<_> = (0..8).map(|_| std::thread::spawn(ensure_crypto_installed)).collect();
+        let installs = threads.into_iter().map(|thread| thread.join().unwrap());
+        assert_eq!(installs.filter(|&installed| installed).count(), 1);
+        assert!(!ensure_crypto_installed());
+        assert_eq!(install_openvm_crypto_once(), None);
+    }
+
+    #[test]
+    fn test_disabled_override_uses_default() {
+        let overrides = CryptoOverrides { sha256: false, ..CryptoOverrides::ALL };
+        let crypto = OpenVmCrypto { overrides, ..Default::default() };
+        let expected = hex!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(crypto.sha256(b"abc"), expected);
+        assert_eq!(DefaultCrypto.sha256(b"abc"), expected);
+        assert_eq!(OpenVmCrypto::default().sha256(b"abc"), expected);
+
+        let crypto = OpenVmCrypto { overrides: CryptoOverrides::NONE, ..Default::default() };
+        assert_eq!(crypto.ripemd160(b"abc"), DefaultCrypto.ripemd160(b"abc"));
+    }
+
+    #[test]
+    fn test_recover_signers_unchecked() {
+        // signatures over sha256("openvm {i}") by the private keys 1, 2 and 3
+        let items: [([u8; 65], [u8; 32]); 3] = [
+            (
+                hex!(
+                    "cfcb312d51d535ffe7f00c4fee6c4ca4c12535b0ea830c3a1318586bb79741b0"
+                    "31134b454e5cbaf03d27ef041d7874f77eae8e51622cf8f1a3e8a29a18d02370"
+                    "01"
+                ),
+                hex!("f47d48f591d752fe8a02e897c8501b31ecaefbd9281bd65cf81397347c83ec77"),
+            ),
+            (
+                hex!(
+                    "22d0956ed93b6743fcd560b03cd932f2d6a74ca85d0977e8bc431c7fa08dd4a1"
+                    "26738a1614a8296505596e874ebd0eb9bc25da69b22ab52cb4703804c353e9a4"
+                    "00"
+                ),
+                hex!("723a2053736980452d44fb00f3da2aae8e218129e0b13f37d64fc201a91d2ed0"),
+            ),
+            (
+                hex!(
+                    "19245b100e2436f6bc26e1a1f1546a9a237cc07c1a91b25135887dbbd2732aeb"
+                    "746a02ea41d3b7a3e305c336859d947a1ae20d96dea2999a16a21d4db2a14e77"
+                    "01"
+                ),
+                hex!("637b380676203e321cea9706c325948330216bb9ea2db8d1f5d8e9a00bf168bc"),
+            ),
+        ];
+        let expected = [
+            address!("7E5F4552091A69125d5DfCb7b8C2659029395Bdf"),
+            address!("2B5AD5c4795c026514f8317c7a215E218DcCD6cF"),
+            address!("6813Eb9362372EEF6200f3b1dbC3f819671cBA69"),
+        ];
+
+        let recovered =
+            recover_signers_unchecked(items.iter().map(|(sig, msg)| (sig, msg))).unwrap();
+        assert_eq!(recovered, expected);
+
+        let provider = OpenVmK256Provider::default();
+        for ((sig, msg), signer) in items.iter().zip(expected) {
+            assert_eq!(provider.recover_signer_unchecked(sig, msg).unwrap(), signer);
+        }
+
+        assert!(recover_signers_unchecked([]).unwrap().is_empty());
+        let mut invalid = items[0].0;
+        invalid[..32].fill(0);
+        assert!(recover_signers_unchecked([(&invalid, &items[0].1)]).is_err());
+    }
+
+    #[test]
+    fn test_high_s_policy() {
+        // the first signature of `test_recover_signers_unchecked`, and its high-s equivalent
+        let low_s = hex!(
+            "cfcb312d51d535ffe7f00c4fee6c4ca4c12535b0ea830c3a1318586bb79741b0"
+            "31134b454e5cbaf03d27ef041d7874f77eae8e51622cf8f1a3e8a29a18d02370"
+            "01"
+        );
+        let msg = hex!("f47d48f591d752fe8a02e897c8501b31ecaefbd9281bd65cf81397347c83ec77");
+        let signer = address!("7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+        let n = alloy_primitives::U256::from_be_bytes(hex!(
+            "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"
+        ));
+        let mut high_s = low_s;
+        let s = alloy_primitives::U256::from_be_slice(&low_s[32..64]);
+        high_s[32..64].copy_from_slice(&(n - s).to_be_bytes::<32>());
+        high_s[64] ^= 1;
+
+        // the ecrecover precompile accepts high-s signatures
+        let crypto = OpenVmCrypto::default();
+        let recovered =
+            crypto.secp256k1_ecrecover(high_s[..64].try_into().unwrap(), high_s[64], &msg).unwrap();
+        assert_eq!(recovered[12..], signer[..]);
+
+        let normalize = OpenVmK256Provider { high_s: HighSPolicy::Normalize };
+        assert_eq!(normalize.recover_signer_unchecked(&high_s, &msg).unwrap(), signer);
+        let reject = OpenVmK256Provider { high_s: HighSPolicy::Reject };
+        assert!(reject.recover_signer_unchecked(&high_s, &msg).is_err());
+        assert_eq!(reject.recover_signer_unchecked(&low_s, &msg).unwrap(), signer);
+    }
+
+    #[test]
+    fn test_recovery_id_range() {
+        // the first signature of `test_recover_signers_unchecked`
+        let sig = hex!(
+            "cfcb312d51d535ffe7f00c4fee6c4ca4c12535b0ea830c3a1318586bb79741b0"
+            "31134b454e5cbaf03d27ef041d7874f77eae8e51622cf8f1a3e8a29a18d02370"
+            "01"
+        );
+        let msg = hex!("f47d48f591d752fe8a02e897c8501b31ecaefbd9281bd65cf81397347c83ec77");
+        let provider = OpenVmK256Provider::default();
+        for recid in [4, 255] {
+            assert_eq!(
+                ecrecover(sig[..64].try_into().unwrap(), recid, &msg),
+                Err(OpenVmCryptoError::InvalidRecoveryId)
+            );
+            let mut invalid = sig;
+            invalid[64] = recid;
+            assert!(provider.recover_signer_unchecked(&invalid, &msg).is_err());
+        }
+    }
+
+    #[test]
+    fn test_ecrecover_errors() {
+        let scalar = |k: u8| {
+            let mut bytes = [0u8; 32];
+            bytes[31] = k;
+            bytes
+        };
+        let sig = |r: [u8; 32], s: [u8; 32]| {
+            let mut sig = [0u8; 64];
+            sig[..32].copy_from_slice(&r);
+            sig[32..].copy_from_slice(&s);
+            sig
+        };
+        let msg = [1u8; 32];
+
+        // r = 0
+        assert_eq!(
+            ecrecover(&sig(scalar(0), scalar(1)), 0, &msg),
+            Err(OpenVmCryptoError::InvalidSignature)
+        );
+        assert_eq!(
+            ecrecover(&sig(scalar(1), scalar(1)), 4, &msg),
+            Err(OpenVmCryptoError::InvalidRecoveryId)
+        );
+        // x = 5 is not the x-coordinate of a point on secp256k1
+        assert_eq!(
+            ecrecover(&sig(scalar(5), scalar(1)), 0, &msg),
+            Err(OpenVmCryptoError::KeyRecoveryFailed)
+        );
+
+        assert_eq!(
+            PrecompileError::from(OpenVmCryptoError::KeyRecoveryFailed),
+            PrecompileError::other("Key recovery failed")
+        );
+    }
+
+    #[test]
+    fn test_verify_kzg_proof_errors() {
+        let settings = EnvKzgSettings::default();
+        let mut infinity = [0u8; 48];
+        infinity[0] = 0xc0;
+        let mut one = [0u8; 32];
+        one[31] = 1;
+
+        // the zero polynomial opens to zero everywhere
+        assert_eq!(
+            verify_kzg_proof(settings.get(), &one, &[0u8; 32], &infinity, &infinity),
+            Ok(())
+        );
+        // wrong evaluation
+        assert_eq!(
+            verify_kzg_proof(settings.get(), &one, &one, &infinity, &infinity),
+            Err(OpenVmCryptoError::KzgVerificationFailed)
+        );
+        // commitment is not a valid compressed point
+        assert_eq!(
+            verify_kzg_proof(settings.get(), &one, &[0u8; 32], &[0xffu8; 48], &infinity),
+            Err(OpenVmCryptoError::KzgVerificationFailed)
+        );
+        // proof is not a valid compressed point
+        assert_eq!(
+            verify_kzg_proof(settings.get(), &one, &[0u8; 32], &infinity, &[0xffu8; 48]),
+            Err(OpenVmCryptoError::KzgVerificationFailed)
+        );
+        // z is not in the scalar field
+        assert_eq!(
+            verify_kzg_proof(settings.get(), &[0xffu8; 32], &[0u8; 32], &infinity, &infinity),
+            Err(OpenVmCryptoError::KzgVerificationFailed)
+        );
+        assert_eq!(
+            PrecompileError::from(OpenVmCryptoError::KzgVerificationFailed),
+            PrecompileError::other("openvm kzg proof verification failed")
+        );
+    }
+
+    #[test]
+    fn test_bls_read_errors() {
+        let mut one = [0u8; BLS_FP_LEN];
+        one[BLS_FP_LEN - 1] = 1;
+
+        assert_eq!(read_bls_fp(&one[1..]).err(), Some(OpenVmCryptoError::InvalidBlsFpLength));
+        assert_eq!(read_bls_fp(&BLS_P).err(), Some(OpenVmCryptoError::BlsFpNotInField));
+        assert_eq!(read_bls_scalar(&[]).err(), Some(OpenVmCryptoError::InvalidBlsScalarLength));
+        assert_eq!(read_bls_g1_point(&(one, one)).err(), Some(OpenVmCryptoError::BlsG1NotOnCurve));
+        assert_eq!(
+            read_bls_g2_point(&(one, one, one, one)).err(),
+            Some(OpenVmCryptoError::BlsG2NotOnCurve)
+        );
+        assert_eq!(
+            read_bls_g1_point_in_subgroup(&bls_g1(&BLS_G1_NOT_IN_SUBGROUP)).err(),
+            Some(OpenVmCryptoError::BlsG1NotInSubgroup)
+        );
+        assert_eq!(
+            read_bls_g2_point_in_subgroup(&bls_g2(&BLS_G2_NOT_IN_SUBGROUP)).err(),
+            Some(OpenVmCryptoError::BlsG2NotInSubgroup)
+        );
+    }
+
+    /// BN254 G1 generator.
+    const BN_G1: [u8; BN_G1_LEN] = hex!(
+        "0000000000000000000000000000000000000000000000000000000000000001"
+        "0000000000000000000000000000000000000000000000000000000000000002"
+    );
+    /// `2 * BN_G1`
+    const BN_G1_DOUBLE: [u8; BN_G1_LEN] = hex!(
+        "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3"
+        "15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4"
+    );
+
+    #[test]
+    fn test_bn254_g1_infinity() {
+        let crypto = OpenVmCrypto::default();
+        let infinity = [0u8; BN_G1_LEN];
+        let scalar = |k: u8| {
+            let mut bytes = [0u8; BN_SCALAR_LEN];
+            bytes[BN_SCALAR_LEN - 1] = k;
+            bytes
+        };
+
+        assert_eq!(crypto.bn254_g1_add(&BN_G1, &BN_G1).unwrap(), BN_G1_DOUBLE);
+        assert_eq!(crypto.bn254_g1_add(&BN_G1, &infinity).unwrap(), BN_G1);
+        assert_eq!(crypto.bn254_g1_add(&infinity, &BN_G1).unwrap(), BN_G1);
+        assert_eq!(crypto.bn254_g1_add(&infinity, &infinity).unwrap(), infinity);
+
+        assert_eq!(crypto.bn254_g1_mul(&BN_G1, &scalar(2)).unwrap(), BN_G1_DOUBLE);
+        assert_eq!(crypto.bn254_g1_mul(&BN_G1, &scalar(0)).unwrap(), infinity);
+        assert_eq!(crypto.bn254_g1_mul(&infinity, &scalar(5)).unwrap(), infinity);
+    }
+
+    /// BN254 G2 generator.
+    const BN_G2: [u8; BN_G2_LEN] = hex!(
+        "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2"
+        "1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed"
+        "090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b"
+        "12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa"
+    );
+    /// A point on the BN254 G2 curve, with `x = 1`, outside of the prime-order subgroup.
+    const BN_G2_NOT_IN_SUBGROUP: [u8; BN_G2_LEN] = hex!(
+        "0000000000000000000000000000000000000000000000000000000000000000"
+        "0000000000000000000000000000000000000000000000000000000000000001"
+        "0d1271953ed9ea0836846e70a1934187998c7f790cb4d7511b7f8da82de048a4"
+        "2869111d5381f072f8e2728fdb825a51aadd70e52c9830e9ab4b871c0531f1bb"
+    );
+
+    #[test]
+    fn test_read_bn_scalar_length() {
+        // used to panic
+        assert_eq!(read_bn_scalar(&[]).err(), Some(OpenVmCryptoError::InvalidBnScalarLength));
+        assert_eq!(
+            read_bn_scalar(&[0u8; BN_SCALAR_LEN + 1]).err(),
+            Some(OpenVmCryptoError::InvalidBnScalarLength)
+        );
+        assert!(read_bn_scalar(&[7u8; BN_SCALAR_LEN]).is_ok());
+        assert_eq!(
+            OpenVmCrypto::default().bn254_g1_mul(&BN_G1, &[]).err(),
+            Some(OpenVmCryptoError::InvalidBnScalarLength.into())
+        );
+    }
+
+    proptest::proptest! {
+        /// The BN254 readers parse untrusted precompile input, so they must fail with one of
+        /// their documented errors instead of panicking.
+        #[test]
+        fn test_bn_readers_never_panic(
+            input in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..=2 * BN_G2_LEN)
+        ) {
+            let field = PrecompileError::Bn254FieldPointNotAMember;
+            let curve = PrecompileError::Bn254AffineGFailedToCreate;
+            let length = PrecompileError::Bn254PairLength;
+
+            if let Err(err) = read_bn_fq(&input) {
+                proptest::prop_assert_eq!(err, field.clone());
+            }
+            if let Err(err) = read_bn_fq2(&input) {
+                proptest::prop_assert_eq!(err, field.clone());
+            }
+            let point_errors = [field, curve, length];
+            if let Err(err) = read_bn_g1_point(&input) {
+                proptest::prop_assert!(point_errors.contains(&err), "{err:?}");
+            }
+            if let Err(err) = read_bn_g2_point(&input) {
+                proptest::prop_assert!(point_errors.contains(&err), "{err:?}");
+            }
+            if let Err(err) = read_bn_scalar(&input) {
+                proptest::prop_assert_eq!(err, OpenVmCryptoError::InvalidBnScalarLength);
+            }
+        }
+
+        /// Same for the BLS12-381 readers that parse inputs of untrusted length.
+        #[test]
+        fn test_bls_readers_never_panic(
+            input in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..=2 * BLS_FP_LEN)
+        ) {
+            if let Err(err) = read_bls_fp(&input) {
+                let errors =
+                    [OpenVmCryptoError::InvalidBlsFpLength, OpenVmCryptoError::BlsFpNotInField];
+                proptest::prop_assert!(errors.contains(&err), "{err:?}");
+            }
+            if let Err(err) = read_bls_scalar(&input) {
+                proptest::prop_assert_eq!(err, OpenVmCryptoError::InvalidBlsScalarLength);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bn254_g2_subgroup() {
+        assert!(read_bn_g2_point(&BN_G2).is_ok());
+        assert_eq!(
+            read_bn_g2_point(&BN_G2_NOT_IN_SUBGROUP).err(),
+            Some(PrecompileError::Bn254AffineGFailedToCreate)
+        );
+
+        let crypto = OpenVmCrypto::default();
+        assert_eq!(
+            crypto.bn254_pairing_check(&[(&BN_G1, &BN_G2_NOT_IN_SUBGROUP)]).err(),
+            Some(PrecompileError::Bn254AffineGFailedToCreate)
+        );
+        assert_eq!(
+            DefaultCrypto.bn254_pairing_check(&[(&BN_G1, &BN_G2_NOT_IN_SUBGROUP)]).is_err(),
+            crypto.bn254_pairing_check(&[(&BN_G1, &BN_G2_NOT_IN_SUBGROUP)]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_ripemd160() {
+        let crypto = OpenVmCrypto::default();
+        let vectors: [(&[u8], [u8; 20]); 4] = [
+            (b"", hex!("9c1185a5c5e9fc54612808977ee8f548b2258d31")),
+            (b"abc", hex!("8eb208f7e05d987a9b044a8e98c6b087f15a0bfc")),
+            (b"message digest", hex!("5d0689ef49d2fae572b881b123a85ffa21595f36")),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                hex!("9b752e45573d4b39f4dbd3323cab82bf63326bfb"),
+            ),
+        ];
+        for (input, digest) in vectors {
+            let output = crypto.ripemd160(input);
+            assert_eq!(output[..12], [0u8; 12]);
+            assert_eq!(output[12..], digest);
+        }
+    }
+
+    #[test]
+    fn test_sha512() {
+        let vectors: [(&[u8], [u8; 64]); 4] = [
+            (
+                b"",
+                hex!(
+                    "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce"
+                    "47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e"
+                ),
+            ),
+            (
+                b"abc",
+                hex!(
+                    "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a"
+                    "2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+                ),
+            ),
+            (
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                hex!(
+                    "204a8fc6dda82f0a0ced7beb8e08a41657c16ef468b228a8279be331a703c335"
+                    "96fd15c13b1b07f9aa1d3bea57789ca031ad85c7a71dd70354ec631238ca3445"
+                ),
+            ),
+            (
+                b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+                hex!(
+                    "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018"
+                    "501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909"
+                ),
+            ),
+        ];
+        for (input, digest) in vectors {
+            assert_eq!(sha512(input), digest);
+        }
+    }
+
+    #[test]
+    fn test_modexp() {
+        let crypto = OpenVmCrypto::default();
+        let modexp = |base: &[u8], exp: &[u8], modulus: &[u8]| {
+            let output = crypto.modexp(base, exp, modulus).unwrap();
+            // the precompile left-pads the result to the modulus length
+            let mut padded = vec![0u8; modulus.len().saturating_sub(output.len())];
+            padded.extend_from_slice(&output);
+            padded
+        };
+
+        // EIP-198 example: 3^(p - 1) mod p = 1 for the secp256k1 field prime p
+        let p = hex!("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f");
+        let p_minus_one = hex!("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e");
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert_eq!(modexp(&[3], &p_minus_one, &p), one);
+
+        // 2^10 mod 1000 = 24, base longer than the modulus
+        assert_eq!(modexp(&[0, 0, 0, 2], &[10], &hex!("03e8")), hex!("0018"));
+        // 1001^1 mod 1000 = 1
+        assert_eq!(modexp(&hex!("03e9"), &[1], &hex!("03e8")), hex!("0001"));
+
+        // zero modulus
+        assert_eq!(modexp(&[3], &[5], &[0, 0]), [0, 0]);
+        assert!(modexp(&[3], &[5], &[]).is_empty());
+        // modulus one
+        assert_eq!(modexp(&[3], &[5], &[0, 1]), [0, 0]);
+        // zero exponent, including 0^0
+        assert_eq!(modexp(&[3], &[], &[0, 7]), [0, 1]);
+        assert_eq!(modexp(&[], &[0], &[7]), [1]);
+        // zero base
+        assert_eq!(modexp(&[0], &[5], &[7]), [0]);
+    }
+
+    #[test]
+    fn test_secp256r1_verify_signature() {
+        let crypto = OpenVmCrypto::default();
+        // sha256("sample")
+        let msg = hex!("af2bdbe1aa9b6ec1e2ade1d694f41fc71a831d0268e9891562113d8a62add1bf");
+        let sig = hex!(
+            "6144629be413b06c58c98b276b4789a3ca3632700943220974515c91c5ab2136"
+            "486bf738247745866f31afcb2a02cef28b57f4e536cf6d5966209632cf82ec46"
+        );
+        let pk = hex!(
+            "60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6"
+            "7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299"
+        );
+        assert!(crypto.secp256r1_verify_signature(&msg, &sig, &pk));
+
+        // the same signature with s replaced by n - s
+        let mut high_s = sig;
+        high_s[32..].copy_from_slice(&hex!(
+            "b79408c6db88ba7a90ce5034d5fd310d318f05c87048312b8d9934902ce0390b"
+        ));
+        assert!(crypto.secp256r1_verify_signature(&msg, &high_s, &pk));
+
+        // tampered message, signature and public key
+        let mut tampered_msg = msg;
+        tampered_msg[0] ^= 1;
+        assert!(!crypto.secp256r1_verify_signature(&tampered_msg, &sig, &pk));
+        let mut tampered_sig = sig;
+        tampered_sig[63] ^= 1;
+        assert!(!crypto.secp256r1_verify_signature(&msg, &tampered_sig, &pk));
+        let mut tampered_pk = pk;
+        tampered_pk[63] ^= 1;
+        assert!(!crypto.secp256r1_verify_signature(&msg, &sig, &tampered_pk));
+
+        // r = 0 and the point at infinity are rejected
+        let mut zero_r = sig;
+        zero_r[..32].fill(0);
+        assert!(!crypto.secp256r1_verify_signature(&msg, &zero_r, &pk));
+        assert!(!crypto.secp256r1_verify_signature(&msg, &sig, &[0u8; 64]));
+    }
+
+    #[test]
+    fn test_verify_kzg_proof_custom_settings() {
+        // A minimal trusted setup with tau = 1: its G2 points are [G2, tau * G2] = [G2, G2]. The
+        // polynomial p(x) = x then commits to the G1 generator, and so does the proof of any
+        // opening, since the quotient (p(x) - p(z)) / (x - z) is 1.
+        let mainnet = EnvKzgSettings::default().get().clone();
+        let g2 = mainnet.g2_points[0];
+        let settings = KzgSettings { g2_points: Vec::leak(vec![g2, g2]), ..mainnet };
+        let crypto = OpenVmCrypto {
+            kzg_settings: EnvKzgSettings::Custom(Arc::new(settings)),
+            ..Default::default()
+        };
+
+        // the compressed G1 generator
+        let g1 = hex!(
+            "97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905"
+            "a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb"
+        );
+        let z = hex!("0000000000000000000000000000000000000000000000000000000000000002");
+        assert!(crypto.verify_kzg_proof(&z, &z, &g1, &g1).is_ok());
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert!(crypto.verify_kzg_proof(&z, &one, &g1, &g1).is_err());
+
+        // the mainnet setup has a different tau, so the same proof does not verify with it
+        assert!(OpenVmCrypto::default().verify_kzg_proof(&z, &z, &g1, &g1).is_err());
+    }
+
+    fn bls_g1(bytes: &[u8; BLS_G1_LEN]) -> BlsG1Point {
+        (bytes[..BLS_FP_LEN].try_into().unwrap(), bytes[BLS_FP_LEN..].try_into().unwrap())
+    }
+
+    fn bls_g2(bytes: &[u8; BLS_G2_LEN]) -> BlsG2Point {
+        let fp = |i: usize| bytes[i * BLS_FP_LEN..(i + 1) * BLS_FP_LEN].try_into().unwrap();
+        (fp(0), fp(1), fp(2), fp(3))
+    }
+
+    fn bls_g1_neg(bytes: &[u8; BLS_G1_LEN]) -> BlsG1Point {
+        let point = read_bls_g1_point(&bls_g1(bytes)).unwrap();
+        bls_g1(&encode_bls_g1_point(&-point))
+    }
+
+    #[test]
+    fn test_bls12_381_g1_add() {
+        let crypto = OpenVmCrypto::default();
+        let g1 = bls_g1(&BLS_G1);
+        let infinity = bls_g1(&[0u8; BLS_G1_LEN]);
+
+        assert_eq!(crypto.bls12_381_g1_add(g1, g1).unwrap(), BLS_G1_DOUBLE);
+        assert_eq!(crypto.bls12_381_g1_add(g1, bls_g1(&BLS_G1_DOUBLE)).unwrap(), BLS_G1_TRIPLE);
+        assert_eq!(crypto.bls12_381_g1_add(g1, infinity).unwrap(), BLS_G1);
+        assert_eq!(crypto.bls12_381_g1_add(infinity, infinity).unwrap(), [0u8; BLS_G1_LEN]);
+        assert_eq!(crypto.bls12_381_g1_add(g1, bls_g1_neg(&BLS_G1)).unwrap(), [0u8; BLS_G1_LEN]);
+    }
+
+    #[test]
+    fn test_bls12_381_g1_msm() {
+        let crypto = OpenVmCrypto::default();
+        let scalar = |k: u8| {
+            let mut bytes = [0u8; BLS_SCALAR_LEN];
+            bytes[BLS_SCALAR_LEN - 1] = k;
+            bytes
+        };
+        let msm = |pairs: Vec<BlsG1PointScalar>| {
+            crypto.bls12_381_g1_msm(&mut pairs.into_iter().map(Ok::<_, PrecompileError>))
+        };
+        let g1 = bls_g1(&BLS_G1);
+
+        assert_eq!(msm(vec![(g1, scalar(2))]).unwrap(), BLS_G1_DOUBLE);
+        assert_eq!(msm(vec![(g1, scalar(1)), (g1, scalar(2))]).unwrap(), BLS_G1_TRIPLE);
+        assert_eq!(
+            msm(vec![(bls_g1(&BLS_G1_DOUBLE), scalar(2)), (g1, scalar(1))]).unwrap(),
+            BLS_G1_QUINTUPLE
+        );
+        assert_eq!(msm(vec![(g1, scalar(0))]).unwrap(), [0u8; BLS_G1_LEN]);
+        assert_eq!(msm(vec![(bls_g1(&[0u8; BLS_G1_LEN]), scalar(3))]).unwrap(), [0u8; BLS_G1_LEN]);
+        assert_eq!(msm(vec![]).unwrap(), [0u8; BLS_G1_LEN]);
+
+        // on the curve, but not in the prime-order subgroup
+        assert!(msm(vec![(bls_g1(&BLS_G1_NOT_IN_SUBGROUP), scalar(1))]).is_err());
+    }
+
+    #[test]
+    fn test_bls12_381_pairing_check() {
+        let crypto = OpenVmCrypto::default();
+        let g1 = bls_g1(&BLS_G1);
+        let g2 = bls_g2(&BLS_G2);
+        let g1_infinity = bls_g1(&[0u8; BLS_G1_LEN]);
+        let g2_infinity = bls_g2(&[0u8; BLS_G2_LEN]);
+
+        // e(G1, G2) * e(-G1, G2) = 1
+        assert!(crypto.bls12_381_pairing_check(&[(g1, g2), (bls_g1_neg(&BLS_G1), g2)]).unwrap());
+        // e(2 * G1, G2) * e(-G1, G2) * e(-G1, G2) = 1
+        let neg_g1 = bls_g1_neg(&BLS_G1);
+        let pairs = [(bls_g1(&BLS_G1_DOUBLE), g2), (neg_g1, g2), (neg_g1, g2)];
+        assert!(crypto.bls12_381_pairing_check(&pairs).unwrap());
+        // e(G1, G2) != 1
+        assert!(!crypto.bls12_381_pairing_check(&[(g1, g2)]).unwrap());
+        assert!(!crypto.bls12_381_pairing_check(&[(g1, g2), (g1, g2_infinity)]).unwrap());
+
+        // pairs containing the identity are neutral
+        assert!(crypto.bls12_381_pairing_check(&[]).unwrap());
+        assert!(crypto.bls12_381_pairing_check(&[(g1_infinity, g2)]).unwrap());
+        assert!(crypto.bls12_381_pairing_check(&[(g1, g2_infinity)]).unwrap());
+    }
+
+    #[test]
+    fn test_bls12_381_pairing_check_not_in_subgroup() {
+        let crypto = OpenVmCrypto::default();
+        let g1 = bls_g1(&BLS_G1);
+        let g2 = bls_g2(&BLS_G2);
+
+        let g1_not_in_subgroup = bls_g1(&BLS_G1_NOT_IN_SUBGROUP);
+        assert!(crypto.bls12_381_pairing_check(&[(g1_not_in_subgroup, g2)]).is_err());
+        let g2_not_in_subgroup = bls_g2(&BLS_G2_NOT_IN_SUBGROUP);
+        assert!(crypto.bls12_381_pairing_check(&[(g1, g2_not_in_subgroup)]).is_err());
+        // the subgroup check applies even when the other point is the identity
+        let g1_infinity = bls_g1(&[0u8; BLS_G1_LEN]);
+        assert!(crypto.bls12_381_pairing_check(&[(g1_infinity, g2_not_in_subgroup)]).is_err());
+    }
+
+    #[test]
+    fn test_bls12_381_g2_msm_not_in_subgroup() {
+        let crypto = OpenVmCrypto::default();
+        let mut one = [0u8; BLS_SCALAR_LEN];
+        one[BLS_SCALAR_LEN - 1] = 1;
+        let msm = |pairs: Vec<BlsG2PointScalar>| {
+            crypto.bls12_381_g2_msm(&mut pairs.into_iter().map(Ok::<_, PrecompileError>))
+        };
+
+        assert_eq!(msm(vec![(bls_g2(&BLS_G2), one)]).unwrap(), BLS_G2);
+        // on the curve, but not in the prime-order subgroup
+        let g2_not_in_subgroup = bls_g2(&BLS_G2_NOT_IN_SUBGROUP);
+        assert!(msm(vec![(g2_not_in_subgroup, one)]).is_err());
+        // the subgroup check applies even when the scalar is zero
+        assert!(
+            msm(vec![(bls_g2(&BLS_G2), one), (g2_not_in_subgroup, [0u8; BLS_SCALAR_LEN])]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_bls12_381_g1_add_invalid() {
+        let crypto = OpenVmCrypto::default();
+        let g1 = bls_g1(&BLS_G1);
+
+        // coordinate equal to the field modulus is not canonical
+        let non_canonical = (BLS_P, g1.1);
+        assert!(crypto.bls12_381_g1_add(non_canonical, g1).is_err());
+
+        // (1, 1) is not on the curve
+        let mut one = [0u8; BLS_FP_LEN];
+        one[BLS_FP_LEN - 1] = 1;
+        assert!(crypto.bls12_381_g1_add(g1, (one, one)).is_err());
+    }
+}